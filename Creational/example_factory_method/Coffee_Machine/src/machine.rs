@@ -0,0 +1,286 @@
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ingredient {
+    Water,
+    Beans,
+    Milk,
+}
+
+impl fmt::Display for Ingredient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ingredient::Water => write!(f, "water"),
+            Ingredient::Beans => write!(f, "beans"),
+            Ingredient::Milk => write!(f, "milk"),
+        }
+    }
+}
+
+// What one cup needs; water and milk in ml, beans in grams, and how long a station is busy with it
+#[derive(Debug, Clone)]
+pub struct Recipe {
+    pub name: &'static str,
+    pub water_ml: u32,
+    pub beans_g: u32,
+    pub milk_ml: u32,
+    pub temperature_c: u32,
+    pub brew_secs: u64,
+    pub steps: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inventory {
+    pub water_ml: u32,
+    pub beans_g: u32,
+    pub milk_ml: u32,
+}
+
+impl Inventory {
+    pub fn amount(&self, ingredient: Ingredient) -> u32 {
+        match ingredient {
+            Ingredient::Water => self.water_ml,
+            Ingredient::Beans => self.beans_g,
+            Ingredient::Milk => self.milk_ml,
+        }
+    }
+
+    fn amount_mut(&mut self, ingredient: Ingredient) -> &mut u32 {
+        match ingredient {
+            Ingredient::Water => &mut self.water_ml,
+            Ingredient::Beans => &mut self.beans_g,
+            Ingredient::Milk => &mut self.milk_ml,
+        }
+    }
+
+    fn check(&self, recipe: &Recipe) -> Result<(), BrewError> {
+        if self.water_ml < recipe.water_ml {
+            return Err(BrewError::OutOfWater {
+                needed: recipe.water_ml,
+                available: self.water_ml,
+            });
+        }
+        if self.beans_g < recipe.beans_g {
+            return Err(BrewError::OutOfBeans {
+                needed: recipe.beans_g,
+                available: self.beans_g,
+            });
+        }
+        if self.milk_ml < recipe.milk_ml {
+            return Err(BrewError::OutOfMilk {
+                needed: recipe.milk_ml,
+                available: self.milk_ml,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "water {} ml, beans {} g, milk {} ml",
+            self.water_ml, self.beans_g, self.milk_ml
+        )
+    }
+}
+
+// The variants are named after the ingredient that ran out
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrewError {
+    OutOfWater { needed: u32, available: u32 },
+    OutOfBeans { needed: u32, available: u32 },
+    OutOfMilk { needed: u32, available: u32 },
+}
+
+impl fmt::Display for BrewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (ingredient, unit, needed, available) = match self {
+            BrewError::OutOfWater { needed, available } => ("water", "ml", needed, available),
+            BrewError::OutOfBeans { needed, available } => ("beans", "g", needed, available),
+            BrewError::OutOfMilk { needed, available } => ("milk", "ml", needed, available),
+        };
+        write!(
+            f,
+            "out of {}: needs {} {}, {} {} left",
+            ingredient, needed, unit, available, unit
+        )
+    }
+}
+
+impl std::error::Error for BrewError {}
+
+// A finished drink
+#[derive(Debug)]
+pub struct Cup {
+    pub name: &'static str,
+    pub volume_ml: u32,
+    pub temperature_c: u32,
+}
+
+// Holds the ingredient containers. All reads and updates go through one mutex, so a recipe is
+// either deducted completely or not at all, even with several drinks brewed at once
+pub struct CoffeeMachine {
+    capacity: Inventory,
+    inventory: Mutex<Inventory>,
+}
+
+impl CoffeeMachine {
+    // Starts with full containers
+    pub fn new(capacity: Inventory) -> Self {
+        CoffeeMachine {
+            capacity,
+            inventory: Mutex::new(capacity),
+        }
+    }
+
+    pub fn inventory(&self) -> Inventory {
+        *self.inventory.lock().unwrap()
+    }
+
+    pub fn can_make(&self, recipe: &Recipe) -> Result<(), BrewError> {
+        self.inventory.lock().unwrap().check(recipe)
+    }
+
+    // Adds up to `amount`, capped at the container size; returns how much was actually added
+    pub fn refill(&self, ingredient: Ingredient, amount: u32) -> u32 {
+        let capacity = self.capacity.amount(ingredient);
+        let mut inventory = self.inventory.lock().unwrap();
+        let current = inventory.amount_mut(ingredient);
+        let added = amount.min(capacity - *current);
+        *current += added;
+        added
+    }
+
+    // Checks and deducts every ingredient under the same lock
+    pub fn dispense(&self, recipe: &Recipe) -> Result<Cup, BrewError> {
+        let mut inventory = self.inventory.lock().unwrap();
+        inventory.check(recipe)?;
+        inventory.water_ml -= recipe.water_ml;
+        inventory.beans_g -= recipe.beans_g;
+        inventory.milk_ml -= recipe.milk_ml;
+        Ok(Cup {
+            name: recipe.name,
+            volume_ml: recipe.water_ml + recipe.milk_ml,
+            temperature_c: recipe.temperature_c,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    static FLAT_WHITE: Recipe = Recipe {
+        name: "Flat white",
+        water_ml: 40,
+        beans_g: 18,
+        milk_ml: 110,
+        temperature_c: 62,
+        brew_secs: 60,
+        steps: &[],
+    };
+
+    fn machine(water_ml: u32, beans_g: u32, milk_ml: u32) -> CoffeeMachine {
+        CoffeeMachine::new(Inventory {
+            water_ml,
+            beans_g,
+            milk_ml,
+        })
+    }
+
+    #[test]
+    fn dispense_deducts_the_recipe() {
+        let machine = machine(1000, 100, 500);
+        let cup = machine.dispense(&FLAT_WHITE).unwrap();
+        assert_eq!((cup.name, cup.volume_ml), ("Flat white", 150));
+        assert_eq!(
+            machine.inventory(),
+            Inventory {
+                water_ml: 960,
+                beans_g: 82,
+                milk_ml: 390,
+            }
+        );
+    }
+
+    #[test]
+    fn out_of_milk_leaves_every_ingredient_untouched() {
+        let machine = machine(1000, 100, 100);
+        let before = machine.inventory();
+        assert_eq!(
+            machine.dispense(&FLAT_WHITE).unwrap_err(),
+            BrewError::OutOfMilk {
+                needed: 110,
+                available: 100,
+            }
+        );
+        // Water and beans were checked first and would have sufficed
+        assert_eq!(machine.inventory(), before);
+    }
+
+    #[test]
+    fn out_of_beans_leaves_every_ingredient_untouched() {
+        let machine = machine(1000, 10, 500);
+        let before = machine.inventory();
+        assert_eq!(
+            machine.can_make(&FLAT_WHITE),
+            Err(BrewError::OutOfBeans {
+                needed: 18,
+                available: 10,
+            })
+        );
+        assert!(machine.dispense(&FLAT_WHITE).is_err());
+        assert_eq!(machine.inventory(), before);
+        assert_eq!(
+            machine.dispense(&FLAT_WHITE).unwrap_err().to_string(),
+            "out of beans: needs 18 g, 10 g left"
+        );
+    }
+
+    #[test]
+    fn refill_is_capped_at_capacity() {
+        let machine = machine(1000, 100, 500);
+        machine.dispense(&FLAT_WHITE).unwrap();
+        assert_eq!(machine.refill(Ingredient::Milk, 1000), 110);
+        assert_eq!(machine.refill(Ingredient::Milk, 1000), 0);
+        assert_eq!(machine.refill(Ingredient::Beans, 5), 5);
+        assert_eq!(machine.inventory().beans_g, 87);
+    }
+
+    #[test]
+    fn concurrent_brews_never_overdraw() {
+        // Enough milk for exactly 9 cups, and plenty of everything else
+        let machine = machine(100_000, 100_000, 9 * 110 + 50);
+        let served = AtomicUsize::new(0);
+        let rejected = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..5 {
+                        match machine.dispense(&FLAT_WHITE) {
+                            Ok(_) => served.fetch_add(1, Ordering::SeqCst),
+                            Err(_) => rejected.fetch_add(1, Ordering::SeqCst),
+                        };
+                    }
+                });
+            }
+        });
+        assert_eq!(served.into_inner(), 9);
+        assert_eq!(rejected.into_inner(), 31);
+        // Every ingredient was deducted for the same 9 cups, never for a rejected one
+        assert_eq!(
+            machine.inventory(),
+            Inventory {
+                water_ml: 100_000 - 9 * 40,
+                beans_g: 100_000 - 9 * 18,
+                milk_ml: 50,
+            }
+        );
+    }
+}
@@ -1,23 +1,90 @@
+mod machine;
+mod menu;
+mod plugin;
+mod simulation;
+
+use machine::{BrewError, CoffeeMachine, Cup, Ingredient, Inventory, Recipe};
+use menu::Menu;
+use simulation::Workload;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::sync::mpsc;
+
+static ESPRESSO: Recipe = Recipe {
+    name: "Espresso",
+    water_ml: 40,
+    beans_g: 18,
+    milk_ml: 0,
+    temperature_c: 92,
+    brew_secs: 30,
+    steps: &["Grind 18 g of beans", "Tamp", "Extract 40 ml for 25 s"],
+};
+
+static CAPPUCCINO: Recipe = Recipe {
+    name: "Cappuccino",
+    water_ml: 40,
+    beans_g: 18,
+    milk_ml: 120,
+    temperature_c: 65,
+    brew_secs: 75,
+    steps: &[
+        "Grind 18 g of beans",
+        "Tamp",
+        "Extract 40 ml for 25 s",
+        "Steam 120 ml of milk into thick foam",
+        "Pour the foam over the shot",
+    ],
+};
+
+static LATTE: Recipe = Recipe {
+    name: "Latte",
+    water_ml: 40,
+    beans_g: 18,
+    milk_ml: 200,
+    temperature_c: 60,
+    brew_secs: 90,
+    steps: &[
+        "Grind 18 g of beans",
+        "Tamp",
+        "Extract 40 ml for 25 s",
+        "Steam 200 ml of milk with a thin layer of foam",
+        "Pour the milk into the shot",
+    ],
+};
+
 // Define the Product trait
 trait Coffee {
-    fn brew(&self);
+    fn recipe(&self) -> &Recipe;
+
+    // Deducts the recipe from the machine's inventory, then runs its steps
+    fn brew(&self, machine: &CoffeeMachine) -> Result<Cup, BrewError> {
+        let recipe = self.recipe();
+        let cup = machine.dispense(recipe)?;
+        println!("Brewing {} at {}°C", recipe.name, recipe.temperature_c);
+        for step in recipe.steps {
+            println!("  - {}", step);
+        }
+        Ok(cup)
+    }
 }
 
 // ConcreteProduct: Espresso
 struct Espresso;
 
 impl Coffee for Espresso {
-    fn brew(&self) {
-        println!("Brewing Espresso");
+    fn recipe(&self) -> &Recipe {
+        &ESPRESSO
     }
 }
 
-// ConcreteProduct: Cappuccino 
+// ConcreteProduct: Cappuccino
 struct Cappuccino;
 
 impl Coffee for Cappuccino {
-    fn brew(&self) {
-        println!("Brewing Cappuccino");
+    fn recipe(&self) -> &Recipe {
+        &CAPPUCCINO
     }
 }
 
@@ -25,53 +92,233 @@ impl Coffee for Cappuccino {
 struct Latte;
 
 impl Coffee for Latte {
-    fn brew(&self) {
-        println!("Brewing Latte");
+    fn recipe(&self) -> &Recipe {
+        &LATTE
     }
 }
 
-// Define the Creator trait
-trait CoffeeFactory {
-    fn create_coffee(&self) -> Box<dyn Coffee>;
+// Define the Creator trait: a drink is only created if the machine can make it right now.
+// Factories are shared by the simulated brewing stations, hence `Sync`
+trait CoffeeFactory: Sync {
+    fn create_coffee(&self, machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError>;
 }
 
 // ConcreteCreator: EspressoFactory
 struct EspressoFactory;
 
 impl CoffeeFactory for EspressoFactory {
-    fn create_coffee(&self) -> Box<dyn Coffee> {
-        Box::new(Espresso)
+    fn create_coffee(&self, machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError> {
+        machine.can_make(&ESPRESSO)?;
+        Ok(Box::new(Espresso))
     }
 }
 
-
 // ConcreteCreator: CappuccinoFactory
 struct CappuccinoFactory;
 
 impl CoffeeFactory for CappuccinoFactory {
-    fn create_coffee(&self) -> Box<dyn Coffee> {
-        Box::new(Cappuccino)
+    fn create_coffee(&self, machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError> {
+        machine.can_make(&CAPPUCCINO)?;
+        Ok(Box::new(Cappuccino))
     }
 }
 
 // ConcreteCreator: LatteFactory
-struct LatteFactory;    
+struct LatteFactory;
+
 impl CoffeeFactory for LatteFactory {
-    fn create_coffee(&self) -> Box<dyn Coffee> {
-        Box::new(Latte)
+    fn create_coffee(&self, machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError> {
+        machine.can_make(&LATTE)?;
+        Ok(Box::new(Latte))
+    }
+}
+
+// The built-in drinks; `plugin::register` adds the rest
+fn default_menu() -> Menu {
+    let mut menu = Menu::default();
+    menu.add("espresso", Box::new(EspressoFactory)).unwrap();
+    menu.add("cappuccino", Box::new(CappuccinoFactory)).unwrap();
+    menu.add("latte", Box::new(LatteFactory)).unwrap();
+    menu
+}
+
+// The value of `key = value` in a config file. Blank lines and lines starting with `#` are
+// skipped, the first line with the key wins and a value may be wrapped in double quotes
+fn config_value(contents: &str, key: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value)
+                .to_string()
+        })
+}
+
+// The `drink = <name>` line of a machine config
+fn configured_drink(path: &str) -> io::Result<Option<String>> {
+    Ok(config_value(&fs::read_to_string(path)?, "drink"))
+}
+
+fn order(factory: &dyn CoffeeFactory, machine: &CoffeeMachine) {
+    match factory
+        .create_coffee(machine)
+        .and_then(|coffee| coffee.brew(machine))
+    {
+        Ok(cup) => println!(
+            "Served {} ({} ml, {}°C)",
+            cup.name, cup.volume_ml, cup.temperature_c
+        ),
+        Err(e) => println!("Order rejected: {}", e),
     }
+    println!("Inventory: {}", machine.inventory());
 }
 
+// Usage: Coffee_Machine [drink...] | --config <file>
 fn main() {
-    let espresso_factory = EspressoFactory;
-    let cappuccino_factory = CappuccinoFactory;
-    let latte_factory = LatteFactory;
+    let mut menu = default_menu();
+    plugin::register(&mut menu).unwrap();
+
+    let machine = CoffeeMachine::new(Inventory {
+        water_ml: 1000,
+        beans_g: 80,
+        milk_ml: 300,
+    });
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let names = match args.as_slice() {
+        [] => {
+            demo(&menu, &machine);
+            return simulate(&menu);
+        }
+        [flag, path] if flag == "--config" => match configured_drink(path) {
+            Ok(Some(drink)) => vec![drink],
+            Ok(None) => {
+                eprintln!("{}: missing `drink = <name>`", path);
+                process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(2);
+            }
+        },
+        names => names.to_vec(),
+    };
+    for name in names {
+        match menu.get(&name) {
+            Ok(factory) => order(factory, &machine),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+}
+
+fn demo(menu: &Menu, machine: &CoffeeMachine) {
+    println!("Menu: {}", menu.drinks().join(", "));
+    let espresso_factory = menu.get("espresso").unwrap();
+    let cappuccino_factory = menu.get("cappuccino").unwrap();
+    let latte_factory = menu.get("Latte").unwrap();
+
+    order(espresso_factory, machine);
+    order(cappuccino_factory, machine);
+    // Only 180 ml of milk left
+    order(latte_factory, machine);
+
+    let added = machine.refill(Ingredient::Milk, 500);
+    println!(
+        "Refilled {} ml of {} (container holds 300 ml)",
+        added,
+        Ingredient::Milk
+    );
+    order(latte_factory, machine);
+
+    // 26 g of beans left: one more espresso, then the grinder runs dry
+    order(espresso_factory, machine);
+    order(espresso_factory, machine);
+
+    for ingredient in [Ingredient::Water, Ingredient::Beans, Ingredient::Milk] {
+        let added = machine.refill(ingredient, 1000);
+        println!("Refilled {}: +{}", ingredient, added);
+    }
+    println!("Inventory: {}", machine.inventory());
+
+    // A drink created while the machine could make it still fails cleanly if the
+    // inventory ran out before it was brewed
+    let first = latte_factory.create_coffee(machine).unwrap();
+    let second = cappuccino_factory.create_coffee(machine).unwrap();
+    first.brew(machine).unwrap();
+    if let Err(e) = second.brew(machine) {
+        println!("Second drink failed: {}", e);
+    }
+    println!("Inventory: {}", machine.inventory());
 
-    let espresso = espresso_factory.create_coffee();
-    let cappuccino = cappuccino_factory.create_coffee();
-    let latte = latte_factory.create_coffee();
+    // Drinks from a plugin are ordered like any other; unknown names list the menu
+    machine.refill(Ingredient::Milk, 300);
+    order(menu.get("mocha").unwrap(), machine);
+    if let Err(e) = menu.get("flat white") {
+        println!("{}", e);
+    }
+}
+
+// Capacity planning: the same morning rush (seeded, so every run is identical) served by
+// one to four stations
+fn simulate(menu: &Menu) {
+    let workload = Workload {
+        orders: 120,
+        mean_interarrival_secs: 35.0,
+        menu: vec![
+            ("espresso", 3),
+            ("cappuccino", 4),
+            ("latte", 4),
+            ("mocha", 1),
+        ],
+        seed: 2026,
+    };
+    let cafe_machine = || {
+        CoffeeMachine::new(Inventory {
+            water_ml: 5000,
+            beans_g: 500,
+            milk_ml: 4000,
+        })
+    };
+
+    println!("\nMorning rush, two stations:");
+    let (notify, counter) = mpsc::channel();
+    let report = simulation::run(menu, &cafe_machine(), 2, workload.generate(), Some(notify));
+    for notice in counter.iter().take(5) {
+        println!("{}", notice);
+    }
+    println!("...");
+    println!("{}", report);
 
-    espresso.brew();
-    cappuccino.brew();
-    latte.brew();
+    println!("\nCapacity planning:");
+    for stations in 1..=4 {
+        let report = simulation::run(menu, &cafe_machine(), stations, workload.generate(), None);
+        println!("{}", report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_value_skips_comments_and_strips_quotes() {
+        let contents = "# drink = commented\n\n  drink = \"quoted\"  \ndrink = second\n";
+        assert_eq!(config_value(contents, "drink"), Some("quoted".to_string()));
+        assert_eq!(
+            config_value("drink=bare", "drink"),
+            Some("bare".to_string())
+        );
+        assert_eq!(config_value("drinks = other", "drink"), None);
+        assert_eq!(config_value("", "drink"), None);
+    }
 }
@@ -0,0 +1,105 @@
+use crate::CoffeeFactory;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuError {
+    NotOnMenu { drink: String, menu: Vec<String> },
+    AlreadyOnMenu(String),
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MenuError::NotOnMenu { drink, menu } => {
+                write!(
+                    f,
+                    "no `{}` on the menu; we serve {}",
+                    drink,
+                    menu.join(", ")
+                )
+            }
+            MenuError::AlreadyOnMenu(drink) => write!(f, "`{}` is already on the menu", drink),
+        }
+    }
+}
+
+impl std::error::Error for MenuError {}
+
+// The drinks the machine can be asked for, each with the factory that makes it. Orders come
+// from customers, so "Latte" and " latte " find the same drink
+#[derive(Default)]
+pub struct Menu {
+    drinks: BTreeMap<String, Box<dyn CoffeeFactory>>,
+}
+
+impl Menu {
+    // Refuses to replace a drink, so an extra module cannot silently change a built-in recipe
+    pub fn add(&mut self, drink: &str, factory: Box<dyn CoffeeFactory>) -> Result<(), MenuError> {
+        let key = drink.trim().to_ascii_lowercase();
+        if self.drinks.contains_key(&key) {
+            return Err(MenuError::AlreadyOnMenu(key));
+        }
+        self.drinks.insert(key, factory);
+        Ok(())
+    }
+
+    pub fn get(&self, drink: &str) -> Result<&dyn CoffeeFactory, MenuError> {
+        self.drinks
+            .get(&drink.trim().to_ascii_lowercase())
+            .map(|factory| &**factory)
+            .ok_or_else(|| MenuError::NotOnMenu {
+                drink: drink.to_string(),
+                menu: self.drinks(),
+            })
+    }
+
+    pub fn drinks(&self) -> Vec<String> {
+        self.drinks.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_menu, plugin, LatteFactory};
+
+    #[test]
+    fn drinks_are_found_ignoring_case() {
+        let menu = default_menu();
+        assert!(menu.get("Latte").is_ok());
+        assert!(menu.get(" ESPRESSO ").is_ok());
+        assert_eq!(menu.drinks(), ["cappuccino", "espresso", "latte"]);
+    }
+
+    #[test]
+    fn a_drink_cannot_be_added_twice() {
+        let mut menu = default_menu();
+        assert_eq!(
+            menu.add(" Latte", Box::new(LatteFactory)).err(),
+            Some(MenuError::AlreadyOnMenu("latte".to_string()))
+        );
+        plugin::register(&mut menu).unwrap();
+        assert!(plugin::register(&mut menu).is_err());
+    }
+
+    #[test]
+    fn unknown_drinks_list_the_menu() {
+        let e = default_menu().get("tea").err().unwrap();
+        assert_eq!(
+            e,
+            MenuError::NotOnMenu {
+                drink: "tea".to_string(),
+                menu: vec![
+                    "cappuccino".to_string(),
+                    "espresso".to_string(),
+                    "latte".to_string()
+                ],
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "no `tea` on the menu; we serve cappuccino, espresso, latte"
+        );
+    }
+}
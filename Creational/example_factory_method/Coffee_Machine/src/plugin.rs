@@ -0,0 +1,43 @@
+use crate::machine::{BrewError, CoffeeMachine, Recipe};
+use crate::menu::{Menu, MenuError};
+use crate::{Coffee, CoffeeFactory};
+
+// A seasonal drink kept out of the core recipes. It is compiled in like any other module, but
+// only needs `Coffee`, `CoffeeFactory` and `Menu::add`; `main` just calls `register` once
+
+static MOCHA: Recipe = Recipe {
+    name: "Mocha",
+    water_ml: 40,
+    beans_g: 18,
+    milk_ml: 150,
+    temperature_c: 62,
+    brew_secs: 100,
+    steps: &[
+        "Grind 18 g of beans",
+        "Tamp",
+        "Extract 40 ml for 25 s",
+        "Stir in 20 g of chocolate",
+        "Steam 150 ml of milk and pour",
+    ],
+};
+
+struct Mocha;
+
+impl Coffee for Mocha {
+    fn recipe(&self) -> &Recipe {
+        &MOCHA
+    }
+}
+
+struct MochaFactory;
+
+impl CoffeeFactory for MochaFactory {
+    fn create_coffee(&self, machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError> {
+        machine.can_make(&MOCHA)?;
+        Ok(Box::new(Mocha))
+    }
+}
+
+pub fn register(menu: &mut Menu) -> Result<(), MenuError> {
+    menu.add("mocha", Box::new(MochaFactory))
+}
@@ -0,0 +1,484 @@
+use crate::machine::{CoffeeMachine, Ingredient};
+use crate::menu::Menu;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::mpsc::Sender;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+// Time a station spends restocking the shared containers when an order cannot be made
+pub const RESTOCK_SECS: u64 = 90;
+
+// Simulated time in seconds. Every station runs on its own thread, but only one of them holds
+// the clock at a time: a station works until it sleeps, then the clock jumps to the earliest
+// pending wake-up (ties go to the lowest station id). Runs are therefore fully reproducible,
+// and an hour of café traffic takes milliseconds
+pub struct SimClock {
+    state: Mutex<ClockState>,
+    turn: Condvar,
+}
+
+struct ClockState {
+    now: u64,
+    running: Option<usize>,
+    pending: BTreeSet<(u64, usize)>,
+}
+
+impl SimClock {
+    pub fn new(participants: usize) -> Self {
+        SimClock {
+            state: Mutex::new(ClockState {
+                now: 0,
+                running: None,
+                pending: (0..participants).map(|id| (0, id)).collect(),
+            }),
+            turn: Condvar::new(),
+        }
+    }
+
+    fn dispatch(&self, state: &mut ClockState) {
+        match state.pending.pop_first() {
+            Some((at, id)) => {
+                state.now = at;
+                state.running = Some(id);
+            }
+            None => state.running = None,
+        }
+        self.turn.notify_all();
+    }
+
+    pub fn start(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.dispatch(&mut state);
+    }
+
+    // Blocks until it is `id`'s turn; returns the current time
+    pub fn wait_turn(&self, id: usize) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        while state.running != Some(id) {
+            state = self.turn.wait(state).unwrap();
+        }
+        state.now
+    }
+
+    pub fn sleep_until(&self, id: usize, at: u64) -> u64 {
+        {
+            let mut state = self.state.lock().unwrap();
+            let at = at.max(state.now);
+            state.pending.insert((at, id));
+            self.dispatch(&mut state);
+        }
+        self.wait_turn(id)
+    }
+
+    // The station holding the clock leaves the simulation
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.dispatch(&mut state);
+    }
+}
+
+// Calls `finish` when a station's thread ends, even by panicking, so the other stations are
+// not left in `wait_turn` forever
+struct Departure<'a>(&'a SimClock);
+
+impl Drop for Departure<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+// 00:04:05
+pub fn clock_time(secs: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct Order {
+    pub id: usize,
+    pub drink: String,
+    pub arrival: u64,
+}
+
+// Random orders from a fixed seed: exponential inter-arrival times and a weighted menu
+pub struct Workload {
+    pub orders: usize,
+    pub mean_interarrival_secs: f64,
+    pub menu: Vec<(&'static str, u32)>,
+    pub seed: u64,
+}
+
+impl Workload {
+    pub fn generate(&self) -> Vec<Order> {
+        let mut rng = XorShift(self.seed.max(1));
+        let total_weight: u32 = self.menu.iter().map(|(_, weight)| weight).sum();
+        let mut arrival = 0.0;
+        (1..=self.orders)
+            .map(|id| {
+                arrival += -(1.0 - rng.next_f64()).ln() * self.mean_interarrival_secs;
+                let mut pick = (rng.next_f64() * total_weight as f64) as u32;
+                let drink = self
+                    .menu
+                    .iter()
+                    .find(|(_, weight)| {
+                        if pick < *weight {
+                            true
+                        } else {
+                            pick -= weight;
+                            false
+                        }
+                    })
+                    .map(|(drink, _)| drink.to_string())
+                    .unwrap_or_default();
+                Order {
+                    id,
+                    drink,
+                    arrival: arrival as u64,
+                }
+            })
+            .collect()
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Sent to the counter as orders complete
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    Ready {
+        order: usize,
+        drink: String,
+        station: usize,
+        at: u64,
+        waited: u64,
+    },
+    Rejected {
+        order: usize,
+        drink: String,
+        at: u64,
+        reason: String,
+    },
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Notice::Ready {
+                order,
+                drink,
+                station,
+                at,
+                waited,
+            } => write!(
+                f,
+                "[{}] Order #{} ({}) ready at station {}, waited {}s",
+                clock_time(*at),
+                order,
+                drink,
+                station,
+                waited
+            ),
+            Notice::Rejected {
+                order,
+                drink,
+                at,
+                reason,
+            } => write!(
+                f,
+                "[{}] Order #{} ({}) rejected: {}",
+                clock_time(*at),
+                order,
+                drink,
+                reason
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub stations: usize,
+    pub served: usize,
+    pub rejected: usize,
+    pub restocks: usize,
+    pub makespan: u64,
+    // Seconds between arrival and a station starting on the order, per served order
+    pub waits: Vec<u64>,
+    pub busy: Vec<u64>,
+}
+
+impl Report {
+    pub fn throughput_per_hour(&self) -> f64 {
+        if self.makespan == 0 {
+            return 0.0;
+        }
+        self.served as f64 * 3600.0 / self.makespan as f64
+    }
+
+    pub fn average_wait(&self) -> f64 {
+        if self.waits.is_empty() {
+            return 0.0;
+        }
+        self.waits.iter().sum::<u64>() as f64 / self.waits.len() as f64
+    }
+
+    pub fn percentile_wait(&self, percentile: f64) -> u64 {
+        let mut waits = self.waits.clone();
+        waits.sort_unstable();
+        let rank = ((percentile / 100.0) * waits.len() as f64).ceil() as usize;
+        waits
+            .get(rank.saturating_sub(1).min(waits.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn utilization(&self) -> f64 {
+        if self.makespan == 0 || self.stations == 0 {
+            return 0.0;
+        }
+        self.busy.iter().sum::<u64>() as f64 / (self.makespan * self.stations as u64) as f64
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} station(s): {} served, {} rejected, {} restock(s) in {} | {:.1} drinks/h | \
+             wait avg {:.0}s, p95 {}s, max {}s | utilization {:.0}%",
+            self.stations,
+            self.served,
+            self.rejected,
+            self.restocks,
+            clock_time(self.makespan),
+            self.throughput_per_hour(),
+            self.average_wait(),
+            self.percentile_wait(95.0),
+            self.percentile_wait(100.0),
+            self.utilization() * 100.0
+        )
+    }
+}
+
+// Runs `orders` through `stations` brewing stations that share one machine. Each station takes
+// the oldest order that has arrived, creates the drink through the menu, deducts its recipe
+// and is busy for the recipe's brew time; if an ingredient runs out it restocks first
+pub fn run(
+    menu: &Menu,
+    machine: &CoffeeMachine,
+    stations: usize,
+    orders: Vec<Order>,
+    notify: Option<Sender<Notice>>,
+) -> Report {
+    let clock = SimClock::new(stations);
+    let queue = Mutex::new(VecDeque::from(orders));
+    let report = Mutex::new(Report {
+        stations,
+        busy: vec![0; stations],
+        ..Report::default()
+    });
+
+    clock.start();
+    thread::scope(|scope| {
+        for station in 0..stations {
+            let (clock, queue, report, notify) = (&clock, &queue, &report, notify.clone());
+            scope.spawn(move || {
+                let mut now = clock.wait_turn(station);
+                let _departure = Departure(clock);
+                loop {
+                    let next = {
+                        let mut queue = queue.lock().unwrap();
+                        match queue.front() {
+                            Some(order) if order.arrival <= now => Ok(queue.pop_front().unwrap()),
+                            Some(order) => Err(Some(order.arrival)),
+                            None => Err(None),
+                        }
+                    };
+                    let order = match next {
+                        Ok(order) => order,
+                        Err(Some(arrival)) => {
+                            now = clock.sleep_until(station, arrival);
+                            continue;
+                        }
+                        Err(None) => break,
+                    };
+
+                    let mut busy = 0;
+                    let mut restocked = false;
+                    let coffee =
+                        menu.get(&order.drink)
+                            .map_err(|e| e.to_string())
+                            .and_then(|factory| {
+                                factory.create_coffee(machine).or_else(|_| {
+                                    for ingredient in
+                                        [Ingredient::Water, Ingredient::Beans, Ingredient::Milk]
+                                    {
+                                        machine.refill(ingredient, u32::MAX);
+                                    }
+                                    restocked = true;
+                                    busy += RESTOCK_SECS;
+                                    factory.create_coffee(machine).map_err(|e| e.to_string())
+                                })
+                            });
+                    let cup = coffee.and_then(|coffee| {
+                        let recipe = coffee.recipe();
+                        busy += recipe.brew_secs;
+                        machine.dispense(recipe).map_err(|e| e.to_string())
+                    });
+
+                    let started = now;
+                    now = clock.sleep_until(station, now + busy);
+                    let mut report = report.lock().unwrap();
+                    report.busy[station] += busy;
+                    report.makespan = report.makespan.max(now);
+                    if restocked {
+                        report.restocks += 1;
+                    }
+                    let notice = match cup {
+                        Ok(_) => {
+                            report.served += 1;
+                            report.waits.push(started - order.arrival);
+                            Notice::Ready {
+                                order: order.id,
+                                drink: order.drink,
+                                station: station + 1,
+                                at: now,
+                                waited: started - order.arrival,
+                            }
+                        }
+                        Err(reason) => {
+                            report.rejected += 1;
+                            Notice::Rejected {
+                                order: order.id,
+                                drink: order.drink,
+                                at: now,
+                                reason,
+                            }
+                        }
+                    };
+                    if let Some(notify) = &notify {
+                        let _ = notify.send(notice);
+                    }
+                }
+            });
+        }
+    });
+
+    report.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::{BrewError, Inventory};
+    use crate::{default_menu, Coffee, CoffeeFactory};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn workload(seed: u64) -> Workload {
+        Workload {
+            orders: 60,
+            mean_interarrival_secs: 20.0,
+            menu: vec![("espresso", 3), ("cappuccino", 4), ("latte", 4), ("tea", 1)],
+            seed,
+        }
+    }
+
+    // Small enough that the stations have to restock during the run
+    fn machine() -> CoffeeMachine {
+        CoffeeMachine::new(Inventory {
+            water_ml: 1000,
+            beans_g: 200,
+            milk_ml: 1000,
+        })
+    }
+
+    fn run_with_notices(seed: u64, stations: usize) -> (Report, Vec<Notice>) {
+        let (notify, counter) = mpsc::channel();
+        let report = run(
+            &default_menu(),
+            &machine(),
+            stations,
+            workload(seed).generate(),
+            Some(notify),
+        );
+        (report, counter.iter().collect())
+    }
+
+    #[test]
+    fn same_seed_gives_identical_runs() {
+        let first = run_with_notices(7, 3);
+        let (report, notices) = &first;
+        assert_eq!(report.served + report.rejected, 60);
+        assert_eq!(notices.len(), 60);
+        assert!(report.restocks > 0);
+        assert!(report.rejected > 0, "`tea` is not on the menu");
+        for _ in 0..5 {
+            assert_eq!(run_with_notices(7, 3), first);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_runs() {
+        assert_ne!(run_with_notices(7, 2), run_with_notices(8, 2));
+    }
+
+    struct Jammed;
+
+    impl CoffeeFactory for Jammed {
+        fn create_coffee(&self, _machine: &CoffeeMachine) -> Result<Box<dyn Coffee>, BrewError> {
+            panic!("grinder jammed");
+        }
+    }
+
+    #[test]
+    fn panicking_station_does_not_stall_the_others() {
+        let (done, finished) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut menu = default_menu();
+            menu.add("jammed", Box::new(Jammed)).unwrap();
+            let orders = vec![
+                Order {
+                    id: 1,
+                    drink: "espresso".to_string(),
+                    arrival: 0,
+                },
+                Order {
+                    id: 2,
+                    drink: "jammed".to_string(),
+                    arrival: 0,
+                },
+                Order {
+                    id: 3,
+                    drink: "espresso".to_string(),
+                    arrival: 100,
+                },
+                Order {
+                    id: 4,
+                    drink: "latte".to_string(),
+                    arrival: 200,
+                },
+            ];
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| run(&menu, &machine(), 2, orders, None)));
+            let _ = done.send(result.is_err());
+        });
+        // Without the drop guard the other station waits for its turn forever
+        assert_eq!(finished.recv_timeout(Duration::from_secs(10)), Ok(true));
+    }
+}
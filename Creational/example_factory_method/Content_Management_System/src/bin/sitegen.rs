@@ -0,0 +1,74 @@
+use cms::site::{BuildOptions, Site};
+use std::env;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "usage: sitegen <content-dir> <output-dir> [--title <title>] \
+                     [--base-url <url>] [--drafts]";
+
+// Static site generator: Markdown files with YAML front matter go through the content
+// factories into a repository, then out through the templates
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut dirs = Vec::new();
+    let mut options = BuildOptions {
+        title: "Design Patterns".to_string(),
+        base_url: "https://example.com".to_string(),
+        drafts: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--title" | "--base-url" => {
+                let Some(value) = args.next() else {
+                    eprintln!("{} needs a value\n{}", arg, USAGE);
+                    process::exit(2);
+                };
+                if arg == "--title" {
+                    options.title = value;
+                } else {
+                    options.base_url = value;
+                }
+            }
+            "--drafts" => options.drafts = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => dirs.push(arg),
+        }
+    }
+    let [content_dir, output_dir] = dirs.as_slice() else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+
+    let report = Site::load(Path::new(content_dir))
+        .and_then(|site| {
+            println!(
+                "Loaded {} item(s), {} published",
+                site.sources.len(),
+                site.repository.list_published().count()
+            );
+            site.build(Path::new(output_dir), &options)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        });
+    for path in &report.written {
+        println!("  wrote   {}", path);
+    }
+    for path in &report.removed {
+        println!("  removed {}", path);
+    }
+    println!(
+        "{} written, {} unchanged, {} removed",
+        report.written.len(),
+        report.unchanged.len(),
+        report.removed.len()
+    );
+}
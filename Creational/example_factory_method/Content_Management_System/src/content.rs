@@ -0,0 +1,331 @@
+use crate::export::ContentVisitor;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::rc::Rc;
+
+// Define the Product trait; items are created by the factories and stored in a `Repository`.
+// Rendering is left to the visitors in `export`
+pub trait Content {
+    fn title(&self) -> &str;
+    fn accept(&self, visitor: &mut dyn ContentVisitor);
+
+    // Tags the item arrives with; the repository adds them to the entry's tags
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    // Running time in seconds; collections add up their items
+    fn duration(&self) -> u32 {
+        0
+    }
+
+    fn as_collection(&self) -> Option<&Collection> {
+        None
+    }
+}
+
+// ConcreteProduct: BlogPost
+pub struct BlogPost {
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+}
+
+impl Content for BlogPost {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_blog_post(self);
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+// ConcreteProduct: Video
+pub struct Video {
+    pub title: String,
+    pub url: String,
+    pub duration: u32,
+    pub description: String,
+}
+
+impl Content for Video {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_video(self);
+    }
+
+    fn duration(&self) -> u32 {
+        self.duration
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    pub caption: String,
+}
+
+impl Image {
+    pub fn new(url: &str, width: u32, height: u32, caption: &str) -> Self {
+        Image {
+            url: url.to_string(),
+            width,
+            height,
+            caption: caption.to_string(),
+        }
+    }
+}
+
+// ConcreteProduct: ImageGallery
+pub struct ImageGallery {
+    pub title: String,
+    pub description: String,
+    pub images: Vec<Image>,
+}
+
+impl Content for ImageGallery {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_image_gallery(self);
+    }
+}
+
+// ConcreteProduct: Podcast episode
+pub struct Podcast {
+    pub title: String,
+    pub audio_url: String,
+    pub duration: u32,
+    pub episode: u32,
+    pub show_notes: String,
+}
+
+impl Content for Podcast {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_podcast(self);
+    }
+
+    fn duration(&self) -> u32 {
+        self.duration
+    }
+}
+
+// ConcreteProduct: Page, a standalone page such as "About" served under its slug
+pub struct Page {
+    pub title: String,
+    pub slug: String,
+    pub body: String,
+}
+
+impl Content for Page {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_page(self);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionKind {
+    Series,
+    Course,
+    Module,
+    Playlist,
+}
+
+impl fmt::Display for CollectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CollectionKind::Series => "series",
+            CollectionKind::Course => "course",
+            CollectionKind::Module => "module",
+            CollectionKind::Playlist => "playlist",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Composite: an ordered group of content that is content itself, so collections nest (a course
+// of modules of posts and videos) and every visitor and exporter handles them like any item.
+// Items are shared with the repository revisions they come from
+#[derive(Clone)]
+pub struct Collection {
+    pub kind: CollectionKind,
+    pub title: String,
+    pub description: String,
+    pub items: Vec<Rc<dyn Content>>,
+}
+
+impl Collection {
+    pub fn new(kind: CollectionKind, title: &str, description: &str) -> Self {
+        Collection {
+            kind,
+            title: title.to_string(),
+            description: description.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, item: Rc<dyn Content>) {
+        self.items.push(item);
+    }
+
+    // Content items below this collection, however deeply nested
+    pub fn leaves(&self) -> Vec<&dyn Content> {
+        let mut leaves = Vec::new();
+        for item in &self.items {
+            match item.as_collection() {
+                Some(collection) => leaves.extend(collection.leaves()),
+                None => leaves.push(&**item),
+            }
+        }
+        leaves
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary {
+            duration: self.duration(),
+            ..Summary::default()
+        };
+        for item in &self.items {
+            match item.as_collection() {
+                Some(collection) => {
+                    let nested = collection.summary();
+                    summary.collections += 1 + nested.collections;
+                    summary.depth = summary.depth.max(nested.depth + 1);
+                    for (kind, count) in nested.by_type {
+                        *summary.by_type.entry(kind).or_default() += count;
+                    }
+                    summary.tags.extend(nested.tags);
+                }
+                None => {
+                    let kind = crate::export::fields(&**item)
+                        .remove("type")
+                        .unwrap_or_default();
+                    *summary.by_type.entry(kind).or_default() += 1;
+                    summary.tags.extend(item.tags().iter().cloned());
+                }
+            }
+        }
+        summary.depth = summary.depth.max(1);
+        summary
+    }
+}
+
+impl Content for Collection {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn accept(&self, visitor: &mut dyn ContentVisitor) {
+        visitor.visit_collection(self);
+    }
+
+    fn duration(&self) -> u32 {
+        self.items
+            .iter()
+            .fold(0u32, |total, item| total.saturating_add(item.duration()))
+    }
+
+    fn as_collection(&self) -> Option<&Collection> {
+        Some(self)
+    }
+}
+
+// Aggregated metadata of a collection tree
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    // Leaf items per content type, e.g. "video" -> 2
+    pub by_type: BTreeMap<String, usize>,
+    // Nested collections, not counting the root
+    pub collections: usize,
+    // Levels of nesting, 1 for a flat collection
+    pub depth: usize,
+    pub duration: u32,
+    pub tags: BTreeSet<String>,
+}
+
+impl Summary {
+    pub fn items(&self) -> usize {
+        self.by_type.values().sum()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let by_type: Vec<String> = self
+            .by_type
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        write!(f, "{} item(s)", self.items())?;
+        if !by_type.is_empty() {
+            write!(f, " ({})", by_type.join(", "))?;
+        }
+        if self.duration > 0 {
+            write!(f, ", {}", crate::export::duration(self.duration))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(secs: u32) -> Rc<dyn Content> {
+        Rc::new(Video {
+            title: "Clip".to_string(),
+            url: "https://example.com/clip".to_string(),
+            duration: secs,
+            description: String::new(),
+        })
+    }
+
+    #[test]
+    fn duration_sums_nested_collections() {
+        let mut module = Collection::new(CollectionKind::Module, "Module", "");
+        module.push(video(60));
+        module.push(video(30));
+        let mut course = Collection::new(CollectionKind::Course, "Course", "");
+        course.push(Rc::new(module));
+        course.push(video(10));
+        course.push(Rc::new(Collection::new(
+            CollectionKind::Module,
+            "Empty",
+            "",
+        )));
+
+        assert_eq!(course.duration(), 100);
+        assert_eq!(course.summary().duration, 100);
+        assert_eq!(course.leaves().len(), 3);
+    }
+
+    #[test]
+    fn duration_saturates_instead_of_overflowing() {
+        let mut inner = Collection::new(CollectionKind::Playlist, "Inner", "");
+        inner.push(video(u32::MAX));
+        let mut outer = Collection::new(CollectionKind::Playlist, "Outer", "");
+        outer.push(Rc::new(inner));
+        outer.push(video(1));
+        assert_eq!(outer.duration(), u32::MAX);
+    }
+}
@@ -0,0 +1,634 @@
+use crate::content::{BlogPost, Collection, Content, ImageGallery, Page, Podcast, Video};
+use crate::repository::{civil_from_days, Entry, Repository};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Visitor: one method per content type. A new output format is a new visitor; the content
+// types only know how to `accept` one
+pub trait ContentVisitor {
+    fn visit_blog_post(&mut self, post: &BlogPost);
+    fn visit_video(&mut self, video: &Video);
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery);
+    fn visit_podcast(&mut self, podcast: &Podcast);
+    fn visit_page(&mut self, page: &Page);
+    // Exporters render the items too, by having each one accept the exporter in turn
+    fn visit_collection(&mut self, collection: &Collection);
+}
+
+// A visitor that renders into a string
+pub trait Exporter: ContentVisitor {
+    // Returns what was rendered so far and starts over
+    fn finish(&mut self) -> String;
+}
+
+pub fn export(content: &dyn Content, exporter: &mut dyn Exporter) -> String {
+    content.accept(exporter);
+    exporter.finish()
+}
+
+// Indented tree of a collection and everything in it, with running times
+pub fn outline(content: &dyn Content) -> String {
+    fn walk(content: &dyn Content, depth: usize, out: &mut String) {
+        let kind = match content.as_collection() {
+            Some(collection) => collection.kind.to_string(),
+            None => fields(content).remove("type").unwrap_or_default(),
+        };
+        write!(out, "{}{}: {}", "  ".repeat(depth), kind, content.title()).unwrap();
+        if content.duration() > 0 {
+            write!(out, " [{}]", duration(content.duration())).unwrap();
+        }
+        out.push('\n');
+        if let Some(collection) = content.as_collection() {
+            for item in &collection.items {
+                walk(&**item, depth + 1, out);
+            }
+        }
+    }
+    let mut out = String::new();
+    walk(content, 0, &mut out);
+    out
+}
+
+// Named fields of a content item, used to diff revisions
+#[derive(Default)]
+pub struct FieldsVisitor {
+    pub fields: BTreeMap<&'static str, String>,
+}
+
+pub fn fields(content: &dyn Content) -> BTreeMap<&'static str, String> {
+    let mut visitor = FieldsVisitor::default();
+    content.accept(&mut visitor);
+    visitor.fields
+}
+
+impl ContentVisitor for FieldsVisitor {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        self.fields.insert("type", "blog post".to_string());
+        self.fields.insert("title", post.title.clone());
+        self.fields.insert("body", post.body.clone());
+        if !post.tags.is_empty() {
+            self.fields.insert("tags", post.tags.join(", "));
+        }
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        self.fields.insert("type", "video".to_string());
+        self.fields.insert("title", video.title.clone());
+        self.fields.insert("url", video.url.clone());
+        self.fields.insert("duration", video.duration.to_string());
+        self.fields.insert("description", video.description.clone());
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        self.fields.insert("type", "image gallery".to_string());
+        self.fields.insert("title", gallery.title.clone());
+        self.fields
+            .insert("description", gallery.description.clone());
+        let images: Vec<String> = gallery
+            .images
+            .iter()
+            .map(|image| {
+                format!(
+                    "{} {}x{} {}",
+                    image.url, image.width, image.height, image.caption
+                )
+            })
+            .collect();
+        self.fields.insert("images", images.join("\n"));
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        self.fields.insert("type", "podcast".to_string());
+        self.fields.insert("title", podcast.title.clone());
+        self.fields.insert("episode", podcast.episode.to_string());
+        self.fields.insert("audio url", podcast.audio_url.clone());
+        self.fields.insert("duration", podcast.duration.to_string());
+        self.fields.insert("show notes", podcast.show_notes.clone());
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        self.fields.insert("type", "page".to_string());
+        self.fields.insert("title", page.title.clone());
+        self.fields.insert("slug", page.slug.clone());
+        self.fields.insert("body", page.body.clone());
+    }
+
+    // Items by title, one per line, so reordering shows up in a diff
+    fn visit_collection(&mut self, collection: &Collection) {
+        self.fields.insert("type", "collection".to_string());
+        self.fields.insert("kind", collection.kind.to_string());
+        self.fields.insert("title", collection.title.clone());
+        self.fields
+            .insert("description", collection.description.clone());
+        let items: Vec<&str> = collection.items.iter().map(|item| item.title()).collect();
+        self.fields.insert("items", items.join("\n"));
+        self.fields
+            .insert("duration", collection.duration().to_string());
+    }
+}
+
+#[derive(Default)]
+pub struct HtmlExporter {
+    out: String,
+}
+
+impl ContentVisitor for HtmlExporter {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        write!(
+            self.out,
+            "<article class=\"post\">\n  <h1>{}</h1>\n",
+            html_escape(&post.title)
+        )
+        .unwrap();
+        self.paragraphs(&post.body);
+        if !post.tags.is_empty() {
+            let tags: Vec<String> = post
+                .tags
+                .iter()
+                .map(|tag| format!("<li>{}</li>", html_escape(tag)))
+                .collect();
+            writeln!(self.out, "  <ul class=\"tags\">{}</ul>", tags.concat()).unwrap();
+        }
+        self.out.push_str("</article>\n");
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        write!(
+            self.out,
+            "<article class=\"video\">\n  <h1>{}</h1>\n  \
+             <iframe src=\"{}\" title=\"{}\" allowfullscreen></iframe>\n  \
+             <p class=\"duration\">{}</p>\n",
+            html_escape(&video.title),
+            html_escape(&video.url),
+            html_escape(&video.title),
+            duration(video.duration)
+        )
+        .unwrap();
+        self.paragraphs(&video.description);
+        self.out.push_str("</article>\n");
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        writeln!(
+            self.out,
+            "<article class=\"gallery\">\n  <h1>{}</h1>",
+            html_escape(&gallery.title)
+        )
+        .unwrap();
+        self.paragraphs(&gallery.description);
+        for image in &gallery.images {
+            writeln!(
+                self.out,
+                "  <figure><img src=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\">\
+                 <figcaption>{}</figcaption></figure>",
+                html_escape(&image.url),
+                image.width,
+                image.height,
+                html_escape(&image.caption),
+                html_escape(&image.caption)
+            )
+            .unwrap();
+        }
+        self.out.push_str("</article>\n");
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        write!(
+            self.out,
+            "<article class=\"podcast\">\n  <h1>Episode {}: {}</h1>\n  \
+             <audio controls src=\"{}\"></audio>\n  <p class=\"duration\">{}</p>\n",
+            podcast.episode,
+            html_escape(&podcast.title),
+            html_escape(&podcast.audio_url),
+            duration(podcast.duration)
+        )
+        .unwrap();
+        self.paragraphs(&podcast.show_notes);
+        self.out.push_str("</article>\n");
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        writeln!(
+            self.out,
+            "<main class=\"page\" id=\"{}\">\n  <h1>{}</h1>",
+            html_escape(&page.slug),
+            html_escape(&page.title)
+        )
+        .unwrap();
+        self.paragraphs(&page.body);
+        self.out.push_str("</main>\n");
+    }
+
+    fn visit_collection(&mut self, collection: &Collection) {
+        write!(
+            self.out,
+            "<section class=\"collection {}\">\n  <h1>{}</h1>\n  <p class=\"summary\">{}</p>\n",
+            collection.kind,
+            html_escape(&collection.title),
+            html_escape(&collection.summary().to_string())
+        )
+        .unwrap();
+        self.paragraphs(&collection.description);
+        self.out.push_str("<ol>\n");
+        for item in &collection.items {
+            self.out.push_str("<li>\n");
+            item.accept(self);
+            self.out.push_str("</li>\n");
+        }
+        self.out.push_str("</ol>\n</section>\n");
+    }
+}
+
+impl HtmlExporter {
+    // One <p> per non-empty line
+    fn paragraphs(&mut self, text: &str) {
+        for paragraph in text.lines().filter(|line| !line.trim().is_empty()) {
+            writeln!(self.out, "  <p>{}</p>", html_escape(paragraph)).unwrap();
+        }
+    }
+}
+
+impl Exporter for HtmlExporter {
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.out)
+    }
+}
+
+#[derive(Default)]
+pub struct MarkdownExporter {
+    out: String,
+}
+
+impl ContentVisitor for MarkdownExporter {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        writeln!(self.out, "# {}\n", post.title).unwrap();
+        writeln!(self.out, "{}", post.body).unwrap();
+        if !post.tags.is_empty() {
+            let tags: Vec<String> = post.tags.iter().map(|tag| format!("`{}`", tag)).collect();
+            writeln!(self.out, "\nTags: {}", tags.join(" ")).unwrap();
+        }
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        writeln!(self.out, "# {}\n", video.title).unwrap();
+        writeln!(
+            self.out,
+            "[Watch the video]({}) ({})",
+            video.url,
+            duration(video.duration)
+        )
+        .unwrap();
+        if !video.description.is_empty() {
+            writeln!(self.out, "\n{}", video.description).unwrap();
+        }
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        writeln!(self.out, "# {}\n", gallery.title).unwrap();
+        if !gallery.description.is_empty() {
+            writeln!(self.out, "{}\n", gallery.description).unwrap();
+        }
+        for image in &gallery.images {
+            writeln!(self.out, "![{}]({})", image.caption, image.url).unwrap();
+        }
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        writeln!(
+            self.out,
+            "# Episode {}: {}\n",
+            podcast.episode, podcast.title
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "[Listen]({}) ({})",
+            podcast.audio_url,
+            duration(podcast.duration)
+        )
+        .unwrap();
+        if !podcast.show_notes.is_empty() {
+            writeln!(self.out, "\n{}", podcast.show_notes).unwrap();
+        }
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        writeln!(self.out, "# {}\n", page.title).unwrap();
+        writeln!(self.out, "{}", page.body).unwrap();
+    }
+
+    // Items follow as sections one heading level down
+    fn visit_collection(&mut self, collection: &Collection) {
+        writeln!(self.out, "# {}\n", collection.title).unwrap();
+        writeln!(self.out, "*{}: {}*", collection.kind, collection.summary()).unwrap();
+        if !collection.description.is_empty() {
+            writeln!(self.out, "\n{}", collection.description).unwrap();
+        }
+        for item in &collection.items {
+            let mut fenced = false;
+            self.out.push('\n');
+            for line in export(&**item, &mut MarkdownExporter::default()).lines() {
+                if line.starts_with("```") {
+                    fenced = !fenced;
+                }
+                if !fenced && line.starts_with('#') {
+                    self.out.push('#');
+                }
+                writeln!(self.out, "{}", line).unwrap();
+            }
+        }
+    }
+}
+
+impl Exporter for MarkdownExporter {
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.out)
+    }
+}
+
+#[derive(Default)]
+pub struct JsonExporter {
+    out: String,
+}
+
+impl ContentVisitor for JsonExporter {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        let tags: Vec<String> = post.tags.iter().map(|tag| json_string(tag)).collect();
+        write!(
+            self.out,
+            "{{\"type\":\"blog_post\",\"title\":{},\"body\":{},\"tags\":[{}]}}",
+            json_string(&post.title),
+            json_string(&post.body),
+            tags.join(",")
+        )
+        .unwrap();
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        write!(
+            self.out,
+            "{{\"type\":\"video\",\"title\":{},\"url\":{},\"duration_secs\":{},\
+             \"description\":{}}}",
+            json_string(&video.title),
+            json_string(&video.url),
+            video.duration,
+            json_string(&video.description)
+        )
+        .unwrap();
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        let images: Vec<String> = gallery
+            .images
+            .iter()
+            .map(|image| {
+                format!(
+                    "{{\"url\":{},\"width\":{},\"height\":{},\"caption\":{}}}",
+                    json_string(&image.url),
+                    image.width,
+                    image.height,
+                    json_string(&image.caption)
+                )
+            })
+            .collect();
+        write!(
+            self.out,
+            "{{\"type\":\"image_gallery\",\"title\":{},\"description\":{},\"images\":[{}]}}",
+            json_string(&gallery.title),
+            json_string(&gallery.description),
+            images.join(",")
+        )
+        .unwrap();
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        write!(
+            self.out,
+            "{{\"type\":\"podcast\",\"title\":{},\"episode\":{},\"audio_url\":{},\
+             \"duration_secs\":{},\"show_notes\":{}}}",
+            json_string(&podcast.title),
+            podcast.episode,
+            json_string(&podcast.audio_url),
+            podcast.duration,
+            json_string(&podcast.show_notes)
+        )
+        .unwrap();
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        write!(
+            self.out,
+            "{{\"type\":\"page\",\"title\":{},\"slug\":{},\"body\":{}}}",
+            json_string(&page.title),
+            json_string(&page.slug),
+            json_string(&page.body)
+        )
+        .unwrap();
+    }
+
+    fn visit_collection(&mut self, collection: &Collection) {
+        write!(
+            self.out,
+            "{{\"type\":\"collection\",\"kind\":\"{}\",\"title\":{},\"description\":{},\
+             \"duration_secs\":{},\"items\":[",
+            collection.kind,
+            json_string(&collection.title),
+            json_string(&collection.description),
+            collection.duration()
+        )
+        .unwrap();
+        for (i, item) in collection.items.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            item.accept(self);
+        }
+        self.out.push_str("]}");
+    }
+}
+
+impl Exporter for JsonExporter {
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.out)
+    }
+}
+
+// Renders one RSS 2.0 `<item>`; the link and date come from the repository, not the content
+pub struct RssItemExporter {
+    link: String,
+    published: SystemTime,
+    out: String,
+}
+
+impl RssItemExporter {
+    pub fn new(link: &str, published: SystemTime) -> Self {
+        RssItemExporter {
+            link: link.to_string(),
+            published,
+            out: String::new(),
+        }
+    }
+
+    fn item(&mut self, title: &str, description: &str) {
+        write!(
+            self.out,
+            "<item>\n  <title>{}</title>\n  <link>{}</link>\n  <guid>{}</guid>\n  \
+             <pubDate>{}</pubDate>\n  <description>{}</description>\n</item>\n",
+            html_escape(title),
+            html_escape(&self.link),
+            html_escape(&self.link),
+            rfc822(self.published),
+            html_escape(description)
+        )
+        .unwrap();
+    }
+}
+
+impl ContentVisitor for RssItemExporter {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        self.item(&post.title, &post.body);
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        let description = format!(
+            "Video, {}: {}\n{}",
+            duration(video.duration),
+            video.url,
+            video.description
+        );
+        self.item(&video.title, description.trim_end());
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        let description = format!("{} image(s)\n{}", gallery.images.len(), gallery.description);
+        self.item(&gallery.title, description.trim_end());
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        let title = format!("Episode {}: {}", podcast.episode, podcast.title);
+        self.item(&title, &podcast.show_notes);
+        // Podcast apps need the audio file as an enclosure
+        let item_end = self.out.len() - "</item>\n".len();
+        self.out.insert_str(
+            item_end,
+            &format!(
+                "  <enclosure url=\"{}\" type=\"audio/mpeg\"/>\n",
+                html_escape(&podcast.audio_url)
+            ),
+        );
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        self.item(&page.title, &page.body);
+    }
+
+    // One feed item for the whole collection, listing what is in it
+    fn visit_collection(&mut self, collection: &Collection) {
+        let mut description = format!("{}: {}\n", collection.kind, collection.summary());
+        for (i, leaf) in collection.leaves().iter().enumerate() {
+            writeln!(description, "{}. {}", i + 1, leaf.title()).unwrap();
+        }
+        description.push_str(&collection.description);
+        self.item(&collection.title, description.trim_end());
+    }
+}
+
+impl Exporter for RssItemExporter {
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.out)
+    }
+}
+
+// A complete RSS channel with one item per published entry
+pub fn rss_feed(repository: &Repository, title: &str, base_url: &str) -> String {
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n\
+         <title>{}</title>\n<link>{}</link>\n<description>{}</description>\n",
+        html_escape(title),
+        html_escape(base_url),
+        html_escape(title)
+    );
+    for entry in repository.list_published() {
+        feed.push_str(&rss_item(repository, entry, base_url));
+    }
+    feed.push_str("</channel>\n</rss>\n");
+    feed
+}
+
+fn rss_item(repository: &Repository, entry: &Entry, base_url: &str) -> String {
+    let link = format!("{}/{}", base_url.trim_end_matches('/'), entry.id);
+    let published = entry.published_at().unwrap_or_else(|| entry.state_since());
+    let mut exporter = RssItemExporter::new(&link, published);
+    // Collections go out with their members
+    let content = repository
+        .assemble(entry.id)
+        .unwrap_or_else(|_| entry.current().shared());
+    export(&*content, &mut exporter)
+}
+
+// 1:05, or 1:02:05 from an hour on
+pub fn duration(secs: u32) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+pub fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// RSS dates, e.g. "Mon, 19 Oct 2026 08:30:00 GMT"
+fn rfc822(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[((secs / 86_400) % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
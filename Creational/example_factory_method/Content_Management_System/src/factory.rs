@@ -0,0 +1,316 @@
+use crate::content::{
+    BlogPost, Collection, CollectionKind, Content, Image, ImageGallery, Page, Podcast, Video,
+};
+use std::fmt;
+
+// Everything a factory may need, built up field by field. Each factory picks the fields its
+// content type uses and rejects the request if a required one is missing or invalid
+#[derive(Debug, Clone, Default)]
+pub struct ContentRequest {
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+    pub url: Option<String>,
+    pub duration_secs: Option<u32>,
+    pub episode: Option<u32>,
+    pub images: Vec<Image>,
+    pub slug: Option<String>,
+}
+
+impl ContentRequest {
+    pub fn new(title: &str) -> Self {
+        ContentRequest {
+            title: title.to_string(),
+            ..ContentRequest::default()
+        }
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn duration(mut self, secs: u32) -> Self {
+        self.duration_secs = Some(secs);
+        self
+    }
+
+    pub fn episode(mut self, episode: u32) -> Self {
+        self.episode = Some(episode);
+        self
+    }
+
+    pub fn image(mut self, image: Image) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    pub fn slug(mut self, slug: &str) -> Self {
+        self.slug = Some(slug.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    Missing(&'static str),
+    Empty(&'static str),
+    InvalidUrl {
+        field: &'static str,
+        url: String,
+        reason: &'static str,
+    },
+    ZeroDuration,
+    ZeroEpisode,
+    InvalidDimensions {
+        url: String,
+        width: u32,
+        height: u32,
+    },
+    InvalidSlug(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Missing(field) => write!(f, "`{}` is required", field),
+            ValidationError::Empty(field) => write!(f, "`{}` must not be empty", field),
+            ValidationError::InvalidUrl { field, url, reason } => {
+                write!(f, "`{}` is not a valid URL ({}): {}", field, reason, url)
+            }
+            ValidationError::ZeroDuration => write!(f, "`duration` must be positive"),
+            ValidationError::ZeroEpisode => write!(f, "`episode` numbers start at 1"),
+            ValidationError::InvalidDimensions { url, width, height } => write!(
+                f,
+                "image {} has invalid dimensions {}x{} (1 to {} pixels per side)",
+                url, width, height, MAX_IMAGE_SIDE
+            ),
+            ValidationError::InvalidSlug(slug) => write!(
+                f,
+                "slug `{}` may only contain lowercase letters, digits and single dashes",
+                slug
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub const MAX_IMAGE_SIDE: u32 = 16_384;
+
+fn require_title(request: &ContentRequest) -> Result<String, ValidationError> {
+    let title = request.title.trim();
+    if title.is_empty() {
+        return Err(ValidationError::Empty("title"));
+    }
+    Ok(title.to_string())
+}
+
+// Absolute http(s) URL with a host, e.g. https://cdn.example.com/a.png
+pub fn validate_url(field: &'static str, url: &str) -> Result<(), ValidationError> {
+    let invalid = |reason| ValidationError::InvalidUrl {
+        field,
+        url: url.to_string(),
+        reason,
+    };
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| invalid("must start with http:// or https://"))?;
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once(':').map(|(host, _)| host).unwrap_or(host);
+    if host.is_empty() || !(host.contains('.') || host == "localhost") {
+        return Err(invalid("missing host"));
+    }
+    if url.chars().any(char::is_whitespace) {
+        return Err(invalid("contains whitespace"));
+    }
+    Ok(())
+}
+
+fn require_url(request: &ContentRequest, field: &'static str) -> Result<String, ValidationError> {
+    let url = request
+        .url
+        .as_deref()
+        .ok_or(ValidationError::Missing(field))?;
+    validate_url(field, url)?;
+    Ok(url.to_string())
+}
+
+fn require_duration(request: &ContentRequest) -> Result<u32, ValidationError> {
+    match request.duration_secs {
+        None => Err(ValidationError::Missing("duration")),
+        Some(0) => Err(ValidationError::ZeroDuration),
+        Some(secs) => Ok(secs),
+    }
+}
+
+// "Design Patterns in Rust!" -> "design-patterns-in-rust"
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+pub fn validate_slug(slug: &str) -> Result<(), ValidationError> {
+    let valid = !slug.is_empty()
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && !slug.contains("--")
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidSlug(slug.to_string()))
+    }
+}
+
+// Creator
+pub trait ContentFactory {
+    fn create_content(&self, request: &ContentRequest)
+        -> Result<Box<dyn Content>, ValidationError>;
+}
+
+// ConcreteCreator: BlogPostFactory
+pub struct BlogPostFactory;
+
+impl ContentFactory for BlogPostFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        Ok(Box::new(BlogPost {
+            title: require_title(request)?,
+            body: request.body.clone(),
+            tags: request.tags.clone(),
+        }))
+    }
+}
+
+// ConcreteCreator: VideoFactory; the request body becomes the video description
+pub struct VideoFactory;
+
+impl ContentFactory for VideoFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        Ok(Box::new(Video {
+            title: require_title(request)?,
+            url: require_url(request, "url")?,
+            duration: require_duration(request)?,
+            description: request.body.clone(),
+        }))
+    }
+}
+
+// ConcreteCreator: ImageGalleryFactory
+pub struct ImageGalleryFactory;
+
+impl ContentFactory for ImageGalleryFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        let title = require_title(request)?;
+        if request.images.is_empty() {
+            return Err(ValidationError::Empty("images"));
+        }
+        for image in &request.images {
+            validate_url("images.url", &image.url)?;
+            let side = 1..=MAX_IMAGE_SIDE;
+            if !side.contains(&image.width) || !side.contains(&image.height) {
+                return Err(ValidationError::InvalidDimensions {
+                    url: image.url.clone(),
+                    width: image.width,
+                    height: image.height,
+                });
+            }
+        }
+        Ok(Box::new(ImageGallery {
+            title,
+            description: request.body.clone(),
+            images: request.images.clone(),
+        }))
+    }
+}
+
+// ConcreteCreator: PodcastFactory; the request body becomes the show notes
+pub struct PodcastFactory;
+
+impl ContentFactory for PodcastFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        let title = require_title(request)?;
+        let audio_url = require_url(request, "audio url")?;
+        let duration = require_duration(request)?;
+        let episode = match request.episode {
+            None => return Err(ValidationError::Missing("episode")),
+            Some(0) => return Err(ValidationError::ZeroEpisode),
+            Some(episode) => episode,
+        };
+        Ok(Box::new(Podcast {
+            title,
+            audio_url,
+            duration,
+            episode,
+            show_notes: request.body.clone(),
+        }))
+    }
+}
+
+// ConcreteCreator: PageFactory; without an explicit slug one is derived from the title
+pub struct PageFactory;
+
+impl ContentFactory for PageFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        let title = require_title(request)?;
+        let slug = request.slug.clone().unwrap_or_else(|| slugify(&title));
+        validate_slug(&slug)?;
+        Ok(Box::new(Page {
+            title,
+            slug,
+            body: request.body.clone(),
+        }))
+    }
+}
+
+// ConcreteCreator: CollectionFactory; creates an empty collection, items are added as members
+// in the repository
+pub struct CollectionFactory(pub CollectionKind);
+
+impl ContentFactory for CollectionFactory {
+    fn create_content(
+        &self,
+        request: &ContentRequest,
+    ) -> Result<Box<dyn Content>, ValidationError> {
+        Ok(Box::new(Collection::new(
+            self.0,
+            &require_title(request)?,
+            &request.body,
+        )))
+    }
+}
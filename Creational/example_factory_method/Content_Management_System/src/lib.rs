@@ -0,0 +1,7 @@
+pub mod content;
+pub mod export;
+pub mod factory;
+pub mod repository;
+pub mod search;
+pub mod site;
+pub mod taxonomy;
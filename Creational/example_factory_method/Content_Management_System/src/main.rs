@@ -1,123 +1,444 @@
-use std::collections::HashMap;
+use cms::content::{CollectionKind, Image};
+use cms::export::{
+    export, outline, rss_feed, Exporter, HtmlExporter, JsonExporter, MarkdownExporter,
+};
+use cms::factory::{
+    BlogPostFactory, CollectionFactory, ContentFactory, ContentRequest, ImageGalleryFactory,
+    PageFactory, PodcastFactory, VideoFactory,
+};
+use cms::repository::{timestamp, Repository, State};
 
-// Define the Product trait
-trait Content {
-    fn create(&self, title: &str, body: &str);
-    fn display(&self);
-    fn metadata(&self) -> HashMap<String, String>;
-}
+// Client code
+fn main() {
+    let blog_post_factory = BlogPostFactory;
+    let video_factory = VideoFactory;
+    let mut repository = Repository::new();
 
-// ConcreteProduct: BlogPost
-struct BlogPost {
-    title: String,
-    body: String,
-    tags: Vec<String>,
-}
+    let post = repository.create(
+        blog_post_factory
+            .create_content(
+                &ContentRequest::new("Design Patterns")
+                    .body("Factory Method Pattern")
+                    .tag("patterns"),
+            )
+            .unwrap(),
+        "alice",
+    );
+    let video = repository.create(
+        video_factory
+            .create_content(
+                &ContentRequest::new("Design Patterns")
+                    .body("Factory Method Pattern")
+                    .url("https://www.youtube.com/watch?v=factory")
+                    .duration(60),
+            )
+            .unwrap(),
+        "alice",
+    );
+    println!("Created {} and {}", post, video);
 
-impl BlogPost {
-    fn new(title: &str, body: &str) -> BlogPost {
-        BlogPost {
-            title: title.to_string(),
-            body: body.to_string(),
-            tags: vec![],
-        }
+    repository
+        .update(
+            post,
+            blog_post_factory
+                .create_content(
+                    &ContentRequest::new("Design Patterns in Rust")
+                        .body("Factory Method Pattern\nCreators return trait objects")
+                        .tag("patterns")
+                        .tag("rust"),
+                )
+                .unwrap(),
+            "alice",
+            "expand the body",
+        )
+        .unwrap();
+    println!("Diff of {} from revision 1 to 2:", post);
+    for change in repository.diff(post, 1, 2).unwrap() {
+        println!("{}", change);
     }
-}
 
-impl Content for BlogPost {
-    fn create(&self, title: &str, body: &str) {
-        println!("Creating Blog Post: {} - {}", title, body);
+    // Review: edits are locked while in review, a rejection sends the post back to draft
+    repository.submit(post, "alice").unwrap();
+    if let Err(e) = repository.update(
+        post,
+        blog_post_factory
+            .create_content(&ContentRequest::new("Design Patterns in Rust").body("typo fix"))
+            .unwrap(),
+        "alice",
+        "sneaky edit",
+    ) {
+        println!("Error: {}", e);
     }
+    repository.reject(post, "bob").unwrap();
+    repository
+        .update(
+            post,
+            blog_post_factory
+                .create_content(
+                    &ContentRequest::new("Design Patterns in Rust")
+                        .body(
+                            "Factory Method Pattern\nCreators return trait objects\n\
+                             See also: Abstract Factory",
+                        )
+                        .tag("patterns")
+                        .tag("rust"),
+                )
+                .unwrap(),
+            "alice",
+            "address review comments",
+        )
+        .unwrap();
+    repository.submit(post, "alice").unwrap();
+    repository.publish(post, "bob").unwrap();
+    repository.submit(video, "alice").unwrap();
 
-    fn display(&self) {
-        println!("Displaying Blog Post: {} - {}", self.title, self.body);
-    }
+    // The other content types go straight to publication
+    let gallery = ImageGalleryFactory
+        .create_content(
+            &ContentRequest::new("Pattern Diagrams")
+                .body("UML for the creational patterns")
+                .image(Image::new(
+                    "https://cdn.example.com/factory-method.png",
+                    1200,
+                    800,
+                    "Factory Method",
+                ))
+                .image(Image::new(
+                    "https://cdn.example.com/abstract-factory.png",
+                    1200,
+                    900,
+                    "Abstract Factory",
+                )),
+        )
+        .unwrap();
+    let podcast = PodcastFactory
+        .create_content(
+            &ContentRequest::new("Why Factories?")
+                .body("We discuss when a factory method beats a constructor")
+                .url("https://cdn.example.com/episodes/1.mp3")
+                .duration(1830)
+                .episode(1),
+        )
+        .unwrap();
+    let page = PageFactory
+        .create_content(
+            &ContentRequest::new("About this Site!").body("Examples of design patterns"),
+        )
+        .unwrap();
+    let [gallery, podcast, _page] = [gallery, podcast, page].map(|content| {
+        let id = repository.create(content, "dave");
+        repository.submit(id, "dave").unwrap();
+        repository.publish(id, "bob").unwrap();
+        id
+    });
 
-    fn metadata(&self) -> HashMap<String, String> {
-        let mut metadata = HashMap::new();
-        metadata.insert("type".to_string(), "BlogPost".to_string());
-        metadata.insert("title".to_string(), self.title.clone());
-        metadata.insert("body".to_string(), self.body.clone());
-        metadata
+    // Invalid requests are rejected before anything reaches the repository
+    let invalid: Vec<(&str, &dyn ContentFactory, ContentRequest)> = vec![
+        (
+            "video",
+            &VideoFactory,
+            ContentRequest::new("No URL").duration(60),
+        ),
+        (
+            "video",
+            &VideoFactory,
+            ContentRequest::new("Bad URL")
+                .url("ftp://example.com/v.mp4")
+                .duration(60),
+        ),
+        (
+            "podcast",
+            &PodcastFactory,
+            ContentRequest::new("Silence")
+                .url("https://cdn.example.com/0.mp3")
+                .duration(0)
+                .episode(2),
+        ),
+        (
+            "gallery",
+            &ImageGalleryFactory,
+            ContentRequest::new("Huge").image(Image::new(
+                "https://cdn.example.com/huge.png",
+                100_000,
+                10,
+                "too wide",
+            )),
+        ),
+        (
+            "page",
+            &PageFactory,
+            ContentRequest::new("Contact").slug("Contact Us"),
+        ),
+        ("blog post", &BlogPostFactory, ContentRequest::new("   ")),
+    ];
+    for (kind, factory, request) in &invalid {
+        if let Err(e) = factory.create_content(request) {
+            println!("Invalid {}: {}", kind, e);
+        }
+    }
+    let unfinished = repository.create(
+        blog_post_factory
+            .create_content(&ContentRequest::new("Unfinished").body("..."))
+            .unwrap(),
+        "carol",
+    );
+    if let Err(e) = repository.archive(unfinished, "carol") {
+        println!("Error: {}", e);
     }
-}
 
-// ConcreteProduct: Video
-struct Video {
-    title: String,
-    url: String,
-    duration: u32,
-}
+    println!("\nAll content:");
+    for entry in repository.list() {
+        let revision = entry.current();
+        println!(
+            "{} [{}] revision {} by {}: {}",
+            entry.id,
+            entry.state,
+            revision.number,
+            revision.author,
+            revision.content().title()
+        );
+    }
 
-impl Video {
-    fn new(title: &str, url: &str, duration: u32) -> Video {
-        Video {
-            title: title.to_string(),
-            url: url.to_string(),
-            duration,
+    println!("\nPublic listing:");
+    let mut exporters: Vec<(&str, Box<dyn Exporter>)> = vec![
+        ("HTML", Box::new(HtmlExporter::default())),
+        ("Markdown", Box::new(MarkdownExporter::default())),
+        ("JSON", Box::new(JsonExporter::default())),
+    ];
+    for entry in repository.list_published() {
+        for (name, exporter) in exporters.iter_mut() {
+            println!("--- {} ---", name);
+            let content = repository.assemble(entry.id).unwrap();
+            println!("{}", export(&*content, exporter.as_mut()));
         }
     }
-}
+    // Editors can preview content that is not public yet
+    let preview = repository.get(video).unwrap().current().content();
+    println!("--- Preview of {} ---", video);
+    print!("{}", export(preview, &mut HtmlExporter::default()));
+    println!("{}", export(preview, &mut MarkdownExporter::default()));
+    println!("--- RSS ---");
+    print!(
+        "{}",
+        rss_feed(&repository, "Design Patterns", "https://cms.example.com")
+    );
 
-impl Content for Video {
-    fn create(&self, title: &str, body: &str) {
-        println!("Creating Video: {} - {}", title, body);
+    // Tags: a small hierarchy, then tags on individual items
+    let taxonomy = repository.taxonomy_mut();
+    taxonomy.set_parent("rust", "programming").unwrap();
+    taxonomy.set_parent("patterns", "programming").unwrap();
+    taxonomy.set_parent("creational", "patterns").unwrap();
+    if let Err(e) = taxonomy.set_parent("programming", "creational") {
+        println!("Error: {}", e);
     }
-
-    fn display(&self) {
+    repository.add_tag(gallery, "Creational").unwrap();
+    repository.add_tag(gallery, "diagrams").unwrap();
+    repository.add_tag(podcast, "patterns").unwrap();
+    repository.add_tag(podcast, "audio").unwrap();
+    repository.add_tag(video, "patterns").unwrap();
+    repository.remove_tag(podcast, "audio").unwrap();
+    if let Err(e) = repository.add_tag(podcast, "  ") {
+        println!("Error: {}", e);
+    }
+    println!("\nTags (direct / including subtags):");
+    for count in repository.tag_counts() {
         println!(
-            "Displaying video: {}\nURL: {}\nDuration: {} seconds",
-            self.title, self.url, self.duration
+            "{}{} {}/{}",
+            "  ".repeat(count.depth + 1),
+            count.tag,
+            count.direct,
+            count.total
         );
     }
-
-    fn metadata(&self) -> HashMap<String, String> {
-        let mut metadata = HashMap::new();
-        metadata.insert("type".to_string(), "Video".to_string());
-        metadata.insert("title".to_string(), self.title.clone());
-        metadata.insert("url".to_string(), self.url.clone());
-        metadata.insert("duration".to_string(), self.duration.to_string());
-        metadata
+    for id in [post, gallery] {
+        let entry = repository.get(id).unwrap();
+        let tags: Vec<&str> = entry.tags.iter().map(String::as_str).collect();
+        println!("{} tags: {}", id, tags.join(", "));
     }
-}
-
-// Creator
-trait ContentFactory {
-    fn create_content(&self, title: &str, body: &str) -> Box<dyn Content>;
-}
 
-// ConcreteCreator: BlogPostFactory
-struct BlogPostFactory;
-
-impl ContentFactory for BlogPostFactory {
-    fn create_content(&self, title: &str, body: &str) -> Box<dyn Content> {
-        Box::new(BlogPost::new(title, body))
+    // Search over title and body of the current revisions
+    println!(
+        "\nSearch index: {} items, {} terms",
+        repository.index().items(),
+        repository.index().terms()
+    );
+    for query in [
+        "factories",
+        "rust AND tag:patterns",
+        "\"factory method\"",
+        "constructor OR diagrams",
+        "tag:programming NOT state:published",
+        "design (pattern OR patterns) state:published",
+        "\"factory method",
+        "rust AND",
+        "author:alice",
+        "state:deleted",
+        "tag:\"design patterns\"",
+    ] {
+        let hits = match repository.search(query) {
+            Ok(hits) => hits,
+            Err(e) => {
+                println!("{}: error: {}", query, e);
+                continue;
+            }
+        };
+        println!("{}: {} hit(s)", query, hits.len());
+        for hit in hits {
+            let entry = repository.get(hit.id).unwrap();
+            println!(
+                "  {:.3} {} [{}] {}",
+                hit.score,
+                hit.id,
+                entry.state,
+                entry.current().content().title()
+            );
+        }
     }
-}
+    // Visitors only ever find published items
+    let hits = repository.search_published("factories").unwrap();
+    println!("factories, published only: {} hit(s)", hits.len());
 
-// ConcreteCreator: VideoFactory
-struct VideoFactory;
-
-impl ContentFactory for VideoFactory {
-    fn create_content(&self, title: &str, body: &str) -> Box<dyn Content> {
-        let video_url = "https://www.youtube.com/";
-        Box::new(Video::new(title, video_url, 60))
+    // Collections: a course made of modules made of posts, videos and podcasts
+    let collection = |kind, title: &str, description: &str| {
+        CollectionFactory(kind)
+            .create_content(&ContentRequest::new(title).body(description))
+            .unwrap()
+    };
+    let course = repository.create(
+        collection(
+            CollectionKind::Course,
+            "Rust Design Patterns",
+            "From factories to decorators",
+        ),
+        "alice",
+    );
+    let creational = repository.create(
+        collection(CollectionKind::Module, "Creational Patterns", ""),
+        "alice",
+    );
+    let structural = repository.create(
+        collection(CollectionKind::Module, "Structural Patterns", ""),
+        "alice",
+    );
+    let adapter = repository.create(
+        blog_post_factory
+            .create_content(
+                &ContentRequest::new("The Adapter Pattern")
+                    .body("Wrap an incompatible interface")
+                    .tag("structural"),
+            )
+            .unwrap(),
+        "alice",
+    );
+    let decorator = repository.create(
+        video_factory
+            .create_content(
+                &ContentRequest::new("Decorator in Ten Minutes")
+                    .url("https://www.youtube.com/watch?v=decorator")
+                    .duration(600),
+            )
+            .unwrap(),
+        "alice",
+    );
+    for (parent, child) in [
+        (course, structural),
+        (course, creational),
+        (creational, podcast),
+        (creational, post),
+        (creational, video),
+        (structural, decorator),
+        (structural, adapter),
+    ] {
+        repository.add_child(parent, child).unwrap();
+    }
+    // Put the modules and lessons in teaching order
+    repository.move_child(course, creational, 0).unwrap();
+    repository.move_child(creational, post, 0).unwrap();
+    repository.move_child(structural, adapter, 0).unwrap();
+    let assembled = repository.assemble(course).unwrap();
+    println!("\nCourse outline:");
+    print!("{}", outline(&*assembled));
+    println!("Summary: {}", assembled.as_collection().unwrap().summary());
+    for (parent, child) in [(creational, course), (creational, post), (post, video)] {
+        if let Err(e) = repository.add_child(parent, child) {
+            println!("Error: {}", e);
+        }
     }
-}
 
-// Client code
-fn main() {
-    let blog_post_factory = BlogPostFactory;
-    let video_factory = VideoFactory;
+    // Publishing the course submits and publishes every draft lesson in it
+    if let Err(e) = repository.publish(course, "bob") {
+        println!("Error: {}", e);
+    }
+    repository.submit(course, "alice").unwrap();
+    repository.publish(course, "bob").unwrap();
+    for id in [course, structural, adapter, decorator, video, post] {
+        let entry = repository.get(id).unwrap();
+        println!(
+            "{} [{}] {}",
+            id,
+            entry.state,
+            entry.current().content().title()
+        );
+    }
+    let playlist = repository.create(
+        collection(CollectionKind::Playlist, "Watch Later", ""),
+        "carol",
+    );
+    repository.add_child(playlist, decorator).unwrap();
+    repository.add_child(playlist, video).unwrap();
+    repository.archive(structural, "bob").unwrap();
+    // The decorator video went out with its module, so the playlist cannot go to review
+    if let Err(e) = repository.submit(playlist, "carol") {
+        println!("Error: {}", e);
+    }
+    println!("--- Markdown of {} ---", creational);
+    print!(
+        "{}",
+        export(
+            &*repository.assemble(creational).unwrap(),
+            &mut MarkdownExporter::default()
+        )
+    );
+    println!("--- JSON of {} ---", playlist);
+    println!(
+        "{}",
+        export(
+            &*repository.assemble(playlist).unwrap(),
+            &mut JsonExporter::default()
+        )
+    );
 
-    let blog_post = blog_post_factory.create_content("Design Patterns", "Factory Method Pattern");
-    let video = video_factory.create_content("Design Patterns", "Factory Method Pattern");
+    println!("\nHistory of {}:", post);
+    let entry = repository.get(post).unwrap();
+    for revision in entry.revisions() {
+        println!(
+            "  r{} {} by {}: {}",
+            revision.number,
+            timestamp(revision.created_at),
+            revision.author,
+            revision.note
+        );
+    }
+    for transition in &entry.history {
+        println!(
+            "  {} -> {} by {} at {}",
+            transition.from,
+            transition.to,
+            transition.by,
+            timestamp(transition.at)
+        );
+    }
+    println!("  {} since {}", entry.state, timestamp(entry.state_since()));
 
-    blog_post.create("Design Patterns", "Factory Method Pattern");
-    blog_post.display();
-    println!("{:?}", blog_post.metadata());
+    repository.archive(post, "bob").unwrap();
+    println!(
+        "Published after archiving: {}",
+        repository.list_published().count()
+    );
+    repository.transition(post, State::Draft, "alice").unwrap();
 
-    video.create("Design Patterns", "Factory Method Pattern");
-    video.display();
-    println!("{:?}", video.metadata());
+    repository.delete(video).unwrap();
+    if let Err(e) = repository.get(video) {
+        println!("Error: {}", e);
+    }
 }
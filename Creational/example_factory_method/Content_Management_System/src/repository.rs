@@ -0,0 +1,1074 @@
+use crate::content::Content;
+use crate::export::fields;
+use crate::search::{self, Hit, Index, Query, QueryError};
+use crate::taxonomy::{normalize_tag, tag_counts, TagCount, TagError, Taxonomy};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Stable identifier; never reused, even after the item is deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContentId(u64);
+
+impl fmt::Display for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "content-{}", self.0)
+    }
+}
+
+// Workflow: Draft -> InReview -> Published -> Archived. Review can send an item back to Draft,
+// and an archived item can be restored as a Draft
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Draft,
+    InReview,
+    Published,
+    Archived,
+}
+
+impl State {
+    pub fn can_become(&self, next: State) -> bool {
+        matches!(
+            (self, next),
+            (State::Draft, State::InReview)
+                | (State::InReview, State::Draft)
+                | (State::InReview, State::Published)
+                | (State::Published, State::Archived)
+                | (State::Archived, State::Draft)
+        )
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            State::Draft => "draft",
+            State::InReview => "in review",
+            State::Published => "published",
+            State::Archived => "archived",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub from: State,
+    pub to: State,
+    pub by: String,
+    pub at: SystemTime,
+}
+
+// An immutable snapshot; editing an item appends a new revision
+pub struct Revision {
+    pub number: usize,
+    pub author: String,
+    pub note: String,
+    pub created_at: SystemTime,
+    content: Rc<dyn Content>,
+}
+
+impl Revision {
+    pub fn content(&self) -> &dyn Content {
+        &*self.content
+    }
+
+    // For building collections out of existing items without copying them
+    pub fn shared(&self) -> Rc<dyn Content> {
+        Rc::clone(&self.content)
+    }
+}
+
+pub struct Entry {
+    pub id: ContentId,
+    pub state: State,
+    pub history: Vec<Transition>,
+    revisions: Vec<Revision>,
+    // The current revision's own tags plus those added with `add_tag`
+    pub tags: BTreeSet<String>,
+    added_tags: BTreeSet<String>,
+    // Members, in order, when the item is a collection
+    pub children: Vec<ContentId>,
+}
+
+impl Entry {
+    pub fn current(&self) -> &Revision {
+        self.revisions.last().unwrap()
+    }
+
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    pub fn revision(&self, number: usize) -> Option<&Revision> {
+        number
+            .checked_sub(1)
+            .and_then(|index| self.revisions.get(index))
+    }
+
+    pub fn created_at(&self) -> SystemTime {
+        self.revisions[0].created_at
+    }
+
+    // When the item was last published, if ever
+    pub fn published_at(&self) -> Option<SystemTime> {
+        self.history
+            .iter()
+            .rev()
+            .find(|transition| transition.to == State::Published)
+            .map(|transition| transition.at)
+    }
+
+    // When the item last entered its current state
+    pub fn state_since(&self) -> SystemTime {
+        self.history
+            .last()
+            .map(|transition| transition.at)
+            .unwrap_or_else(|| self.created_at())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositoryError {
+    NotFound(ContentId),
+    RevisionNotFound {
+        id: ContentId,
+        revision: usize,
+    },
+    InvalidTransition {
+        id: ContentId,
+        from: State,
+        to: State,
+    },
+    NotEditable {
+        id: ContentId,
+        state: State,
+    },
+    Tag(TagError),
+    NotACollection(ContentId),
+    AlreadyMember {
+        parent: ContentId,
+        child: ContentId,
+    },
+    NotAMember {
+        parent: ContentId,
+        child: ContentId,
+    },
+    Cycle {
+        parent: ContentId,
+        child: ContentId,
+    },
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryError::NotFound(id) => write!(f, "{} not found", id),
+            RepositoryError::RevisionNotFound { id, revision } => {
+                write!(f, "{} has no revision {}", id, revision)
+            }
+            RepositoryError::InvalidTransition { id, from, to } => {
+                write!(f, "{} cannot move from {} to {}", id, from, to)
+            }
+            RepositoryError::NotEditable { id, state } => {
+                write!(f, "{} is {}; only drafts can be edited", id, state)
+            }
+            RepositoryError::Tag(e) => write!(f, "{}", e),
+            RepositoryError::NotACollection(id) => write!(f, "{} is not a collection", id),
+            RepositoryError::AlreadyMember { parent, child } => {
+                write!(f, "{} is already in {}", child, parent)
+            }
+            RepositoryError::NotAMember { parent, child } => {
+                write!(f, "{} is not in {}", child, parent)
+            }
+            RepositoryError::Cycle { parent, child } => write!(
+                f,
+                "cannot add {} to {}: {} already contains {}",
+                child, parent, child, parent
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<TagError> for RepositoryError {
+    fn from(e: TagError) -> Self {
+        RepositoryError::Tag(e)
+    }
+}
+
+// Repository: owns every content item, its revisions and its workflow history, along with the
+// tag taxonomy and a search index that follows the current revision of every item
+#[derive(Default)]
+pub struct Repository {
+    next_id: u64,
+    entries: BTreeMap<ContentId, Entry>,
+    taxonomy: Taxonomy,
+    index: Index,
+}
+
+impl Repository {
+    pub fn new() -> Self {
+        Repository::default()
+    }
+
+    pub fn create(&mut self, content: Box<dyn Content>, author: &str) -> ContentId {
+        self.next_id += 1;
+        let id = ContentId(self.next_id);
+        self.index.insert(id, content.as_ref());
+        let tags = content_tags(content.as_ref());
+        let revision = Revision {
+            number: 1,
+            author: author.to_string(),
+            note: "created".to_string(),
+            created_at: SystemTime::now(),
+            content: Rc::from(content),
+        };
+        self.entries.insert(
+            id,
+            Entry {
+                id,
+                state: State::Draft,
+                history: Vec::new(),
+                revisions: vec![revision],
+                tags,
+                added_tags: BTreeSet::new(),
+                children: Vec::new(),
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: ContentId) -> Result<&Entry, RepositoryError> {
+        self.entries.get(&id).ok_or(RepositoryError::NotFound(id))
+    }
+
+    fn get_mut(&mut self, id: ContentId) -> Result<&mut Entry, RepositoryError> {
+        self.entries
+            .get_mut(&id)
+            .ok_or(RepositoryError::NotFound(id))
+    }
+
+    // Appends a revision; returns its number
+    pub fn update(
+        &mut self,
+        id: ContentId,
+        content: Box<dyn Content>,
+        author: &str,
+        note: &str,
+    ) -> Result<usize, RepositoryError> {
+        // Borrow the entry through the field so the index stays available
+        let entry = self
+            .entries
+            .get_mut(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        if entry.state != State::Draft {
+            return Err(RepositoryError::NotEditable {
+                id,
+                state: entry.state,
+            });
+        }
+        let number = entry.revisions.len() + 1;
+        entry.tags = content_tags(content.as_ref());
+        entry.tags.extend(entry.added_tags.iter().cloned());
+        self.index.insert(id, content.as_ref());
+        entry.revisions.push(Revision {
+            number,
+            author: author.to_string(),
+            note: note.to_string(),
+            created_at: SystemTime::now(),
+            content: Rc::from(content),
+        });
+        Ok(number)
+    }
+
+    // Deleting a collection leaves its members in place; deleting a member takes it out of
+    // every collection
+    pub fn delete(&mut self, id: ContentId) -> Result<Entry, RepositoryError> {
+        let entry = self
+            .entries
+            .remove(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        self.index.remove(id);
+        for other in self.entries.values_mut() {
+            other.children.retain(|child| *child != id);
+        }
+        Ok(entry)
+    }
+
+    pub fn transition(
+        &mut self,
+        id: ContentId,
+        to: State,
+        by: &str,
+    ) -> Result<(), RepositoryError> {
+        let entry = self.get_mut(id)?;
+        if !entry.state.can_become(to) {
+            return Err(RepositoryError::InvalidTransition {
+                id,
+                from: entry.state,
+                to,
+            });
+        }
+        entry.history.push(Transition {
+            from: entry.state,
+            to,
+            by: by.to_string(),
+            at: SystemTime::now(),
+        });
+        entry.state = to;
+        Ok(())
+    }
+
+    // submit, publish and archive cascade from a collection to everything in it; see `cascade`
+    pub fn submit(&mut self, id: ContentId, by: &str) -> Result<(), RepositoryError> {
+        self.cascade(id, State::InReview, by)
+    }
+
+    pub fn reject(&mut self, id: ContentId, by: &str) -> Result<(), RepositoryError> {
+        self.transition(id, State::Draft, by)
+    }
+
+    pub fn publish(&mut self, id: ContentId, by: &str) -> Result<(), RepositoryError> {
+        self.cascade(id, State::Published, by)
+    }
+
+    pub fn archive(&mut self, id: ContentId, by: &str) -> Result<(), RepositoryError> {
+        self.cascade(id, State::Archived, by)
+    }
+
+    // Moves `id` to `to`, then brings every member below it along: publishing a course submits
+    // and publishes its draft lessons, archiving it archives the published ones. Members that
+    // are already further along are left alone. Nothing changes unless every step is valid
+    fn cascade(&mut self, id: ContentId, to: State, by: &str) -> Result<(), RepositoryError> {
+        let entry = self.get(id)?;
+        if !entry.state.can_become(to) {
+            return Err(RepositoryError::InvalidTransition {
+                id,
+                from: entry.state,
+                to,
+            });
+        }
+        let mut plan = vec![(id, vec![to])];
+        for member in self.descendants(id)? {
+            let from = self.get(member)?.state;
+            let steps = match (from, to) {
+                (State::Draft, State::InReview) => vec![State::InReview],
+                (State::Draft, State::Published) => vec![State::InReview, State::Published],
+                (State::InReview, State::Published) => vec![State::Published],
+                (State::Published, State::Archived) => vec![State::Archived],
+                (State::Archived, State::Published | State::InReview) => {
+                    return Err(RepositoryError::InvalidTransition {
+                        id: member,
+                        from,
+                        to,
+                    })
+                }
+                _ => continue,
+            };
+            plan.push((member, steps));
+        }
+        for (member, steps) in plan {
+            for step in steps {
+                self.transition(member, step, by)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collection_entry(&self, id: ContentId) -> Result<&Entry, RepositoryError> {
+        let entry = self.get(id)?;
+        if entry.current().content().as_collection().is_none() {
+            return Err(RepositoryError::NotACollection(id));
+        }
+        Ok(entry)
+    }
+
+    // Every member below `id`, depth first in collection order, each once
+    pub fn descendants(&self, id: ContentId) -> Result<Vec<ContentId>, RepositoryError> {
+        let mut seen = BTreeSet::new();
+        let mut order = Vec::new();
+        let mut stack: Vec<ContentId> = self.get(id)?.children.iter().rev().copied().collect();
+        while let Some(next) = stack.pop() {
+            if seen.insert(next) {
+                order.push(next);
+                stack.extend(self.get(next)?.children.iter().rev());
+            }
+        }
+        Ok(order)
+    }
+
+    // Collections that directly contain `id`
+    pub fn parents(&self, id: ContentId) -> Vec<ContentId> {
+        self.entries
+            .values()
+            .filter(|entry| entry.children.contains(&id))
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    pub fn add_child(
+        &mut self,
+        parent: ContentId,
+        child: ContentId,
+    ) -> Result<(), RepositoryError> {
+        let position = self.get(parent)?.children.len();
+        self.insert_child(parent, child, position)
+    }
+
+    // Positions past the end append. An item may belong to several collections, but not twice
+    // to the same one, and a collection cannot end up inside itself
+    pub fn insert_child(
+        &mut self,
+        parent: ContentId,
+        child: ContentId,
+        position: usize,
+    ) -> Result<(), RepositoryError> {
+        let members = &self.collection_entry(parent)?.children;
+        self.get(child)?;
+        if members.contains(&child) {
+            return Err(RepositoryError::AlreadyMember { parent, child });
+        }
+        if child == parent || self.descendants(child)?.contains(&parent) {
+            return Err(RepositoryError::Cycle { parent, child });
+        }
+        let children = &mut self.get_mut(parent)?.children;
+        children.insert(position.min(children.len()), child);
+        Ok(())
+    }
+
+    pub fn move_child(
+        &mut self,
+        parent: ContentId,
+        child: ContentId,
+        position: usize,
+    ) -> Result<(), RepositoryError> {
+        self.remove_child(parent, child)?;
+        let children = &mut self.get_mut(parent)?.children;
+        children.insert(position.min(children.len()), child);
+        Ok(())
+    }
+
+    pub fn remove_child(
+        &mut self,
+        parent: ContentId,
+        child: ContentId,
+    ) -> Result<(), RepositoryError> {
+        let children = &mut self.get_mut(parent)?.children;
+        let position = children
+            .iter()
+            .position(|member| *member == child)
+            .ok_or(RepositoryError::NotAMember { parent, child })?;
+        children.remove(position);
+        Ok(())
+    }
+
+    // The item as a content tree: a collection comes back with the current revision of each
+    // member in place, nested collections included
+    pub fn assemble(&self, id: ContentId) -> Result<Rc<dyn Content>, RepositoryError> {
+        let entry = self.get(id)?;
+        let content = entry.current().content();
+        let Some(collection) = content.as_collection() else {
+            return Ok(entry.current().shared());
+        };
+        let mut collection = collection.clone();
+        for child in &entry.children {
+            collection.push(self.assemble(*child)?);
+        }
+        Ok(Rc::new(collection))
+    }
+
+    // Every item, whatever its state; for editors
+    pub fn list(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    // What visitors may see
+    pub fn list_published(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .values()
+            .filter(|entry| entry.state == State::Published)
+    }
+
+    // Tags are editorial metadata: changing them does not create a revision and is allowed in any
+    // state. Added tags stay across revisions; the content's own tags follow its latest revision,
+    // so one removed here comes back if the next revision still has it. Both return whether the
+    // tag set changed
+    pub fn add_tag(&mut self, id: ContentId, tag: &str) -> Result<bool, RepositoryError> {
+        let tag = normalize_tag(tag)?;
+        let entry = self.get_mut(id)?;
+        entry.added_tags.insert(tag.clone());
+        Ok(entry.tags.insert(tag))
+    }
+
+    pub fn remove_tag(&mut self, id: ContentId, tag: &str) -> Result<bool, RepositoryError> {
+        let tag = normalize_tag(tag)?;
+        let entry = self.get_mut(id)?;
+        entry.added_tags.remove(&tag);
+        Ok(entry.tags.remove(&tag))
+    }
+
+    pub fn taxonomy(&self) -> &Taxonomy {
+        &self.taxonomy
+    }
+
+    pub fn taxonomy_mut(&mut self) -> &mut Taxonomy {
+        &mut self.taxonomy
+    }
+
+    pub fn tag_counts(&self) -> Vec<TagCount> {
+        tag_counts(
+            &self.taxonomy,
+            self.entries.values().map(|entry| &entry.tags),
+        )
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    // Items in every state, for editors; e.g. `rust AND tag:patterns`,
+    // `"factory method" OR podcast`, `state:published NOT video`
+    pub fn search(&self, query: &str) -> Result<Vec<Hit>, QueryError> {
+        Ok(search::search(self, &Query::parse(query)?))
+    }
+
+    // What visitors may find
+    pub fn search_published(&self, query: &str) -> Result<Vec<Hit>, QueryError> {
+        Ok(search::search_published(self, &Query::parse(query)?))
+    }
+
+    pub fn diff(
+        &self,
+        id: ContentId,
+        from: usize,
+        to: usize,
+    ) -> Result<Vec<FieldChange>, RepositoryError> {
+        let entry = self.get(id)?;
+        let revision = |number| {
+            entry
+                .revision(number)
+                .ok_or(RepositoryError::RevisionNotFound {
+                    id,
+                    revision: number,
+                })
+        };
+        Ok(diff_content(
+            revision(from)?.content(),
+            revision(to)?.content(),
+        ))
+    }
+}
+
+// Normalized tags carried by the content itself; unusable ones are dropped
+fn content_tags(content: &dyn Content) -> BTreeSet<String> {
+    content
+        .tags()
+        .iter()
+        .filter_map(|tag| normalize_tag(tag).ok())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineChange {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldChange {
+    Added {
+        field: String,
+        value: String,
+    },
+    Removed {
+        field: String,
+        value: String,
+    },
+    Changed {
+        field: String,
+        lines: Vec<LineChange>,
+    },
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldChange::Added { field, value } => write!(f, "+ {}: {}", field, value),
+            FieldChange::Removed { field, value } => write!(f, "- {}: {}", field, value),
+            FieldChange::Changed { field, lines } => {
+                write!(f, "~ {}:", field)?;
+                for line in lines {
+                    match line {
+                        LineChange::Same(text) => write!(f, "\n    {}", text)?,
+                        LineChange::Added(text) => write!(f, "\n  + {}", text)?,
+                        LineChange::Removed(text) => write!(f, "\n  - {}", text)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Compares two versions field by field; changed fields get a line diff
+pub fn diff_content(old: &dyn Content, new: &dyn Content) -> Vec<FieldChange> {
+    let old = fields(old);
+    let new = fields(new);
+    let fields: BTreeSet<&&str> = old.keys().chain(new.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| match (old.get(field), new.get(field)) {
+            (Some(before), Some(after)) if before != after => Some(FieldChange::Changed {
+                field: field.to_string(),
+                lines: diff_lines(before, after),
+            }),
+            (None, Some(after)) => Some(FieldChange::Added {
+                field: field.to_string(),
+                value: after.clone(),
+            }),
+            (Some(before), None) => Some(FieldChange::Removed {
+                field: field.to_string(),
+                value: before.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// Longest-common-subsequence line diff
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(LineChange::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(LineChange::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            changes.push(LineChange::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    changes.extend(
+        old[i..]
+            .iter()
+            .map(|line| LineChange::Removed(line.to_string())),
+    );
+    changes.extend(
+        new[j..]
+            .iter()
+            .map(|line| LineChange::Added(line.to_string())),
+    );
+    changes
+}
+
+// UTC, e.g. 2026-10-19T08:30:00Z
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's `civil_from_days`
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Inverse of `civil_from_days`
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::CollectionKind;
+    use crate::factory::{
+        BlogPostFactory, CollectionFactory, ContentFactory, ContentRequest, VideoFactory,
+    };
+
+    fn post(title: &str, body: &str, tags: &[&str]) -> Box<dyn Content> {
+        let request = tags
+            .iter()
+            .fold(ContentRequest::new(title).body(body), |request, tag| {
+                request.tag(tag)
+            });
+        BlogPostFactory.create_content(&request).unwrap()
+    }
+
+    fn video(title: &str, secs: u32) -> Box<dyn Content> {
+        let request = ContentRequest::new(title)
+            .url("https://example.com/video")
+            .duration(secs);
+        VideoFactory.create_content(&request).unwrap()
+    }
+
+    fn collection(kind: CollectionKind, title: &str) -> Box<dyn Content> {
+        CollectionFactory(kind)
+            .create_content(&ContentRequest::new(title))
+            .unwrap()
+    }
+
+    fn state(repository: &Repository, id: ContentId) -> State {
+        repository.get(id).unwrap().state
+    }
+
+    // A course holding a module (with a draft post and a video in review) and a published post
+    struct Course {
+        repository: Repository,
+        course: ContentId,
+        module: ContentId,
+        draft: ContentId,
+        in_review: ContentId,
+        published: ContentId,
+    }
+
+    fn course() -> Course {
+        let mut repository = Repository::new();
+        let course = repository.create(collection(CollectionKind::Course, "Rust"), "alice");
+        let module = repository.create(collection(CollectionKind::Module, "Basics"), "alice");
+        let draft = repository.create(post("Ownership", "", &[]), "alice");
+        let in_review = repository.create(video("Borrowing", 600), "alice");
+        let published = repository.create(post("Welcome", "", &[]), "alice");
+        repository.submit(in_review, "alice").unwrap();
+        repository.submit(published, "alice").unwrap();
+        repository.publish(published, "bob").unwrap();
+        repository.add_child(course, module).unwrap();
+        repository.add_child(module, draft).unwrap();
+        repository.add_child(module, in_review).unwrap();
+        repository.add_child(course, published).unwrap();
+        Course {
+            repository,
+            course,
+            module,
+            draft,
+            in_review,
+            published,
+        }
+    }
+
+    fn tags(repository: &Repository, id: ContentId) -> Vec<&str> {
+        let entry = repository.get(id).unwrap();
+        entry.tags.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn update_recomputes_tags_and_keeps_added_ones() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("A", "", &["Rust", "old"]), "alice");
+        repository.add_tag(id, "featured").unwrap();
+        assert_eq!(tags(&repository, id), ["featured", "old", "rust"]);
+
+        repository
+            .update(id, post("A", "", &["rust", "new"]), "alice", "retag")
+            .unwrap();
+        assert_eq!(tags(&repository, id), ["featured", "new", "rust"]);
+
+        // A removed content tag comes back while the content still carries it; a removed
+        // added tag does not
+        repository.remove_tag(id, "rust").unwrap();
+        repository.remove_tag(id, "featured").unwrap();
+        assert_eq!(tags(&repository, id), ["new"]);
+        repository
+            .update(id, post("A", "", &["rust"]), "alice", "again")
+            .unwrap();
+        assert_eq!(tags(&repository, id), ["rust"]);
+    }
+
+    #[test]
+    fn added_tag_outlives_the_content_tag_it_duplicates() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("A", "", &["rust"]), "alice");
+        assert!(!repository.add_tag(id, "rust").unwrap());
+        repository
+            .update(id, post("A", "", &[]), "alice", "untag")
+            .unwrap();
+        assert_eq!(tags(&repository, id), ["rust"]);
+    }
+
+    #[test]
+    fn diff_between_revisions() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("Title", "one\ntwo\nthree", &[]), "alice");
+        let second = repository
+            .update(id, post("Title", "one\n2\nthree", &["rust"]), "bob", "edit")
+            .unwrap();
+        assert_eq!(second, 2);
+        let entry = repository.get(id).unwrap();
+        assert_eq!(entry.revisions().len(), 2);
+        assert_eq!(entry.current().author, "bob");
+        assert_eq!(entry.revision(1).unwrap().content().title(), "Title");
+
+        assert_eq!(
+            repository.diff(id, 1, 2).unwrap(),
+            [
+                FieldChange::Changed {
+                    field: "body".to_string(),
+                    lines: vec![
+                        LineChange::Same("one".to_string()),
+                        LineChange::Removed("two".to_string()),
+                        LineChange::Added("2".to_string()),
+                        LineChange::Same("three".to_string()),
+                    ],
+                },
+                FieldChange::Added {
+                    field: "tags".to_string(),
+                    value: "rust".to_string(),
+                },
+            ]
+        );
+        assert!(repository.diff(id, 2, 2).unwrap().is_empty());
+        assert_eq!(
+            repository.diff(id, 0, 2),
+            Err(RepositoryError::RevisionNotFound { id, revision: 0 })
+        );
+        assert_eq!(
+            repository.diff(id, 1, 3),
+            Err(RepositoryError::RevisionNotFound { id, revision: 3 })
+        );
+    }
+
+    #[test]
+    fn workflow_moves_draft_to_review_to_published_to_archived() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("A", "", &[]), "alice");
+        assert_eq!(repository.get(id).unwrap().state, State::Draft);
+        assert_eq!(repository.get(id).unwrap().published_at(), None);
+
+        repository.submit(id, "alice").unwrap();
+        repository.reject(id, "bob").unwrap();
+        repository.submit(id, "alice").unwrap();
+        repository.publish(id, "bob").unwrap();
+        repository.archive(id, "bob").unwrap();
+        repository.transition(id, State::Draft, "alice").unwrap();
+
+        let entry = repository.get(id).unwrap();
+        let steps: Vec<(State, State, &str)> = entry
+            .history
+            .iter()
+            .map(|t| (t.from, t.to, t.by.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (State::Draft, State::InReview, "alice"),
+                (State::InReview, State::Draft, "bob"),
+                (State::Draft, State::InReview, "alice"),
+                (State::InReview, State::Published, "bob"),
+                (State::Published, State::Archived, "bob"),
+                (State::Archived, State::Draft, "alice"),
+            ]
+        );
+        assert!(entry.published_at().is_some());
+    }
+
+    #[test]
+    fn workflow_rejects_skipped_and_backward_steps() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("A", "", &[]), "alice");
+        let invalid = |from, to| Err(RepositoryError::InvalidTransition { id, from, to });
+        assert_eq!(
+            repository.publish(id, "bob"),
+            invalid(State::Draft, State::Published)
+        );
+        assert_eq!(
+            repository.archive(id, "bob"),
+            invalid(State::Draft, State::Archived)
+        );
+        repository.submit(id, "alice").unwrap();
+        repository.publish(id, "bob").unwrap();
+        assert_eq!(
+            repository.reject(id, "bob"),
+            invalid(State::Published, State::Draft)
+        );
+        repository.archive(id, "bob").unwrap();
+        assert_eq!(
+            repository.publish(id, "bob"),
+            invalid(State::Archived, State::Published)
+        );
+        // Failed steps leave no trace in the history
+        assert_eq!(repository.get(id).unwrap().history.len(), 3);
+    }
+
+    #[test]
+    fn only_drafts_can_be_edited() {
+        let mut repository = Repository::new();
+        let id = repository.create(post("A", "", &[]), "alice");
+        repository.submit(id, "alice").unwrap();
+        assert_eq!(
+            repository.update(id, post("B", "", &[]), "alice", "edit"),
+            Err(RepositoryError::NotEditable {
+                id,
+                state: State::InReview,
+            })
+        );
+        assert_eq!(repository.get(id).unwrap().revisions().len(), 1);
+    }
+
+    #[test]
+    fn publishing_a_collection_publishes_everything_below_it() {
+        let Course {
+            mut repository,
+            course,
+            module,
+            draft,
+            in_review,
+            published,
+        } = course();
+        let before = repository.get(published).unwrap().history.len();
+        repository.submit(course, "alice").unwrap();
+        assert_eq!(state(&repository, draft), State::InReview);
+        let late = repository.create(post("Lifetimes", "", &[]), "alice");
+        repository.add_child(module, late).unwrap();
+        repository.publish(course, "editor").unwrap();
+
+        for id in [course, module, draft, in_review, late, published] {
+            assert_eq!(state(&repository, id), State::Published, "{}", id);
+        }
+        // A draft goes through review on the way, an item already published is left alone
+        let steps: Vec<(State, State, &str)> = repository
+            .get(late)
+            .unwrap()
+            .history
+            .iter()
+            .map(|t| (t.from, t.to, t.by.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (State::Draft, State::InReview, "editor"),
+                (State::InReview, State::Published, "editor")
+            ]
+        );
+        assert_eq!(repository.get(published).unwrap().history.len(), before);
+    }
+
+    #[test]
+    fn an_archived_member_stops_the_whole_publish() {
+        let Course {
+            mut repository,
+            course,
+            module,
+            draft,
+            in_review,
+            published,
+        } = course();
+        repository.submit(course, "alice").unwrap();
+        repository.archive(published, "bob").unwrap();
+
+        assert_eq!(
+            repository.publish(course, "editor"),
+            Err(RepositoryError::InvalidTransition {
+                id: published,
+                from: State::Archived,
+                to: State::Published,
+            })
+        );
+        for id in [course, module, draft, in_review] {
+            assert_eq!(state(&repository, id), State::InReview, "{}", id);
+        }
+    }
+
+    #[test]
+    fn archiving_a_collection_archives_only_published_members() {
+        let Course {
+            mut repository,
+            course,
+            module,
+            draft,
+            published,
+            ..
+        } = course();
+        repository.submit(module, "alice").unwrap();
+        repository.publish(module, "bob").unwrap();
+        repository.submit(course, "alice").unwrap();
+        repository.publish(course, "bob").unwrap();
+        let unpublished = repository.create(post("Later", "", &[]), "alice");
+        repository.add_child(course, unpublished).unwrap();
+
+        repository.archive(course, "bob").unwrap();
+        for id in [course, module, draft, published] {
+            assert_eq!(state(&repository, id), State::Archived, "{}", id);
+        }
+        assert_eq!(state(&repository, unpublished), State::Draft);
+    }
+
+    #[test]
+    fn duration_rolls_up_through_nested_collections() {
+        let Course {
+            mut repository,
+            course,
+            module,
+            in_review,
+            ..
+        } = course();
+        let intro = repository.create(video("Intro", 90), "alice");
+        repository.insert_child(course, intro, 0).unwrap();
+
+        assert_eq!(repository.assemble(module).unwrap().duration(), 600);
+        assert_eq!(repository.assemble(course).unwrap().duration(), 690);
+
+        // The tree is assembled from current revisions, so an edit shows up at every level
+        repository.reject(in_review, "bob").unwrap();
+        repository
+            .update(in_review, video("Borrowing", 900), "alice", "re-cut")
+            .unwrap();
+        assert_eq!(repository.assemble(module).unwrap().duration(), 900);
+        assert_eq!(repository.assemble(course).unwrap().duration(), 990);
+        let summary = repository
+            .assemble(course)
+            .unwrap()
+            .as_collection()
+            .unwrap()
+            .summary();
+        assert_eq!(summary.duration, 990);
+        assert_eq!(summary.depth, 2);
+    }
+}
@@ -0,0 +1,704 @@
+use crate::content::Content;
+use crate::export::fields;
+use crate::repository::{ContentId, Repository, State};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// Fields that make up an item's searchable text; the title is indexed first
+const TEXT_FIELDS: [&str; 4] = ["title", "body", "description", "show notes"];
+
+// BM25 parameters: term frequency saturation and document length normalization
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// Lowercased, stemmed words: "Factories, factory!" -> ["factori", "factori"]
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| stem(&word.to_lowercase()))
+        .collect()
+}
+
+fn is_vowel(word: &[u8], i: usize) -> bool {
+    match word[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => true,
+        b'y' => i > 0 && !is_vowel(word, i - 1),
+        _ => false,
+    }
+}
+
+fn has_vowel(word: &str) -> bool {
+    (0..word.len()).any(|i| is_vowel(word.as_bytes(), i))
+}
+
+// The plural and past-tense rules of the Porter stemmer (steps 1a to 1c): enough to make
+// "patterns", "created", "creating" and "factories" meet "pattern", "create" and "factory"
+pub fn stem(word: &str) -> String {
+    if word.len() <= 3 || !word.is_ascii() {
+        return word.to_string();
+    }
+    let mut word = word.to_string();
+
+    // 1a: plurals
+    if word.ends_with("sses") || word.ends_with("ies") {
+        word.truncate(word.len() - 2);
+    } else if word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") {
+        word.pop();
+    }
+
+    // 1b: -eed, -ed, -ing
+    if word.ends_with("eed") {
+        if word.len() > 4 {
+            word.pop();
+        }
+    } else {
+        let stripped = ["ed", "ing"].iter().find_map(|suffix| {
+            word.strip_suffix(suffix)
+                .filter(|stem| stem.len() >= 2 && has_vowel(stem))
+                .map(str::to_string)
+        });
+        if let Some(stripped) = stripped {
+            word = stripped;
+            let bytes = word.as_bytes();
+            let n = bytes.len();
+            if word.ends_with("at") || word.ends_with("bl") || word.ends_with("iz") {
+                word.push('e');
+            } else if n >= 2
+                && bytes[n - 1] == bytes[n - 2]
+                && !is_vowel(bytes, n - 1)
+                && !matches!(bytes[n - 1], b'l' | b's' | b'z')
+            {
+                word.pop();
+            } else if n == 3 && !is_vowel(bytes, 0) && is_vowel(bytes, 1) && !is_vowel(bytes, 2) {
+                // Short stems such as "hop" in "hoping" get their e back
+                word.push('e');
+            }
+        }
+    }
+
+    // 1c: terminal y after a vowel-bearing stem
+    if word.ends_with('y') && has_vowel(&word[..word.len() - 1]) {
+        word.pop();
+        word.push('i');
+    }
+    word
+}
+
+// Inverted index: term -> item -> positions of the term in the item's text
+#[derive(Debug, Default)]
+pub struct Index {
+    postings: BTreeMap<String, BTreeMap<ContentId, Vec<usize>>>,
+    lengths: BTreeMap<ContentId, usize>,
+}
+
+impl Index {
+    // (Re)indexes an item from its current content
+    pub fn insert(&mut self, id: ContentId, content: &dyn Content) {
+        self.remove(id);
+        let fields = fields(content);
+        let mut position = 0;
+        for field in TEXT_FIELDS {
+            let Some(text) = fields.get(field) else {
+                continue;
+            };
+            for term in tokenize(text) {
+                let positions = self
+                    .postings
+                    .entry(term)
+                    .or_default()
+                    .entry(id)
+                    .or_default();
+                positions.push(position);
+                position += 1;
+            }
+            // Leave a gap so a phrase cannot run from the title into the body
+            position += 1;
+        }
+        self.lengths.insert(id, position);
+    }
+
+    pub fn remove(&mut self, id: ContentId) {
+        if self.lengths.remove(&id).is_none() {
+            return;
+        }
+        self.postings.retain(|_, items| {
+            items.remove(&id);
+            !items.is_empty()
+        });
+    }
+
+    pub fn items(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn terms(&self) -> usize {
+        self.postings.len()
+    }
+
+    fn items_with(&self, term: &str) -> BTreeSet<ContentId> {
+        self.postings
+            .get(term)
+            .map(|items| items.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn items_with_phrase(&self, terms: &[String]) -> BTreeSet<ContentId> {
+        let Some((first, rest)) = terms.split_first() else {
+            return BTreeSet::new();
+        };
+        let Some(starts) = self.postings.get(first) else {
+            return BTreeSet::new();
+        };
+        starts
+            .iter()
+            .filter(|(id, positions)| {
+                positions.iter().any(|start| {
+                    rest.iter().enumerate().all(|(offset, term)| {
+                        self.postings
+                            .get(term)
+                            .and_then(|items| items.get(id))
+                            .is_some_and(|p| p.contains(&(start + offset + 1)))
+                    })
+                })
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Okapi BM25 contribution of one term to one item
+    fn bm25(&self, term: &str, id: ContentId) -> f64 {
+        let Some(items) = self.postings.get(term) else {
+            return 0.0;
+        };
+        let Some(positions) = items.get(&id) else {
+            return 0.0;
+        };
+        let n = self.lengths.len() as f64;
+        let matching = items.len() as f64;
+        let idf = (1.0 + (n - matching + 0.5) / (matching + 0.5)).ln();
+        let average = self.lengths.values().sum::<usize>() as f64 / n;
+        let length = self.lengths[&id] as f64;
+        let tf = positions.len() as f64;
+        idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / average))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    Empty,
+    UnexpectedEnd,
+    Unexpected(String),
+    UnterminatedPhrase,
+    NoTerms(String),
+    UnknownFilter(String),
+    // `tag:` or `state:` with nothing after the colon, as in `tag:"design patterns"`
+    EmptyFilter(String),
+    UnknownState(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::Empty => write!(f, "empty query"),
+            QueryError::UnexpectedEnd => write!(f, "query ends too early"),
+            QueryError::Unexpected(token) => write!(f, "unexpected `{}`", token),
+            QueryError::UnterminatedPhrase => write!(f, "phrase is missing its closing quote"),
+            QueryError::NoTerms(text) => write!(f, "`{}` contains no searchable words", text),
+            QueryError::UnknownFilter(name) => {
+                write!(f, "unknown filter `{}:` (use tag: or state:)", name)
+            }
+            QueryError::EmptyFilter(name) => write!(
+                f,
+                "`{}:` needs a value right after the colon; tags are single words, \
+                 e.g. tag:design-patterns",
+                name
+            ),
+            QueryError::UnknownState(state) => write!(
+                f,
+                "unknown state `{}` (draft, review, published or archived)",
+                state
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),
+    Phrase(Vec<String>),
+    Tag(String),
+    State(State),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Open,
+    Close,
+}
+
+fn lex(text: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(QueryError::UnterminatedPhrase),
+                    }
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Grammar, loosest binding first; adjacent clauses are joined with AND:
+//   or   := and ("OR" and)*
+//   and  := not ("AND"? not)*
+//   not  := "NOT" not | atom
+//   atom := "(" or ")" | "phrase" | tag:name | state:name | word
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.and()?;
+        while self.peek_keyword("OR") {
+            self.next += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.not()?;
+        loop {
+            if self.peek_keyword("AND") {
+                self.next += 1;
+            } else if self.peek().is_none()
+                || self.peek_keyword("OR")
+                || self.peek() == Some(&Token::Close)
+            {
+                return Ok(query);
+            }
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        if self.peek_keyword("NOT") {
+            self.next += 1;
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Query, QueryError> {
+        let token = self.peek().cloned().ok_or(QueryError::UnexpectedEnd)?;
+        self.next += 1;
+        match token {
+            Token::Open => {
+                let query = self.or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.next += 1;
+                        Ok(query)
+                    }
+                    Some(_) => Err(QueryError::Unexpected(self.describe())),
+                    None => Err(QueryError::UnexpectedEnd),
+                }
+            }
+            Token::Close => Err(QueryError::Unexpected(")".to_string())),
+            Token::Phrase(phrase) => words(&phrase),
+            Token::Word(word) if ["AND", "OR"].contains(&word.as_str()) => {
+                Err(QueryError::Unexpected(word))
+            }
+            Token::Word(word) => match word.split_once(':') {
+                Some((filter @ ("tag" | "state"), "")) => {
+                    Err(QueryError::EmptyFilter(filter.to_string()))
+                }
+                Some(("tag", tag)) => crate::taxonomy::normalize_tag(tag)
+                    .map(Query::Tag)
+                    .map_err(|_| QueryError::NoTerms(word.clone())),
+                Some(("state", state)) => parse_state(state).map(Query::State),
+                Some((filter, _)) if !filter.is_empty() => {
+                    Err(QueryError::UnknownFilter(filter.to_string()))
+                }
+                _ => words(&word),
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Token::Word(word)) => word.clone(),
+            Some(Token::Phrase(phrase)) => format!("\"{}\"", phrase),
+            Some(Token::Open) => "(".to_string(),
+            Some(Token::Close) => ")".to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+// A single word is a term; anything that tokenizes to several words ("design-patterns") is a
+// phrase
+fn words(text: &str) -> Result<Query, QueryError> {
+    let mut terms = tokenize(text);
+    match terms.len() {
+        0 => Err(QueryError::NoTerms(text.to_string())),
+        1 => Ok(Query::Term(terms.remove(0))),
+        _ => Ok(Query::Phrase(terms)),
+    }
+}
+
+fn parse_state(state: &str) -> Result<State, QueryError> {
+    match state.to_lowercase().as_str() {
+        "draft" => Ok(State::Draft),
+        "review" | "in-review" | "inreview" => Ok(State::InReview),
+        "published" => Ok(State::Published),
+        "archived" => Ok(State::Archived),
+        _ => Err(QueryError::UnknownState(state.to_string())),
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, QueryError> {
+        let tokens = lex(text)?;
+        if tokens.is_empty() {
+            return Err(QueryError::Empty);
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let query = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(QueryError::Unexpected(parser.describe()));
+        }
+        Ok(query)
+    }
+
+    fn matches(&self, repository: &Repository) -> BTreeSet<ContentId> {
+        let index = repository.index();
+        match self {
+            Query::Term(term) => index.items_with(term),
+            Query::Phrase(terms) => index.items_with_phrase(terms),
+            Query::Tag(tag) => repository
+                .list()
+                .filter(|entry| {
+                    entry
+                        .tags
+                        .iter()
+                        .any(|t| repository.taxonomy().is_within(t, tag))
+                })
+                .map(|entry| entry.id)
+                .collect(),
+            Query::State(state) => repository
+                .list()
+                .filter(|entry| entry.state == *state)
+                .map(|entry| entry.id)
+                .collect(),
+            Query::And(left, right) => {
+                let left = left.matches(repository);
+                let right = right.matches(repository);
+                left.intersection(&right).copied().collect()
+            }
+            Query::Or(left, right) => {
+                let mut left = left.matches(repository);
+                left.extend(right.matches(repository));
+                left
+            }
+            Query::Not(query) => {
+                let excluded = query.matches(repository);
+                repository
+                    .list()
+                    .map(|entry| entry.id)
+                    .filter(|id| !excluded.contains(id))
+                    .collect()
+            }
+        }
+    }
+
+    // Terms that count towards the score: everything not under a NOT
+    fn scoring_terms<'a>(&'a self, terms: &mut Vec<&'a str>) {
+        match self {
+            Query::Term(term) => terms.push(term),
+            Query::Phrase(phrase) => terms.extend(phrase.iter().map(String::as_str)),
+            Query::And(left, right) | Query::Or(left, right) => {
+                left.scoring_terms(terms);
+                right.scoring_terms(terms);
+            }
+            Query::Tag(_) | Query::State(_) | Query::Not(_) => {}
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::Term(term) => write!(f, "{}", term),
+            Query::Phrase(terms) => write!(f, "\"{}\"", terms.join(" ")),
+            Query::Tag(tag) => write!(f, "tag:{}", tag),
+            Query::State(state) => write!(f, "state:{}", state),
+            Query::And(left, right) => write!(f, "({} AND {})", left, right),
+            Query::Or(left, right) => write!(f, "({} OR {})", left, right),
+            Query::Not(query) => write!(f, "NOT {}", query),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub id: ContentId,
+    pub score: f64,
+}
+
+// Matching items in every state, best first; ties (including pure filter queries, which
+// score 0) by id. This is the editors' search, hence `state:`; visitors get `search_published`
+pub fn search(repository: &Repository, query: &Query) -> Vec<Hit> {
+    let mut terms = Vec::new();
+    query.scoring_terms(&mut terms);
+    let index = repository.index();
+    let mut hits: Vec<Hit> = query
+        .matches(repository)
+        .into_iter()
+        .map(|id| Hit {
+            id,
+            score: terms
+                .iter()
+                .fold(0.0, |score, term| score + index.bm25(term, id)),
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    hits
+}
+
+// Like `search`, limited to what visitors may see
+pub fn search_published(repository: &Repository, query: &Query) -> Vec<Hit> {
+    let mut hits = search(repository, query);
+    hits.retain(|hit| {
+        repository
+            .get(hit.id)
+            .is_ok_and(|entry| entry.state == State::Published)
+    });
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factory::{BlogPostFactory, ContentFactory, ContentRequest};
+
+    fn post(repository: &mut Repository, title: &str, body: &str, tags: &[&str]) -> ContentId {
+        let request = tags
+            .iter()
+            .fold(ContentRequest::new(title).body(body), |request, tag| {
+                request.tag(tag)
+            });
+        let content = BlogPostFactory.create_content(&request).unwrap();
+        repository.create(content, "alice")
+    }
+
+    fn ids(hits: &[Hit]) -> Vec<ContentId> {
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    fn term(term: &str) -> Box<Query> {
+        Box::new(Query::Term(term.to_string()))
+    }
+
+    #[test]
+    fn stem_plurals_and_past_tenses() {
+        for (word, stemmed) in [
+            ("patterns", "pattern"),
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("factories", "factori"),
+            ("factory", "factori"),
+            ("status", "status"),
+            ("created", "create"),
+            ("creating", "create"),
+            ("hopping", "hop"),
+            ("hoping", "hope"),
+            ("falling", "fall"),
+            ("agreed", "agree"),
+            ("feed", "feed"),
+            ("sing", "sing"),
+            ("cat", "cat"),
+            ("naïves", "naïves"),
+        ] {
+            assert_eq!(stem(word), stemmed, "{}", word);
+        }
+    }
+
+    #[test]
+    fn tokenize_lowercases_splits_and_stems() {
+        assert_eq!(
+            tokenize("Factories, factory! Design-patterns"),
+            ["factori", "factori", "design", "pattern"]
+        );
+    }
+
+    #[test]
+    fn bm25_ranks_frequent_terms_in_short_items_first() {
+        let mut repository = Repository::new();
+        let long = post(
+            &mut repository,
+            "Long",
+            "Rust appears once in a body that goes on and on about other things entirely",
+            &[],
+        );
+        let short = post(&mut repository, "Short", "Rust, rust and more rust", &[]);
+        post(&mut repository, "Other", "Nothing to see", &[]);
+        let hits = search(&repository, &Query::parse("rust").unwrap());
+        assert_eq!(ids(&hits), [short, long]);
+        assert!(hits[0].score > hits[1].score && hits[1].score > 0.0);
+    }
+
+    #[test]
+    fn rare_terms_weigh_more() {
+        let mut repository = Repository::new();
+        let common = post(&mut repository, "A", "rust rust", &[]);
+        let rare = post(&mut repository, "B", "rust tokio", &[]);
+        post(&mut repository, "C", "rust", &[]);
+        let hits = search(&repository, &Query::parse("rust OR tokio").unwrap());
+        assert_eq!(ids(&hits)[..2], [rare, common]);
+    }
+
+    #[test]
+    fn filter_only_queries_tie_and_sort_by_id() {
+        let mut repository = Repository::new();
+        let a = post(&mut repository, "A", "x", &["rust"]);
+        let b = post(&mut repository, "B", "y", &["rust"]);
+        let hits = search(&repository, &Query::parse("tag:rust").unwrap());
+        assert_eq!(ids(&hits), [a, b]);
+        assert!(hits.iter().all(|hit| hit.score == 0.0));
+    }
+
+    #[test]
+    fn phrases_need_adjacent_words_within_one_field() {
+        let mut repository = Repository::new();
+        let phrase = post(&mut repository, "Intro", "The factory method pattern", &[]);
+        post(&mut repository, "Apart", "A method for every factory", &[]);
+        post(&mut repository, "Factory", "Method acting", &[]);
+        let query = Query::parse("\"factory method\"").unwrap();
+        assert_eq!(
+            query,
+            Query::Phrase(vec!["factori".into(), "method".into()])
+        );
+        assert_eq!(ids(&search(&repository, &query)), [phrase]);
+        // A hyphenated word is a phrase too
+        let hyphenated = Query::parse("factory-method").unwrap();
+        assert_eq!(hyphenated, query);
+    }
+
+    #[test]
+    fn parse_and_or_not_and_filters() {
+        let tag = |tag: &str| Box::new(Query::Tag(tag.to_string()));
+        assert_eq!(
+            Query::parse("rust AND tag:Patterns").unwrap(),
+            Query::And(term("rust"), tag("patterns"))
+        );
+        // Adjacent clauses are joined with AND, which binds tighter than OR
+        assert_eq!(
+            Query::parse("a OR rust tag:x").unwrap(),
+            Query::Or(term("a"), Box::new(Query::And(term("rust"), tag("x"))))
+        );
+        assert_eq!(
+            Query::parse("(a OR rust) NOT state:draft").unwrap(),
+            Query::And(
+                Box::new(Query::Or(term("a"), term("rust"))),
+                Box::new(Query::Not(Box::new(Query::State(State::Draft))))
+            )
+        );
+        assert_eq!(
+            Query::parse("state:review").unwrap(),
+            Query::State(State::InReview)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (text, error) in [
+            ("  ", QueryError::Empty),
+            ("rust AND", QueryError::UnexpectedEnd),
+            ("(rust", QueryError::UnexpectedEnd),
+            ("rust)", QueryError::Unexpected(")".into())),
+            ("OR rust", QueryError::Unexpected("OR".into())),
+            ("\"factory method", QueryError::UnterminatedPhrase),
+            ("!!!", QueryError::NoTerms("!!!".into())),
+            ("author:alice", QueryError::UnknownFilter("author".into())),
+            ("state:deleted", QueryError::UnknownState("deleted".into())),
+        ] {
+            assert_eq!(Query::parse(text), Err(error), "{}", text);
+        }
+    }
+
+    #[test]
+    fn quoted_tag_is_an_error() {
+        let error = Query::parse("tag:\"design patterns\"").unwrap_err();
+        assert_eq!(error, QueryError::EmptyFilter("tag".into()));
+        assert!(error.to_string().contains("tag:design-patterns"));
+        assert_eq!(
+            Query::parse("tag:design-patterns").unwrap(),
+            Query::Tag("design-patterns".into())
+        );
+    }
+
+    #[test]
+    fn published_search_hides_other_states() {
+        let mut repository = Repository::new();
+        let draft = post(&mut repository, "Draft", "rust", &[]);
+        let published = post(&mut repository, "Published", "rust", &[]);
+        repository.submit(published, "bob").unwrap();
+        repository.publish(published, "bob").unwrap();
+        let query = Query::parse("rust").unwrap();
+        assert_eq!(ids(&search(&repository, &query)), [draft, published]);
+        assert_eq!(ids(&search_published(&repository, &query)), [published]);
+        // Even a `state:` filter cannot reach drafts
+        let drafts = Query::parse("state:draft").unwrap();
+        assert!(search_published(&repository, &drafts).is_empty());
+    }
+}
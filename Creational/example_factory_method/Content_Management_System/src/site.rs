@@ -0,0 +1,1042 @@
+use crate::content::{BlogPost, Collection, Content, Image, ImageGallery, Page, Podcast, Video};
+use crate::export::{duration, export, html_escape, ContentVisitor, RssItemExporter};
+use crate::factory::{
+    slugify, validate_slug, BlogPostFactory, ContentFactory, ContentRequest, ImageGalleryFactory,
+    PageFactory, PodcastFactory, ValidationError, VideoFactory,
+};
+use crate::repository::{civil_from_days, days_from_civil, ContentId, Entry, Repository};
+use crate::taxonomy::tag_counts;
+use minijinja::{context, AutoEscape, Environment};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Kept in the output directory; maps every generated file to a hash of the inputs it was
+// rendered from, so a rebuild only renders what changed
+pub const MANIFEST: &str = ".sitegen-manifest";
+
+// Names the build writes at the top of the output directory (as `.html`, `.xml` or a
+// directory), so no page may use them as its slug
+const RESERVED_SLUGS: [&str; 5] = ["index", "posts", "tags", "feed", "sitemap"];
+
+// Built-in templates; a file of the same name in `<content>/templates/` replaces one
+const TEMPLATES: [(&str, &str); 4] = [
+    ("base.html", include_str!("templates/base.html")),
+    ("item.html", include_str!("templates/item.html")),
+    ("index.html", include_str!("templates/index.html")),
+    ("tag.html", include_str!("templates/tag.html")),
+];
+
+#[derive(Debug)]
+pub enum SiteError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    MissingFrontMatter(PathBuf),
+    FrontMatter {
+        path: PathBuf,
+        error: String,
+    },
+    UnknownType {
+        path: PathBuf,
+        kind: String,
+    },
+    InvalidDate {
+        path: PathBuf,
+        date: String,
+    },
+    Invalid {
+        path: PathBuf,
+        error: ValidationError,
+    },
+    DuplicateSlug {
+        slug: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    ReservedSlug {
+        path: PathBuf,
+        slug: String,
+    },
+    // `path` is the first file carrying the tag, if any
+    InvalidTag {
+        path: Option<PathBuf>,
+        tag: String,
+    },
+    DuplicateOutput {
+        path: String,
+        first: String,
+        second: String,
+    },
+    Template {
+        name: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SiteError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SiteError::MissingFrontMatter(path) => write!(
+                f,
+                "{}: missing front matter (the file must start with a `---` line)",
+                path.display()
+            ),
+            SiteError::FrontMatter { path, error } => {
+                write!(f, "{}: invalid front matter: {}", path.display(), error)
+            }
+            SiteError::UnknownType { path, kind } => write!(
+                f,
+                "{}: unknown type `{}` (post, video, gallery, podcast or page)",
+                path.display(),
+                kind
+            ),
+            SiteError::InvalidDate { path, date } => {
+                write!(
+                    f,
+                    "{}: invalid date `{}` (use YYYY-MM-DD)",
+                    path.display(),
+                    date
+                )
+            }
+            SiteError::Invalid { path, error } => write!(f, "{}: {}", path.display(), error),
+            SiteError::DuplicateSlug {
+                slug,
+                first,
+                second,
+            } => write!(
+                f,
+                "{} and {} would both be published as `{}`",
+                first.display(),
+                second.display(),
+                slug
+            ),
+            SiteError::ReservedSlug { path, slug } => write!(
+                f,
+                "{}: `{}` is reserved for a generated file; choose another slug",
+                path.display(),
+                slug
+            ),
+            SiteError::InvalidTag { path, tag } => {
+                if let Some(path) = path {
+                    write!(f, "{}: ", path.display())?;
+                }
+                write!(f, "tag `{}` has no letters or digits to name its page", tag)
+            }
+            SiteError::DuplicateOutput {
+                path,
+                first,
+                second,
+            } => write!(
+                f,
+                "{} and {} would both be written to {}",
+                first, second, path
+            ),
+            SiteError::Template { name, error } => write!(f, "template {}: {}", name, error),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> SiteError + '_ {
+    move |error| SiteError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+// The YAML block between the leading `---` lines of a source file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    pub title: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+    pub url: Option<String>,
+    pub duration: Option<u32>,
+    pub episode: Option<u32>,
+    pub slug: Option<String>,
+    pub draft: bool,
+    pub images: Vec<FrontMatterImage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatterImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub caption: String,
+}
+
+// Splits "---\n<yaml>\n---\n<markdown>" into its parts
+pub fn parse_source(path: &Path, text: &str) -> Result<(FrontMatter, String), SiteError> {
+    let missing = || SiteError::MissingFrontMatter(path.to_path_buf());
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let rest = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+        .ok_or_else(missing)?;
+    let (yaml, body) = match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + 4..];
+            let body = body.split_once('\n').map(|(_, body)| body).unwrap_or("");
+            (&rest[..end], body)
+        }
+        None => return Err(missing()),
+    };
+    let front: FrontMatter = serde_yaml_ng::from_str(yaml).map_err(|e| SiteError::FrontMatter {
+        path: path.to_path_buf(),
+        error: e.to_string(),
+    })?;
+    Ok((front, body.trim().to_string()))
+}
+
+fn factory_for(kind: &str) -> Option<&'static dyn ContentFactory> {
+    match kind {
+        "post" => Some(&BlogPostFactory),
+        "video" => Some(&VideoFactory),
+        "gallery" => Some(&ImageGalleryFactory),
+        "podcast" => Some(&PodcastFactory),
+        "page" => Some(&PageFactory),
+        _ => None,
+    }
+}
+
+// "2024-03-01" -> days since 1970-01-01
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-').map(str::parse::<u32>);
+    let (year, month, day) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day);
+    // Rejects dates such as February 30th, which would silently roll over
+    (civil_from_days(days) == (year as i64, month, day)).then_some(days)
+}
+
+// What the site knows about an item beyond its content
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub kind: String,
+    pub slug: String,
+    pub date: Option<String>,
+    pub days: Option<i64>,
+    pub draft: bool,
+    pub hash: u64,
+}
+
+impl Source {
+    // Output file, relative to the output directory
+    pub fn output(&self) -> String {
+        if self.kind == "page" {
+            format!("{}.html", self.slug)
+        } else {
+            format!("posts/{}.html", self.slug)
+        }
+    }
+}
+
+// A content directory loaded into a repository: published items, plus drafts that stay drafts
+pub struct Site {
+    pub repository: Repository,
+    pub sources: BTreeMap<ContentId, Source>,
+    templates: Vec<(String, String)>,
+}
+
+impl Site {
+    pub fn load(content_dir: &Path) -> Result<Site, SiteError> {
+        let mut files = Vec::new();
+        collect_markdown(content_dir, &mut files)?;
+        files.sort();
+
+        let mut repository = Repository::new();
+        let mut sources = BTreeMap::new();
+        let mut slugs: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for path in files {
+            let relative = path
+                .strip_prefix(content_dir)
+                .unwrap_or(&path)
+                .to_path_buf();
+            let text = fs::read_to_string(&path).map_err(io_error(&path))?;
+            let (front, body) = parse_source(&relative, &text)?;
+            let kind = front.kind.clone().unwrap_or_else(|| "post".to_string());
+            let factory = factory_for(&kind).ok_or_else(|| SiteError::UnknownType {
+                path: relative.clone(),
+                kind: kind.clone(),
+            })?;
+            let invalid = |error| SiteError::Invalid {
+                path: relative.clone(),
+                error,
+            };
+
+            let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+            let slug = front.slug.clone().unwrap_or_else(|| slugify(&stem));
+            validate_slug(&slug).map_err(invalid)?;
+            if kind == "page" && RESERVED_SLUGS.contains(&slug.as_str()) {
+                return Err(SiteError::ReservedSlug {
+                    path: relative,
+                    slug,
+                });
+            }
+            if let Some(tag) = front.tags.iter().find(|tag| tag_slug(tag).is_none()) {
+                return Err(SiteError::InvalidTag {
+                    path: Some(relative),
+                    tag: tag.clone(),
+                });
+            }
+            let days = match &front.date {
+                Some(date) => Some(parse_date(date).ok_or_else(|| SiteError::InvalidDate {
+                    path: relative.clone(),
+                    date: date.clone(),
+                })?),
+                None => None,
+            };
+            let request = ContentRequest {
+                title: front.title.clone(),
+                body,
+                tags: front.tags.clone(),
+                url: front.url.clone(),
+                duration_secs: front.duration,
+                episode: front.episode,
+                images: front
+                    .images
+                    .iter()
+                    .map(|image| Image::new(&image.url, image.width, image.height, &image.caption))
+                    .collect(),
+                slug: Some(slug.clone()),
+            };
+            let content = factory.create_content(&request).map_err(invalid)?;
+
+            if let Some(first) = slugs.insert(slug.clone(), relative.clone()) {
+                return Err(SiteError::DuplicateSlug {
+                    slug,
+                    first,
+                    second: relative,
+                });
+            }
+            let id = repository.create(content, "sitegen");
+            for tag in &front.tags {
+                repository
+                    .add_tag(id, tag)
+                    .map_err(|_| invalid(ValidationError::Empty("tags")))?;
+            }
+            if !front.draft {
+                repository.submit(id, "sitegen").unwrap();
+                repository.publish(id, "sitegen").unwrap();
+            }
+            sources.insert(
+                id,
+                Source {
+                    path: relative,
+                    kind,
+                    slug,
+                    date: front.date,
+                    days,
+                    draft: front.draft,
+                    hash: fnv1a(text.as_bytes()),
+                },
+            );
+        }
+
+        let mut templates = Vec::new();
+        for (name, builtin) in TEMPLATES {
+            let path = content_dir.join("templates").join(name);
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => builtin.to_string(),
+                Err(e) => return Err(io_error(&path)(e)),
+            };
+            templates.push((name.to_string(), source));
+        }
+
+        Ok(Site {
+            repository,
+            sources,
+            templates,
+        })
+    }
+}
+
+fn collect_markdown(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), SiteError> {
+    for entry in fs::read_dir(dir).map_err(io_error(dir))? {
+        let path = entry.map_err(io_error(dir))?.path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name != "templates") {
+                collect_markdown(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Tags name files under `tags/`, so they follow the same rules as slugs: "Rust Tips" becomes
+// `rust-tips`, and "../../x" becomes `x` rather than a path out of the output directory
+fn tag_slug(tag: &str) -> Option<String> {
+    let slug = slugify(tag);
+    validate_slug(&slug).ok().map(|()| slug)
+}
+
+// Stable across runs and Rust versions, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn combine(hashes: impl IntoIterator<Item = u64>) -> u64 {
+    let bytes: Vec<u8> = hashes.into_iter().flat_map(u64::to_le_bytes).collect();
+    fnv1a(&bytes)
+}
+
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(markdown));
+    html
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ImageView {
+    url: String,
+    width: u32,
+    height: u32,
+    caption: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct TagView {
+    name: String,
+    slug: String,
+}
+
+// What the templates see of one item
+#[derive(Debug, Default, Serialize)]
+struct ItemView {
+    kind: String,
+    title: String,
+    link: String,
+    date: Option<String>,
+    draft: bool,
+    tags: Vec<TagView>,
+    body_html: String,
+    media_url: Option<String>,
+    duration: Option<String>,
+    episode: Option<u32>,
+    images: Vec<ImageView>,
+}
+
+// Fills in the content-specific part of an `ItemView`; bodies are Markdown
+impl ContentVisitor for ItemView {
+    fn visit_blog_post(&mut self, post: &BlogPost) {
+        self.title = post.title.clone();
+        self.body_html = markdown_to_html(&post.body);
+    }
+
+    fn visit_video(&mut self, video: &Video) {
+        self.title = video.title.clone();
+        self.body_html = markdown_to_html(&video.description);
+        self.media_url = Some(video.url.clone());
+        self.duration = Some(duration(video.duration));
+    }
+
+    fn visit_image_gallery(&mut self, gallery: &ImageGallery) {
+        self.title = gallery.title.clone();
+        self.body_html = markdown_to_html(&gallery.description);
+        self.images = gallery
+            .images
+            .iter()
+            .map(|image| ImageView {
+                url: image.url.clone(),
+                width: image.width,
+                height: image.height,
+                caption: image.caption.clone(),
+            })
+            .collect();
+    }
+
+    fn visit_podcast(&mut self, podcast: &Podcast) {
+        self.title = podcast.title.clone();
+        self.body_html = markdown_to_html(&podcast.show_notes);
+        self.media_url = Some(podcast.audio_url.clone());
+        self.duration = Some(duration(podcast.duration));
+        self.episode = Some(podcast.episode);
+    }
+
+    fn visit_page(&mut self, page: &Page) {
+        self.title = page.title.clone();
+        self.body_html = markdown_to_html(&page.body);
+    }
+
+    fn visit_collection(&mut self, collection: &Collection) {
+        self.title = collection.title.clone();
+        let items: String = collection
+            .items
+            .iter()
+            .map(|item| format!("1. {}\n", item.title()))
+            .collect();
+        self.body_html = markdown_to_html(&format!("{}\n\n{}", collection.description, items));
+        if collection.duration() > 0 {
+            self.duration = Some(duration(collection.duration()));
+        }
+    }
+}
+
+fn item_view(entry: &Entry, source: &Source, tag_slugs: &BTreeMap<String, String>) -> ItemView {
+    let mut view = ItemView {
+        kind: source.kind.clone(),
+        link: source.output(),
+        date: source.date.clone(),
+        draft: source.draft,
+        tags: entry
+            .tags
+            .iter()
+            .map(|tag| TagView {
+                name: tag.clone(),
+                slug: tag_slugs[tag].clone(),
+            })
+            .collect(),
+        ..ItemView::default()
+    };
+    entry.current().content().accept(&mut view);
+    view
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub title: String,
+    pub base_url: String,
+    // Also render drafts, marked as such; for previews
+    pub drafts: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    pub written: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+// One file to generate: its path, what produces it (for error messages), a hash of
+// everything it depends on, and how to render it
+struct Output<'a> {
+    path: String,
+    origin: String,
+    hash: u64,
+    render: Box<dyn FnOnce() -> Result<String, SiteError> + 'a>,
+}
+
+impl Site {
+    // Items going on the site, newest first; undated items sort last, by title
+    fn selected(&self, options: &BuildOptions) -> Vec<(&Entry, &Source)> {
+        let mut items: Vec<(&Entry, &Source)> = self
+            .repository
+            .list()
+            .filter(|entry| options.drafts || !self.sources[&entry.id].draft)
+            .map(|entry| (entry, &self.sources[&entry.id]))
+            .collect();
+        items.sort_by(|(a_entry, a), (b_entry, b)| {
+            b.days
+                .is_some()
+                .cmp(&a.days.is_some())
+                .then(b.days.cmp(&a.days))
+                .then_with(|| {
+                    let title = |entry: &Entry| entry.current().content().title().to_string();
+                    title(a_entry).cmp(&title(b_entry))
+                })
+        });
+        items
+    }
+
+    fn environment(&self) -> Result<Environment<'_>, SiteError> {
+        let mut environment = Environment::new();
+        // Same escaping as the exporters; minijinja's default also escapes `/`, which makes
+        // every link in the output unreadable
+        environment.set_formatter(|out, state, value| match value.as_str() {
+            Some(s) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => out
+                .write_str(&html_escape(s))
+                .map_err(minijinja::Error::from),
+            _ => minijinja::escape_formatter(out, state, value),
+        });
+        for (name, source) in &self.templates {
+            environment
+                .add_template(name, source)
+                .map_err(|e| SiteError::Template {
+                    name: name.clone(),
+                    error: e.to_string(),
+                })?;
+        }
+        Ok(environment)
+    }
+
+    // Renders into `output_dir`, skipping every file whose inputs are unchanged since the last
+    // build and deleting files that are no longer produced
+    pub fn build(
+        &self,
+        output_dir: &Path,
+        options: &BuildOptions,
+    ) -> Result<BuildReport, SiteError> {
+        let environment = self.environment()?;
+        let render = |name: &str, ctx: minijinja::Value| {
+            environment
+                .get_template(name)
+                .and_then(|template| template.render(ctx))
+                .map_err(|e| SiteError::Template {
+                    name: name.to_string(),
+                    error: format!("{:#}", e),
+                })
+        };
+        let site = context! { title => options.title, base_url => options.base_url };
+        let base_hash = combine(
+            self.templates
+                .iter()
+                .map(|(_, source)| fnv1a(source.as_bytes()))
+                .chain([
+                    fnv1a(env!("CARGO_PKG_VERSION").as_bytes()),
+                    fnv1a(options.title.as_bytes()),
+                    fnv1a(options.base_url.as_bytes()),
+                    options.drafts as u64,
+                ]),
+        );
+
+        let items = self.selected(options);
+        let counts = tag_counts(
+            self.repository.taxonomy(),
+            items.iter().map(|(entry, _)| &entry.tags),
+        );
+        let members = |tag: &str| -> Vec<(&Entry, &Source)> {
+            items
+                .iter()
+                .filter(|(entry, _)| {
+                    entry
+                        .tags
+                        .iter()
+                        .any(|t| self.repository.taxonomy().is_within(t, tag))
+                })
+                .copied()
+                .collect()
+        };
+        // Front matter tags were checked on load; this also covers tags added since
+        let mut tag_slugs = BTreeMap::new();
+        for count in &counts {
+            let slug = tag_slug(&count.tag).ok_or_else(|| SiteError::InvalidTag {
+                path: members(&count.tag)
+                    .first()
+                    .map(|(_, source)| source.path.clone()),
+                tag: count.tag.clone(),
+            })?;
+            tag_slugs.insert(count.tag.clone(), slug);
+        }
+        let tag_slugs = &tag_slugs;
+        let tag_pages: Vec<&str> = counts
+            .iter()
+            .map(|count| tag_slugs[&count.tag].as_str())
+            .collect();
+
+        let mut outputs: Vec<Output> = Vec::new();
+        for (entry, source) in &items {
+            let (entry, source) = (*entry, *source);
+            let site = site.clone();
+            outputs.push(Output {
+                path: source.output(),
+                origin: source.path.display().to_string(),
+                hash: combine([base_hash, source.hash]),
+                render: Box::new(move || {
+                    let root = if source.kind == "page" { "" } else { "../" };
+                    render(
+                        "item.html",
+                        context! { site, root, item => item_view(entry, source, tag_slugs) },
+                    )
+                }),
+            });
+        }
+
+        // Everything below lists several items, so any change to the selection re-renders it
+        let all_hash = combine(
+            [base_hash].into_iter().chain(
+                items
+                    .iter()
+                    .map(|(_, source)| combine([fnv1a(source.output().as_bytes()), source.hash]))
+                    .chain(tag_slugs.values().map(|slug| fnv1a(slug.as_bytes()))),
+            ),
+        );
+        let views = || -> Vec<ItemView> {
+            items
+                .iter()
+                .map(|(entry, source)| item_view(entry, source, tag_slugs))
+                .collect()
+        };
+        {
+            let (site, counts) = (site.clone(), counts.clone());
+            outputs.push(Output {
+                path: "index.html".to_string(),
+                origin: "the index".to_string(),
+                hash: all_hash,
+                render: Box::new(move || {
+                    let (pages, items): (Vec<ItemView>, Vec<ItemView>) =
+                        views().into_iter().partition(|view| view.kind == "page");
+                    let tags: Vec<_> = counts
+                        .iter()
+                        .map(|count| {
+                            let slug = &tag_slugs[&count.tag];
+                            context! { name => count.tag, slug, count => count.total }
+                        })
+                        .collect();
+                    render(
+                        "index.html",
+                        context! { site, root => "", items, pages, tags },
+                    )
+                }),
+            });
+        }
+        for count in &counts {
+            let tag = count.tag.clone();
+            let members = members(&tag);
+            let site = site.clone();
+            outputs.push(Output {
+                path: format!("tags/{}.html", tag_slugs[&tag]),
+                origin: format!("tag `{}`", tag),
+                hash: combine(
+                    [base_hash, fnv1a(tag.as_bytes())]
+                        .into_iter()
+                        .chain(members.iter().map(|(_, source)| source.hash)),
+                ),
+                render: Box::new(move || {
+                    let items: Vec<ItemView> = members
+                        .iter()
+                        .map(|(entry, source)| item_view(entry, source, tag_slugs))
+                        .collect();
+                    render("tag.html", context! { site, root => "../", tag, items })
+                }),
+            });
+        }
+        outputs.push(Output {
+            path: "sitemap.xml".to_string(),
+            origin: "the sitemap".to_string(),
+            hash: all_hash,
+            render: Box::new(|| Ok(sitemap(&items, &tag_pages, &options.base_url))),
+        });
+        outputs.push(Output {
+            path: "feed.xml".to_string(),
+            origin: "the feed".to_string(),
+            hash: all_hash,
+            render: Box::new(|| Ok(feed(&items, options))),
+        });
+
+        // Checked before anything is written, so a clash never leaves a half-built site
+        let mut claimed: BTreeMap<&str, &str> = BTreeMap::new();
+        for output in &outputs {
+            if let Some(first) = claimed.insert(&output.path, &output.origin) {
+                return Err(SiteError::DuplicateOutput {
+                    path: output.path.clone(),
+                    first: first.to_string(),
+                    second: output.origin.clone(),
+                });
+            }
+        }
+
+        let manifest_path = output_dir.join(MANIFEST);
+        let previous = read_manifest(&manifest_path);
+        let mut report = BuildReport::default();
+        let mut manifest = BTreeMap::new();
+        for output in outputs {
+            let path = output_dir.join(&output.path);
+            if previous.get(&output.path) == Some(&output.hash) && path.exists() {
+                report.unchanged.push(output.path.clone());
+            } else {
+                let text = (output.render)()?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(io_error(parent))?;
+                }
+                fs::write(&path, text).map_err(io_error(&path))?;
+                report.written.push(output.path.clone());
+            }
+            manifest.insert(output.path, output.hash);
+        }
+        for stale in previous.keys().filter(|path| !manifest.contains_key(*path)) {
+            let path = output_dir.join(stale);
+            match fs::remove_file(&path) {
+                Ok(()) => report.removed.push(stale.clone()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(io_error(&path)(e)),
+            }
+        }
+        write_manifest(&manifest_path, &manifest)?;
+        Ok(report)
+    }
+}
+
+// One "<hash> <path>" line per generated file; a missing or unreadable manifest means a full
+// rebuild
+fn read_manifest(path: &Path) -> BTreeMap<String, u64> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (hash, file) = line.split_once(' ')?;
+            Some((file.to_string(), u64::from_str_radix(hash, 16).ok()?))
+        })
+        .collect()
+}
+
+fn write_manifest(path: &Path, manifest: &BTreeMap<String, u64>) -> Result<(), SiteError> {
+    let text: String = manifest
+        .iter()
+        .map(|(file, hash)| format!("{:016x} {}\n", hash, file))
+        .collect();
+    fs::write(path, text).map_err(io_error(path))
+}
+
+fn url(base_url: &str, path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path)
+}
+
+fn sitemap(items: &[(&Entry, &Source)], tag_slugs: &[&str], base_url: &str) -> String {
+    let mut locations = vec![(url(base_url, "index.html"), None)];
+    locations.extend(
+        items
+            .iter()
+            .map(|(_, source)| (url(base_url, &source.output()), source.date.clone())),
+    );
+    locations.extend(
+        tag_slugs
+            .iter()
+            .map(|slug| (url(base_url, &format!("tags/{}.html", slug)), None)),
+    );
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (location, date) in locations {
+        xml.push_str(&format!("  <url><loc>{}</loc>", html_escape(&location)));
+        if let Some(date) = date {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", date));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+// RSS channel of everything except standalone pages, newest first
+fn feed(items: &[(&Entry, &Source)], options: &BuildOptions) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n\
+         <title>{}</title>\n<link>{}</link>\n<description>{}</description>\n",
+        html_escape(&options.title),
+        html_escape(&url(&options.base_url, "index.html")),
+        html_escape(&options.title)
+    );
+    for (entry, source) in items.iter().filter(|(_, source)| source.kind != "page") {
+        let published = match source.days {
+            Some(days) => UNIX_EPOCH + Duration::from_secs(days.max(0) as u64 * 86_400),
+            None => entry.published_at().unwrap_or_else(SystemTime::now),
+        };
+        let mut exporter =
+            RssItemExporter::new(&url(&options.base_url, &source.output()), published);
+        xml.push_str(&export(entry.current().content(), &mut exporter));
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A fresh `<tmp>/sitegen-test-<pid>-<n>/` with `content/` holding the given files
+    fn content(files: &[(&str, &str)]) -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "sitegen-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("content")).unwrap();
+        for (name, text) in files {
+            fs::write(root.join("content").join(name), text).unwrap();
+        }
+        root
+    }
+
+    fn options() -> BuildOptions {
+        BuildOptions {
+            title: "Test".to_string(),
+            base_url: "https://example.com".to_string(),
+            drafts: false,
+        }
+    }
+
+    #[test]
+    fn tags_cannot_escape_the_output_directory() {
+        let root = content(&[(
+            "a.md",
+            "---\ntitle: A\ntags: [\"../../escaped\", \"Rust Tips\"]\n---\nBody\n",
+        )]);
+        let out = root.join("out").join("site");
+        let site = Site::load(&root.join("content")).unwrap();
+        let report = site.build(&out, &options()).unwrap();
+
+        assert!(report.written.contains(&"tags/escaped.html".to_string()));
+        assert!(report.written.contains(&"tags/rust-tips.html".to_string()));
+        assert!(!root.join("out").join("escaped.html").exists());
+        assert!(!root.join("escaped.html").exists());
+        let item = fs::read_to_string(out.join("posts/a.html")).unwrap();
+        assert!(item.contains("href=\"../tags/escaped.html\""));
+        let sitemap = fs::read_to_string(out.join("sitemap.xml")).unwrap();
+        assert!(sitemap.contains("https://example.com/tags/rust-tips.html"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tag_without_letters_or_digits_is_rejected() {
+        let root = content(&[("a.md", "---\ntitle: A\ntags: [\"!!!\"]\n---\nBody\n")]);
+        let error = Site::load(&root.join("content")).err().unwrap();
+        assert!(matches!(error, SiteError::InvalidTag { tag, .. } if tag == "!!!"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn page_cannot_take_a_generated_name() {
+        for slug in RESERVED_SLUGS {
+            let root = content(&[(
+                &format!("{}.md", slug),
+                "---\ntitle: Home\ntype: page\n---\nBody\n",
+            )]);
+            let error = Site::load(&root.join("content")).err().unwrap();
+            assert!(matches!(error, SiteError::ReservedSlug { .. }), "{}", slug);
+            fs::remove_dir_all(root).unwrap();
+        }
+    }
+
+    #[test]
+    fn colliding_outputs_fail_before_anything_is_written() {
+        let root = content(&[("a.md", "---\ntitle: A\ntags: [\"c++\", \"c\"]\n---\nBody\n")]);
+        let out = root.join("out");
+        let site = Site::load(&root.join("content")).unwrap();
+        let error = site.build(&out, &options()).err().unwrap();
+        assert!(matches!(error, SiteError::DuplicateOutput { path, .. } if path == "tags/c.html"));
+        assert!(!out.exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    fn sorted(mut paths: Vec<String>) -> Vec<String> {
+        paths.sort();
+        paths
+    }
+
+    fn two_posts() -> PathBuf {
+        content(&[
+            ("a.md", "---\ntitle: A\ntags: [rust]\n---\nFirst\n"),
+            ("b.md", "---\ntitle: B\ntags: [go]\n---\nSecond\n"),
+        ])
+    }
+
+    #[test]
+    fn rebuilding_without_changes_writes_nothing() {
+        let root = two_posts();
+        let out = root.join("out");
+        let first = Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+        let second = Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+
+        assert!(second.written.is_empty(), "{:?}", second.written);
+        assert!(second.removed.is_empty(), "{:?}", second.removed);
+        assert_eq!(sorted(second.unchanged), sorted(first.written));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn editing_a_post_rewrites_only_what_lists_it() {
+        let root = two_posts();
+        let out = root.join("out");
+        Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+        fs::write(
+            root.join("content/a.md"),
+            "---\ntitle: A\ntags: [rust]\n---\nFirst, revised\n",
+        )
+        .unwrap();
+        let report = Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+
+        assert_eq!(
+            sorted(report.written),
+            [
+                "feed.xml",
+                "index.html",
+                "posts/a.html",
+                "sitemap.xml",
+                "tags/rust.html"
+            ]
+        );
+        assert_eq!(sorted(report.unchanged), ["posts/b.html", "tags/go.html"]);
+        assert!(fs::read_to_string(out.join("posts/a.html"))
+            .unwrap()
+            .contains("First, revised"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn deleting_a_post_removes_its_files() {
+        let root = two_posts();
+        let out = root.join("out");
+        Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+        fs::remove_file(root.join("content/b.md")).unwrap();
+        let report = Site::load(&root.join("content"))
+            .unwrap()
+            .build(&out, &options())
+            .unwrap();
+
+        assert_eq!(sorted(report.removed), ["posts/b.html", "tags/go.html"]);
+        assert!(!out.join("posts/b.html").exists());
+        assert!(!out.join("tags/go.html").exists());
+        assert!(report.written.contains(&"index.html".to_string()));
+        assert_eq!(sorted(report.unchanged), ["posts/a.html", "tags/rust.html"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_deleted_output_file_is_written_again() {
+        let root = two_posts();
+        let out = root.join("out");
+        let site = Site::load(&root.join("content")).unwrap();
+        site.build(&out, &options()).unwrap();
+        fs::remove_file(out.join("posts/a.html")).unwrap();
+        let report = site.build(&out, &options()).unwrap();
+
+        assert_eq!(report.written, ["posts/a.html"]);
+        assert!(out.join("posts/a.html").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
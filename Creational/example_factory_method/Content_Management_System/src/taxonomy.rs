@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagError {
+    Empty,
+    Cycle { tag: String, parent: String },
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagError::Empty => write!(f, "tags must not be empty"),
+            TagError::Cycle { tag, parent } => write!(
+                f,
+                "cannot nest `{}` under `{}`: `{}` is already below `{}`",
+                tag, parent, parent, tag
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+// "  Rust " -> "rust"; inner whitespace becomes a dash so tags stay single query words
+pub fn normalize_tag(tag: &str) -> Result<String, TagError> {
+    let tag = tag
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    if tag.is_empty() {
+        Err(TagError::Empty)
+    } else {
+        Ok(tag)
+    }
+}
+
+// Tag hierarchy: every tag has at most one parent, e.g. rust -> programming. Content tagged
+// `rust` also counts as, and is found by, `programming`
+#[derive(Debug, Default)]
+pub struct Taxonomy {
+    parents: BTreeMap<String, String>,
+}
+
+impl Taxonomy {
+    pub fn set_parent(&mut self, tag: &str, parent: &str) -> Result<(), TagError> {
+        let tag = normalize_tag(tag)?;
+        let parent = normalize_tag(parent)?;
+        if tag == parent || self.ancestors(&parent).contains(&tag) {
+            return Err(TagError::Cycle { tag, parent });
+        }
+        self.parents.insert(tag, parent);
+        Ok(())
+    }
+
+    pub fn parent(&self, tag: &str) -> Option<&str> {
+        self.parents.get(tag).map(String::as_str)
+    }
+
+    // Closest first: rust -> [programming, technology]
+    pub fn ancestors(&self, tag: &str) -> Vec<String> {
+        let mut ancestors = Vec::new();
+        let mut current = tag;
+        while let Some(parent) = self.parents.get(current) {
+            ancestors.push(parent.clone());
+            current = parent;
+        }
+        ancestors
+    }
+
+    pub fn children(&self, tag: &str) -> Vec<&str> {
+        self.parents
+            .iter()
+            .filter(|(_, parent)| *parent == tag)
+            .map(|(child, _)| child.as_str())
+            .collect()
+    }
+
+    // True if `tag` is `ancestor` or sits anywhere below it
+    pub fn is_within(&self, tag: &str, ancestor: &str) -> bool {
+        tag == ancestor || self.ancestors(tag).iter().any(|a| a == ancestor)
+    }
+
+    // Every tag the taxonomy knows about
+    pub fn tags(&self) -> BTreeSet<&str> {
+        self.parents
+            .iter()
+            .flat_map(|(child, parent)| [child.as_str(), parent.as_str()])
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    pub tag: String,
+    pub depth: usize,
+    // Items carrying the tag itself
+    pub direct: usize,
+    // Items carrying the tag or any tag below it, each counted once
+    pub total: usize,
+}
+
+// Counts over the given tag sets, in hierarchy order (parents before their children)
+pub fn tag_counts<'a>(
+    taxonomy: &Taxonomy,
+    tagged: impl Iterator<Item = &'a BTreeSet<String>>,
+) -> Vec<TagCount> {
+    let mut direct: BTreeMap<String, usize> = BTreeMap::new();
+    let mut total: BTreeMap<String, usize> = BTreeMap::new();
+    for tags in tagged {
+        let mut within = BTreeSet::new();
+        for tag in tags {
+            *direct.entry(tag.clone()).or_default() += 1;
+            within.insert(tag.clone());
+            within.extend(taxonomy.ancestors(tag));
+        }
+        for tag in within {
+            *total.entry(tag).or_default() += 1;
+        }
+    }
+    let mut known: BTreeSet<String> = total.keys().cloned().collect();
+    known.extend(taxonomy.tags().into_iter().map(str::to_string));
+
+    let mut counts = Vec::new();
+    let roots = known.iter().filter(|tag| taxonomy.parent(tag).is_none());
+    let mut stack: Vec<(&str, usize)> = roots.rev().map(|tag| (tag.as_str(), 0)).collect();
+    while let Some((tag, depth)) = stack.pop() {
+        counts.push(TagCount {
+            tag: tag.to_string(),
+            depth,
+            direct: direct.get(tag).copied().unwrap_or(0),
+            total: total.get(tag).copied().unwrap_or(0),
+        });
+        let mut children = taxonomy.children(tag);
+        children.sort_unstable();
+        stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+    }
+    counts
+}
@@ -0,0 +1,708 @@
+use crate::query::{DbError, Value};
+use crate::sql::{Command, Condition, Delete, Insert, Op, Order, Select, Term, Update, Upsert};
+
+// SQL text for one backend plus the parameters to bind to its placeholders, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+// How a backend spells the parts of SQL that differ between databases. `render` turns a
+// neutral `Command` into that backend's SQL, or rejects one that has no valid SQL form
+pub trait Dialect {
+    fn name(&self) -> &'static str;
+
+    // The placeholder for the `index`-th (1-based) parameter
+    fn placeholder(&self, index: usize) -> String;
+
+    fn quote_identifier(&self, identifier: &str) -> String;
+
+    fn boolean(&self, value: bool) -> &'static str;
+
+    // The LIMIT/OFFSET clause, without a leading space
+    fn limit_offset(&self, limit: Option<u64>, offset: Option<u64>) -> String;
+
+    // The clause after VALUES (...) that turns an INSERT into an upsert
+    fn upsert(&self, upsert: &Upsert) -> String;
+
+    fn render(&self, command: &Command) -> Result<Rendered, DbError> {
+        validate(command).map_err(DbError::InvalidStatement)?;
+        let mut renderer = Renderer {
+            dialect: self,
+            params: Vec::new(),
+        };
+        let sql = match command {
+            Command::Select(select) => renderer.select(select),
+            Command::Insert(insert) => renderer.insert(insert),
+            Command::Update(update) => renderer.update(update),
+            Command::Delete(delete) => renderer.delete(delete),
+        };
+        Ok(Rendered {
+            sql,
+            params: renderer.params,
+        })
+    }
+}
+
+// Statements the builders accept but no backend does: an INSERT without rows, an UPDATE
+// without assignments, an upsert without a conflict target
+fn validate(command: &Command) -> Result<(), String> {
+    match command {
+        Command::Insert(insert) => {
+            if insert.columns.is_empty() {
+                return Err(format!("INSERT INTO {} names no columns", insert.table));
+            }
+            if insert.rows.is_empty() {
+                return Err(format!("INSERT INTO {} has no rows", insert.table));
+            }
+            for (i, row) in insert.rows.iter().enumerate() {
+                if row.len() != insert.columns.len() {
+                    return Err(format!(
+                        "INSERT INTO {}: row {} has {} values for {} columns",
+                        insert.table,
+                        i + 1,
+                        row.len(),
+                        insert.columns.len()
+                    ));
+                }
+            }
+            if insert
+                .upsert
+                .as_ref()
+                .is_some_and(|u| u.conflict.is_empty())
+            {
+                return Err(format!(
+                    "INSERT INTO {}: on_conflict needs at least one conflict column",
+                    insert.table
+                ));
+            }
+            Ok(())
+        }
+        Command::Update(update) if update.set.is_empty() => {
+            Err(format!("UPDATE {} sets no columns", update.table))
+        }
+        Command::Select(_) | Command::Update(_) | Command::Delete(_) => Ok(()),
+    }
+}
+
+// Doubles any embedded quote, so no identifier can break out of its quotes
+fn quote_with(identifier: &str, quote: char) -> String {
+    let escaped = identifier.replace(quote, &format!("{}{}", quote, quote));
+    format!("{}{}{}", quote, escaped, quote)
+}
+
+// `ON CONFLICT (...) DO UPDATE SET c = excluded.c`, shared by PostgreSQL and SQLite
+fn on_conflict(dialect: &dyn Dialect, upsert: &Upsert) -> String {
+    let conflict: Vec<String> = upsert
+        .conflict
+        .iter()
+        .map(|c| dialect.quote_identifier(c))
+        .collect();
+    if upsert.update.is_empty() {
+        return format!("ON CONFLICT ({}) DO NOTHING", conflict.join(", "));
+    }
+    let update: Vec<String> = upsert
+        .update
+        .iter()
+        .map(|c| {
+            let c = dialect.quote_identifier(c);
+            format!("{} = excluded.{}", c, c)
+        })
+        .collect();
+    format!(
+        "ON CONFLICT ({}) DO UPDATE SET {}",
+        conflict.join(", "),
+        update.join(", ")
+    )
+}
+
+pub struct MySqlDialect;
+
+impl Dialect for MySqlDialect {
+    fn name(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn placeholder(&self, _index: usize) -> String {
+        "?".to_string()
+    }
+
+    fn quote_identifier(&self, identifier: &str) -> String {
+        quote_with(identifier, '`')
+    }
+
+    fn boolean(&self, value: bool) -> &'static str {
+        if value {
+            "TRUE"
+        } else {
+            "FALSE"
+        }
+    }
+
+    // MySQL has no OFFSET without LIMIT; its documented idiom is the largest possible LIMIT
+    fn limit_offset(&self, limit: Option<u64>, offset: Option<u64>) -> String {
+        match (limit, offset) {
+            (None, None) => String::new(),
+            (Some(limit), None) => format!("LIMIT {}", limit),
+            (limit, Some(offset)) => {
+                format!("LIMIT {} OFFSET {}", limit.unwrap_or(u64::MAX), offset)
+            }
+        }
+    }
+
+    // MySQL picks the conflicting key itself, so `conflict` is not written out. With nothing
+    // to update, a no-op assignment keeps the existing row, like DO NOTHING
+    fn upsert(&self, upsert: &Upsert) -> String {
+        let update: Vec<String> = match upsert.update.is_empty() {
+            true => upsert
+                .conflict
+                .iter()
+                .take(1)
+                .map(|c| {
+                    let c = self.quote_identifier(c);
+                    format!("{} = {}", c, c)
+                })
+                .collect(),
+            false => upsert
+                .update
+                .iter()
+                .map(|c| {
+                    let c = self.quote_identifier(c);
+                    format!("{} = VALUES({})", c, c)
+                })
+                .collect(),
+        };
+        format!("ON DUPLICATE KEY UPDATE {}", update.join(", "))
+    }
+}
+
+pub struct PostgresDialect;
+
+impl Dialect for PostgresDialect {
+    fn name(&self) -> &'static str {
+        "postgresql"
+    }
+
+    fn placeholder(&self, index: usize) -> String {
+        format!("${}", index)
+    }
+
+    fn quote_identifier(&self, identifier: &str) -> String {
+        quote_with(identifier, '"')
+    }
+
+    fn boolean(&self, value: bool) -> &'static str {
+        if value {
+            "TRUE"
+        } else {
+            "FALSE"
+        }
+    }
+
+    fn limit_offset(&self, limit: Option<u64>, offset: Option<u64>) -> String {
+        let mut clauses = Vec::new();
+        if let Some(limit) = limit {
+            clauses.push(format!("LIMIT {}", limit));
+        }
+        if let Some(offset) = offset {
+            clauses.push(format!("OFFSET {}", offset));
+        }
+        clauses.join(" ")
+    }
+
+    fn upsert(&self, upsert: &Upsert) -> String {
+        on_conflict(self, upsert)
+    }
+}
+
+pub struct SqliteDialect;
+
+impl Dialect for SqliteDialect {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn placeholder(&self, _index: usize) -> String {
+        "?".to_string()
+    }
+
+    fn quote_identifier(&self, identifier: &str) -> String {
+        quote_with(identifier, '"')
+    }
+
+    // SQLite stores booleans as integers
+    fn boolean(&self, value: bool) -> &'static str {
+        if value {
+            "1"
+        } else {
+            "0"
+        }
+    }
+
+    // SQLite has no OFFSET without LIMIT; a negative LIMIT means "no limit"
+    fn limit_offset(&self, limit: Option<u64>, offset: Option<u64>) -> String {
+        match (limit, offset) {
+            (None, None) => String::new(),
+            (Some(limit), None) => format!("LIMIT {}", limit),
+            (Some(limit), Some(offset)) => format!("LIMIT {} OFFSET {}", limit, offset),
+            (None, Some(offset)) => format!("LIMIT -1 OFFSET {}", offset),
+        }
+    }
+
+    fn upsert(&self, upsert: &Upsert) -> String {
+        on_conflict(self, upsert)
+    }
+}
+
+struct Renderer<'d, D: Dialect + ?Sized> {
+    dialect: &'d D,
+    params: Vec<Value>,
+}
+
+impl<D: Dialect + ?Sized> Renderer<'_, D> {
+    fn ident(&self, identifier: &str) -> String {
+        self.dialect.quote_identifier(identifier)
+    }
+
+    fn term(&mut self, term: &Term) -> String {
+        match term {
+            Term::Bool(value) => self.dialect.boolean(*value).to_string(),
+            Term::Param(value) => {
+                self.params.push(value.clone());
+                self.dialect.placeholder(self.params.len())
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition) -> String {
+        match condition {
+            Condition::Compare(column, Op::Eq, Term::Param(Value::Null)) => {
+                format!("{} IS NULL", self.ident(column))
+            }
+            Condition::Compare(column, Op::Ne, Term::Param(Value::Null)) => {
+                format!("{} IS NOT NULL", self.ident(column))
+            }
+            Condition::Compare(column, op, term) => {
+                format!("{} {} {}", self.ident(column), op.sql(), self.term(term))
+            }
+            Condition::IsNull(column) => format!("{} IS NULL", self.ident(column)),
+            // `x IN ()` is not valid SQL; an empty list matches nothing
+            Condition::In(_, terms) if terms.is_empty() => "1 = 0".to_string(),
+            Condition::In(column, terms) => {
+                let terms: Vec<String> = terms.iter().map(|t| self.term(t)).collect();
+                format!("{} IN ({})", self.ident(column), terms.join(", "))
+            }
+            Condition::And(all) => self.group(all, " AND "),
+            Condition::Or(any) => self.group(any, " OR "),
+            Condition::Not(inner) => format!("NOT ({})", self.condition(inner)),
+        }
+    }
+
+    fn group(&mut self, conditions: &[Condition], separator: &str) -> String {
+        let parts: Vec<String> = conditions
+            .iter()
+            .map(|c| match c {
+                Condition::And(_) | Condition::Or(_) => format!("({})", self.condition(c)),
+                _ => self.condition(c),
+            })
+            .collect();
+        parts.join(separator)
+    }
+
+    fn filter(&mut self, filter: &Option<Condition>) -> String {
+        match filter {
+            Some(condition) => format!(" WHERE {}", self.condition(condition)),
+            None => String::new(),
+        }
+    }
+
+    fn select(&mut self, select: &Select) -> String {
+        let columns = match select.columns.is_empty() {
+            true => "*".to_string(),
+            false => {
+                let columns: Vec<String> = select.columns.iter().map(|c| self.ident(c)).collect();
+                columns.join(", ")
+            }
+        };
+        let mut sql = format!("SELECT {} FROM {}", columns, self.ident(&select.table));
+        sql += &self.filter(&select.filter);
+        if !select.order_by.is_empty() {
+            let order: Vec<String> = select
+                .order_by
+                .iter()
+                .map(|(column, order)| match order {
+                    Order::Asc => format!("{} ASC", self.ident(column)),
+                    Order::Desc => format!("{} DESC", self.ident(column)),
+                })
+                .collect();
+            sql += &format!(" ORDER BY {}", order.join(", "));
+        }
+        let limit = self.dialect.limit_offset(select.limit, select.offset);
+        if !limit.is_empty() {
+            sql += " ";
+            sql += &limit;
+        }
+        sql
+    }
+
+    fn insert(&mut self, insert: &Insert) -> String {
+        let columns: Vec<String> = insert.columns.iter().map(|c| self.ident(c)).collect();
+        let rows: Vec<String> = insert
+            .rows
+            .iter()
+            .map(|row| {
+                let terms: Vec<String> = row.iter().map(|t| self.term(t)).collect();
+                format!("({})", terms.join(", "))
+            })
+            .collect();
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.ident(&insert.table),
+            columns.join(", "),
+            rows.join(", ")
+        );
+        if let Some(upsert) = &insert.upsert {
+            sql += " ";
+            sql += &self.dialect.upsert(upsert);
+        }
+        sql
+    }
+
+    fn update(&mut self, update: &Update) -> String {
+        let set: Vec<String> = update
+            .set
+            .iter()
+            .map(|(column, term)| format!("{} = {}", self.ident(column), self.term(term)))
+            .collect();
+        let mut sql = format!(
+            "UPDATE {} SET {}",
+            self.ident(&update.table),
+            set.join(", ")
+        );
+        sql += &self.filter(&update.filter);
+        sql
+    }
+
+    fn delete(&mut self, delete: &Delete) -> String {
+        let mut sql = format!("DELETE FROM {}", self.ident(&delete.table));
+        sql += &self.filter(&delete.filter);
+        sql
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::Condition;
+
+    fn rendered(sql: &str, params: Vec<Value>) -> Rendered {
+        Rendered {
+            sql: sql.to_string(),
+            params,
+        }
+    }
+
+    // One rendering per dialect, in the order MySQL, PostgreSQL, SQLite
+    fn render_all(command: impl Into<Command>) -> [Rendered; 3] {
+        let command = command.into();
+        [
+            MySqlDialect.render(&command).unwrap(),
+            PostgresDialect.render(&command).unwrap(),
+            SqliteDialect.render(&command).unwrap(),
+        ]
+    }
+
+    // The error every dialect reports for `command`
+    fn invalid(command: impl Into<Command>) -> String {
+        let command = command.into();
+        let errors: Vec<DbError> = [
+            &MySqlDialect as &dyn Dialect,
+            &PostgresDialect,
+            &SqliteDialect,
+        ]
+        .iter()
+        .map(|dialect| dialect.render(&command).unwrap_err())
+        .collect();
+        match &errors[..] {
+            [DbError::InvalidStatement(message), rest @ ..]
+                if rest.iter().all(|e| e == &errors[0]) =>
+            {
+                message.clone()
+            }
+            other => panic!("expected the same invalid statement error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let select = Select::from("users")
+            .filter(Condition::eq("name", "alice").and(Condition::gt("score", 5)));
+        let params = vec![Value::Text("alice".to_string()), Value::Integer(5)];
+        assert_eq!(
+            render_all(select),
+            [
+                rendered(
+                    "SELECT * FROM `users` WHERE `name` = ? AND `score` > ?",
+                    params.clone()
+                ),
+                rendered(
+                    r#"SELECT * FROM "users" WHERE "name" = $1 AND "score" > $2"#,
+                    params.clone()
+                ),
+                rendered(
+                    r#"SELECT * FROM "users" WHERE "name" = ? AND "score" > ?"#,
+                    params
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn placeholders_number_across_clauses() {
+        let update = Update::table("users")
+            .set("score", 1.5)
+            .filter(Condition::is_in("id", [1, 2]));
+        let params = vec![Value::Real(1.5), Value::Integer(1), Value::Integer(2)];
+        assert_eq!(
+            PostgresDialect.render(&update.into()).unwrap(),
+            rendered(
+                r#"UPDATE "users" SET "score" = $1 WHERE "id" IN ($2, $3)"#,
+                params
+            )
+        );
+    }
+
+    #[test]
+    fn identifier_quoting() {
+        let select = Select::from("my table").columns(&["a`b", r#"c"d"#]);
+        assert_eq!(
+            render_all(select),
+            [
+                rendered(r#"SELECT `a``b`, `c"d` FROM `my table`"#, vec![]),
+                rendered(r#"SELECT "a`b", "c""d" FROM "my table""#, vec![]),
+                rendered(r#"SELECT "a`b", "c""d" FROM "my table""#, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_and_offset() {
+        let select = Select::from("t").limit(10).offset(20);
+        assert_eq!(
+            render_all(select),
+            [
+                rendered("SELECT * FROM `t` LIMIT 10 OFFSET 20", vec![]),
+                rendered(r#"SELECT * FROM "t" LIMIT 10 OFFSET 20"#, vec![]),
+                rendered(r#"SELECT * FROM "t" LIMIT 10 OFFSET 20"#, vec![]),
+            ]
+        );
+        assert_eq!(
+            render_all(Select::from("t").limit(10)),
+            [
+                rendered("SELECT * FROM `t` LIMIT 10", vec![]),
+                rendered(r#"SELECT * FROM "t" LIMIT 10"#, vec![]),
+                rendered(r#"SELECT * FROM "t" LIMIT 10"#, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn offset_without_limit() {
+        assert_eq!(
+            render_all(Select::from("t").offset(20)),
+            [
+                rendered(
+                    "SELECT * FROM `t` LIMIT 18446744073709551615 OFFSET 20",
+                    vec![]
+                ),
+                rendered(r#"SELECT * FROM "t" OFFSET 20"#, vec![]),
+                rendered(r#"SELECT * FROM "t" LIMIT -1 OFFSET 20"#, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn upsert_updates_columns() {
+        let insert = Insert::into("scores", &["user", "score"])
+            .values(vec!["alice".into(), 9.into()])
+            .on_conflict(&["user"], &["score"]);
+        let params = vec![Value::Text("alice".to_string()), Value::Integer(9)];
+        assert_eq!(
+            render_all(insert),
+            [
+                rendered(
+                    "INSERT INTO `scores` (`user`, `score`) VALUES (?, ?) \
+                     ON DUPLICATE KEY UPDATE `score` = VALUES(`score`)",
+                    params.clone()
+                ),
+                rendered(
+                    r#"INSERT INTO "scores" ("user", "score") VALUES ($1, $2) ON CONFLICT ("user") DO UPDATE SET "score" = excluded."score""#,
+                    params.clone()
+                ),
+                rendered(
+                    r#"INSERT INTO "scores" ("user", "score") VALUES (?, ?) ON CONFLICT ("user") DO UPDATE SET "score" = excluded."score""#,
+                    params
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn upsert_without_update_columns_keeps_the_row() {
+        let insert = Insert::into("scores", &["user"])
+            .values(vec!["alice".into()])
+            .on_conflict(&["user"], &[]);
+        let params = vec![Value::Text("alice".to_string())];
+        assert_eq!(
+            render_all(insert),
+            [
+                rendered(
+                    "INSERT INTO `scores` (`user`) VALUES (?) ON DUPLICATE KEY UPDATE `user` = `user`",
+                    params.clone()
+                ),
+                rendered(
+                    r#"INSERT INTO "scores" ("user") VALUES ($1) ON CONFLICT ("user") DO NOTHING"#,
+                    params.clone()
+                ),
+                rendered(
+                    r#"INSERT INTO "scores" ("user") VALUES (?) ON CONFLICT ("user") DO NOTHING"#,
+                    params
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn boolean_literals() {
+        let update = Update::table("users")
+            .set("active", false)
+            .filter(Condition::eq("active", true));
+        assert_eq!(
+            render_all(update),
+            [
+                rendered(
+                    "UPDATE `users` SET `active` = FALSE WHERE `active` = TRUE",
+                    vec![]
+                ),
+                rendered(
+                    r#"UPDATE "users" SET "active" = FALSE WHERE "active" = TRUE"#,
+                    vec![]
+                ),
+                rendered(
+                    r#"UPDATE "users" SET "active" = 0 WHERE "active" = 1"#,
+                    vec![]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_in_list_matches_nothing() {
+        let delete = Delete::from("users")
+            .filter(Condition::is_in("id", Vec::<i64>::new()).or(Condition::eq("id", 3)));
+        assert_eq!(
+            render_all(delete),
+            [
+                rendered(
+                    "DELETE FROM `users` WHERE 1 = 0 OR `id` = ?",
+                    vec![Value::Integer(3)]
+                ),
+                rendered(
+                    r#"DELETE FROM "users" WHERE 1 = 0 OR "id" = $1"#,
+                    vec![Value::Integer(3)]
+                ),
+                rendered(
+                    r#"DELETE FROM "users" WHERE 1 = 0 OR "id" = ?"#,
+                    vec![Value::Integer(3)]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn comparing_with_null_uses_is_null() {
+        let select = Select::from("users").filter(
+            Condition::eq("deleted_at", Value::Null).and(Condition::ne("email", Value::Null)),
+        );
+        assert_eq!(
+            render_all(select),
+            [
+                rendered(
+                    "SELECT * FROM `users` WHERE `deleted_at` IS NULL AND `email` IS NOT NULL",
+                    vec![]
+                ),
+                rendered(
+                    r#"SELECT * FROM "users" WHERE "deleted_at" IS NULL AND "email" IS NOT NULL"#,
+                    vec![]
+                ),
+                rendered(
+                    r#"SELECT * FROM "users" WHERE "deleted_at" IS NULL AND "email" IS NOT NULL"#,
+                    vec![]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn le_and_ne() {
+        let delete = Delete::from("t").filter(Condition::le("a", 1).and(Condition::ne("b", "x")));
+        let params = vec![Value::Integer(1), Value::Text("x".to_string())];
+        assert_eq!(
+            render_all(delete),
+            [
+                rendered(
+                    "DELETE FROM `t` WHERE `a` <= ? AND `b` <> ?",
+                    params.clone()
+                ),
+                rendered(
+                    r#"DELETE FROM "t" WHERE "a" <= $1 AND "b" <> $2"#,
+                    params.clone()
+                ),
+                rendered(r#"DELETE FROM "t" WHERE "a" <= ? AND "b" <> ?"#, params),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_without_rows_is_rejected() {
+        assert_eq!(
+            invalid(Insert::into("users", &["name"])),
+            "INSERT INTO users has no rows"
+        );
+        assert_eq!(
+            invalid(Insert::into("users", &[]).values(vec![])),
+            "INSERT INTO users names no columns"
+        );
+    }
+
+    #[test]
+    fn insert_rows_must_match_the_columns() {
+        let insert = Insert::into("users", &["id", "name"])
+            .values(vec![1.into(), "alice".into()])
+            .values(vec![2.into()]);
+        assert_eq!(
+            invalid(insert),
+            "INSERT INTO users: row 2 has 1 values for 2 columns"
+        );
+    }
+
+    #[test]
+    fn upsert_without_conflict_columns_is_rejected() {
+        for update in [&[][..], &["name"][..]] {
+            let insert = Insert::into("users", &["name"])
+                .values(vec!["alice".into()])
+                .on_conflict(&[], update);
+            assert_eq!(
+                invalid(insert),
+                "INSERT INTO users: on_conflict needs at least one conflict column"
+            );
+        }
+    }
+
+    #[test]
+    fn update_without_assignments_is_rejected() {
+        let update = Update::table("users").filter(Condition::eq("id", 1));
+        assert_eq!(invalid(update), "UPDATE users sets no columns");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::ConnectionFactory;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriverError {
+    Unknown {
+        driver: String,
+        installed: Vec<String>,
+    },
+    Duplicate(String),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::Unknown { driver, installed } => write!(
+                f,
+                "no database driver for `{}`; installed: {}",
+                driver,
+                installed.join(", ")
+            ),
+            DriverError::Duplicate(driver) => {
+                write!(f, "a `{}` driver is already installed", driver)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DriverError {}
+
+// Connection factories keyed by driver name, which is also the scheme of the connection URLs
+// they accept (see `ConnectionUrl::driver`). Schemes are case-insensitive, so names are
+// matched ignoring case and surrounding spaces
+#[derive(Default)]
+pub struct Drivers {
+    factories: BTreeMap<String, Box<dyn ConnectionFactory>>,
+}
+
+impl Drivers {
+    pub fn install(
+        &mut self,
+        driver: &str,
+        factory: Box<dyn ConnectionFactory>,
+    ) -> Result<(), DriverError> {
+        let key = driver.trim().to_ascii_lowercase();
+        if self.factories.contains_key(&key) {
+            return Err(DriverError::Duplicate(key));
+        }
+        self.factories.insert(key, factory);
+        Ok(())
+    }
+
+    pub fn get(&self, driver: &str) -> Result<&dyn ConnectionFactory, DriverError> {
+        self.factories
+            .get(&driver.trim().to_ascii_lowercase())
+            .map(|factory| &**factory)
+            .ok_or_else(|| DriverError::Unknown {
+                driver: driver.to_string(),
+                installed: self.names(),
+            })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_drivers, plugin, SQLiteConnectionFactory};
+
+    #[test]
+    fn drivers_are_found_ignoring_case() {
+        let drivers = default_drivers();
+        assert!(drivers.get("SQLite").is_ok());
+        assert!(drivers.get(" mysql ").is_ok());
+        assert_eq!(drivers.names(), ["mysql", "postgresql", "sqlite"]);
+    }
+
+    #[test]
+    fn a_driver_cannot_be_installed_twice() {
+        let mut drivers = default_drivers();
+        assert_eq!(
+            drivers.install("SQLITE", Box::new(SQLiteConnectionFactory)),
+            Err(DriverError::Duplicate("sqlite".to_string()))
+        );
+        plugin::register(&mut drivers).unwrap();
+        assert!(plugin::register(&mut drivers).is_err());
+    }
+
+    #[test]
+    fn unknown_drivers_list_the_installed_ones() {
+        let e = default_drivers().get("oracle").err().unwrap();
+        assert_eq!(
+            e,
+            DriverError::Unknown {
+                driver: "oracle".to_string(),
+                installed: vec![
+                    "mysql".to_string(),
+                    "postgresql".to_string(),
+                    "sqlite".to_string()
+                ],
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "no database driver for `oracle`; installed: mysql, postgresql, sqlite"
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use crate::dialect::{Dialect, SqliteDialect};
+use crate::query::{DbError, QueryResult, Value};
+use crate::transaction::IsolationLevel;
+use crate::url::ConnectionUrl;
+use crate::{ConnectionFactory, DatabaseConnection};
+use regex::Regex;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+// Mock backend for testing code written against `DatabaseConnection`. A `MockDatabase` is a
+// shared handle: script it, hand out connections (directly or through `MockConnectionFactory`),
+// run the code under test, then assert on what it executed
+//
+//   let db = MockDatabase::new();
+//   db.when(Matcher::regex(r"^SELECT .* FROM users")).returns(rows);
+//   db.when(Matcher::exact("DELETE FROM users")).fails("permission denied");
+//   ...
+//   db.assert_called_times("UPDATE users SET active = 0 WHERE id = 2", 1);
+
+#[derive(Clone)]
+pub enum Matcher {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn exact(query: &str) -> Self {
+        Matcher::Exact(query.to_string())
+    }
+
+    // Panics on an invalid pattern, like a failed assertion would
+    pub fn regex(pattern: &str) -> Self {
+        match Regex::new(pattern) {
+            Ok(regex) => Matcher::Regex(regex),
+            Err(e) => panic!("invalid mock pattern `{}`: {}", pattern, e),
+        }
+    }
+
+    pub fn matches(&self, query: &str) -> bool {
+        match self {
+            Matcher::Exact(expected) => expected == query,
+            Matcher::Regex(regex) => regex.is_match(query),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Matcher::Exact(query) => write!(f, "`{}`", query),
+            Matcher::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Response {
+    Result(QueryResult),
+    Error(String),
+}
+
+struct Rule {
+    matcher: Matcher,
+    params: Option<Vec<Value>>,
+    delay: Duration,
+    response: Response,
+}
+
+#[derive(Default)]
+struct State {
+    rules: Vec<Rule>,
+    connect_error: Option<String>,
+    connections: usize,
+    calls: Vec<(String, Vec<Value>)>,
+}
+
+#[derive(Clone, Default)]
+pub struct MockDatabase {
+    state: Rc<RefCell<State>>,
+}
+
+// Builder returned by `MockDatabase::when`; `returns`, `affects` and `fails` add the rule
+pub struct Script<'a> {
+    db: &'a MockDatabase,
+    matcher: Matcher,
+    params: Option<Vec<Value>>,
+    delay: Duration,
+}
+
+impl Script<'_> {
+    // Only match runs of a prepared statement with exactly these bound parameters
+    pub fn with_params(mut self, params: &[Value]) -> Self {
+        self.params = Some(params.to_vec());
+        self
+    }
+
+    // Simulated latency: the query blocks this long before answering
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn returns(self, result: QueryResult) {
+        self.add(Response::Result(result));
+    }
+
+    pub fn affects(self, rows: u64) {
+        self.add(Response::Result(QueryResult::Affected(rows)));
+    }
+
+    pub fn fails(self, message: &str) {
+        self.add(Response::Error(message.to_string()));
+    }
+
+    fn add(self, response: Response) {
+        self.db.state.borrow_mut().rules.push(Rule {
+            matcher: self.matcher,
+            params: self.params,
+            delay: self.delay,
+            response,
+        });
+    }
+}
+
+impl MockDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rules are tried in the order they were added; the first match answers. A query that
+    // matches no rule fails, so unexpected statements do not go unnoticed
+    pub fn when(&self, matcher: Matcher) -> Script<'_> {
+        Script {
+            db: self,
+            matcher,
+            params: None,
+            delay: Duration::ZERO,
+        }
+    }
+
+    // Every later `connect` fails with this message
+    pub fn fail_connect(&self, message: &str) {
+        self.state.borrow_mut().connect_error = Some(message.to_string());
+    }
+
+    pub fn connection(&self) -> MockConnection {
+        MockConnection {
+            db: self.clone(),
+            connected: false,
+        }
+    }
+
+    // Every statement executed on any of this database's connections, in order
+    pub fn calls(&self) -> Vec<String> {
+        let state = self.state.borrow();
+        state.calls.iter().map(|(query, _)| query.clone()).collect()
+    }
+
+    pub fn call_count(&self, matcher: &Matcher) -> usize {
+        let state = self.state.borrow();
+        let calls = state.calls.iter();
+        calls.filter(|(query, _)| matcher.matches(query)).count()
+    }
+
+    pub fn assert_connected(&self) {
+        if self.state.borrow().connections == 0 {
+            panic!("expected a connection to the mock database, but none was opened");
+        }
+    }
+
+    pub fn assert_called_times(&self, query: &str, times: usize) {
+        self.assert_matched_times(&Matcher::exact(query), times);
+    }
+
+    pub fn assert_matched_times(&self, matcher: &Matcher, times: usize) {
+        let count = self.call_count(matcher);
+        if count != times {
+            panic!(
+                "expected {} to run {} time(s), but it ran {} time(s)\n{}",
+                matcher,
+                times,
+                count,
+                self.log()
+            );
+        }
+    }
+
+    // At least one run of `query` had exactly these bound parameters
+    pub fn assert_called_with(&self, query: &str, params: &[Value]) {
+        let state = self.state.borrow();
+        let mut runs = state.calls.iter().filter(|(q, _)| q == query).peekable();
+        if runs.peek().is_none() {
+            drop(state);
+            panic!(
+                "expected `{}` to run, but it did not\n{}",
+                query,
+                self.log()
+            );
+        }
+        let seen: Vec<&Vec<Value>> = runs.map(|(_, p)| p).collect();
+        if !seen.iter().any(|p| p.as_slice() == params) {
+            panic!(
+                "expected `{}` to run with {:?}, but it ran with {:?}",
+                query, params, seen
+            );
+        }
+    }
+
+    pub fn assert_not_called(&self, matcher: &Matcher) {
+        self.assert_matched_times(matcher, 0);
+    }
+
+    // The matchers must each match a call, in this order; other calls may come in between
+    pub fn assert_called_in_order(&self, matchers: &[Matcher]) {
+        let calls = self.calls();
+        let mut remaining = calls.iter();
+        for (i, matcher) in matchers.iter().enumerate() {
+            if !remaining.any(|query| matcher.matches(query)) {
+                panic!(
+                    "expected {} to run after {}, but it did not\n{}",
+                    matcher,
+                    match i {
+                        0 => "the start".to_string(),
+                        _ => matchers[i - 1].to_string(),
+                    },
+                    self.log()
+                );
+            }
+        }
+    }
+
+    fn log(&self) -> String {
+        let calls = self.calls();
+        if calls.is_empty() {
+            return "no statements were executed".to_string();
+        }
+        let lines: Vec<String> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, query)| format!("  {}. {}", i + 1, query))
+            .collect();
+        format!("executed statements:\n{}", lines.join("\n"))
+    }
+}
+
+pub struct MockConnection {
+    db: MockDatabase,
+    connected: bool,
+}
+
+impl DatabaseConnection for MockConnection {
+    fn connect(&mut self) -> Result<(), DbError> {
+        let mut state = self.db.state.borrow_mut();
+        if let Some(message) = &state.connect_error {
+            return Err(DbError::Connection(message.clone()));
+        }
+        state.connections += 1;
+        self.connected = true;
+        Ok(())
+    }
+
+    // Plain `?` placeholders, so scripted queries read like the SQL passed to `prepare`
+    fn dialect(&self) -> &'static dyn Dialect {
+        &SqliteDialect
+    }
+
+    fn execute_with(&mut self, query: &str, params: &[Value]) -> Result<QueryResult, DbError> {
+        if !self.connected {
+            return Err(DbError::NotConnected);
+        }
+        let (delay, response) = {
+            let mut state = self.db.state.borrow_mut();
+            state.calls.push((query.to_string(), params.to_vec()));
+            let rule = state.rules.iter().find(|rule| {
+                rule.matcher.matches(query)
+                    && rule.params.as_ref().is_none_or(|p| p.as_slice() == params)
+            });
+            match rule {
+                Some(rule) => (rule.delay, rule.response.clone()),
+                // Transaction control succeeds unless scripted otherwise, so code that uses
+                // `begin` does not have to script every BEGIN and COMMIT
+                None if is_transaction_control(query) => {
+                    (Duration::ZERO, Response::Result(QueryResult::Affected(0)))
+                }
+                None => (
+                    Duration::ZERO,
+                    Response::Error("no scripted result for this query".to_string()),
+                ),
+            }
+        };
+        thread::sleep(delay);
+        match response {
+            Response::Result(result) => Ok(result),
+            Response::Error(message) => Err(DbError::Query {
+                query: query.to_string(),
+                message,
+            }),
+        }
+    }
+
+    fn isolation_levels(&self) -> &'static [IsolationLevel] {
+        &[
+            IsolationLevel::ReadUncommitted,
+            IsolationLevel::ReadCommitted,
+            IsolationLevel::RepeatableRead,
+            IsolationLevel::Serializable,
+        ]
+    }
+}
+
+// Only the statements `begin` and its guards emit; any other SET or START is an ordinary
+// query and must be scripted
+fn is_transaction_control(query: &str) -> bool {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    [
+        &["BEGIN"][..],
+        &["START", "TRANSACTION"],
+        &["SET", "TRANSACTION", "ISOLATION", "LEVEL"],
+        &["COMMIT"],
+        &["ROLLBACK"],
+        &["SAVEPOINT"],
+        &["RELEASE"],
+    ]
+    .iter()
+    .any(|prefix| {
+        words.len() >= prefix.len()
+            && prefix
+                .iter()
+                .zip(&words)
+                .all(|(keyword, word)| keyword.eq_ignore_ascii_case(word))
+    })
+}
+
+// ConcreteCreator: hands out connections to one shared `MockDatabase`, so code that picks its
+// connection through `Drivers` or a URL can be tested too
+pub struct MockConnectionFactory(pub MockDatabase);
+
+impl ConnectionFactory for MockConnectionFactory {
+    fn default_url(&self) -> &'static str {
+        "mock://localhost/test"
+    }
+
+    fn create_connection(&self, _url: &ConnectionUrl) -> Box<dyn DatabaseConnection> {
+        Box::new(self.0.connection())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn connected(db: &MockDatabase) -> MockConnection {
+        let mut conn = db.connection();
+        conn.connect().unwrap();
+        conn
+    }
+
+    fn error_message(result: Result<QueryResult, DbError>) -> String {
+        match result {
+            Err(DbError::Query { message, .. }) => message,
+            other => panic!("expected a query error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn exact_matcher_needs_the_whole_query() {
+        let exact = Matcher::exact("SELECT 1");
+        assert!(exact.matches("SELECT 1"));
+        assert!(!exact.matches("SELECT 1 "));
+        assert!(!exact.matches("SELECT 10"));
+    }
+
+    #[test]
+    fn regex_matcher_matches_anywhere_unless_anchored() {
+        assert!(Matcher::regex("FROM users").matches("SELECT * FROM users WHERE id = ?"));
+        assert!(!Matcher::regex("^FROM users").matches("SELECT * FROM users"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid mock pattern `(`")]
+    fn invalid_regex_panics() {
+        Matcher::regex("(");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let db = MockDatabase::new();
+        db.when(Matcher::exact("DELETE FROM users")).affects(1);
+        db.when(Matcher::regex("^DELETE")).affects(2);
+        db.when(Matcher::exact("DELETE FROM users")).affects(3);
+        let mut conn = connected(&db);
+        assert_eq!(
+            conn.execute_query("DELETE FROM users"),
+            Ok(QueryResult::Affected(1))
+        );
+        assert_eq!(
+            conn.execute_query("DELETE FROM posts"),
+            Ok(QueryResult::Affected(2))
+        );
+    }
+
+    #[test]
+    fn unscripted_query_fails_but_transaction_control_succeeds() {
+        let db = MockDatabase::new();
+        let mut conn = connected(&db);
+        assert_eq!(
+            error_message(conn.execute_query("SELECT 1")),
+            "no scripted result for this query"
+        );
+        assert_eq!(conn.execute_query("BEGIN"), Ok(QueryResult::Affected(0)));
+        assert_eq!(db.calls(), ["SELECT 1", "BEGIN"]);
+    }
+
+    #[test]
+    fn only_transaction_statements_pass_unscripted() {
+        let db = MockDatabase::new();
+        let mut conn = connected(&db);
+        for query in [
+            "START TRANSACTION",
+            "START TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+            "set transaction isolation level READ COMMITTED",
+            "SAVEPOINT sp_1",
+            "RELEASE SAVEPOINT sp_1",
+            "ROLLBACK TO SAVEPOINT sp_1",
+            "COMMIT",
+        ] {
+            assert_eq!(
+                conn.execute_query(query),
+                Ok(QueryResult::Affected(0)),
+                "{}",
+                query
+            );
+        }
+        for query in [
+            "SET search_path TO app",
+            "SET TRANSACTION READ ONLY",
+            "SET",
+            "START SLAVE",
+            "BEGINNING",
+        ] {
+            assert_eq!(
+                error_message(conn.execute_query(query)),
+                "no scripted result for this query",
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn with_params_only_matches_those_parameters() {
+        let db = MockDatabase::new();
+        let query = "UPDATE users SET active = 0 WHERE id = ?";
+        db.when(Matcher::exact(query))
+            .with_params(&[Value::Integer(2)])
+            .affects(1);
+        db.when(Matcher::exact(query)).affects(0);
+        let mut conn = connected(&db);
+        assert_eq!(
+            conn.execute_with(query, &[Value::Integer(2)]),
+            Ok(QueryResult::Affected(1))
+        );
+        assert_eq!(
+            conn.execute_with(query, &[Value::Integer(3)]),
+            Ok(QueryResult::Affected(0))
+        );
+        db.assert_called_with(query, &[Value::Integer(3)]);
+    }
+
+    #[test]
+    fn returns_scripted_rows() {
+        let db = MockDatabase::new();
+        let rows = QueryResult::rows(&["id"], vec![vec![Value::Integer(7)]]);
+        db.when(Matcher::regex("^SELECT")).returns(rows.clone());
+        assert_eq!(
+            connected(&db).execute_query("SELECT id FROM users"),
+            Ok(rows)
+        );
+    }
+
+    #[test]
+    fn injected_query_error() {
+        let db = MockDatabase::new();
+        db.when(Matcher::exact("DELETE FROM users"))
+            .fails("permission denied");
+        let mut conn = connected(&db);
+        assert_eq!(
+            error_message(conn.execute_query("DELETE FROM users")),
+            "permission denied"
+        );
+        // A failed statement still counts as a call
+        db.assert_called_times("DELETE FROM users", 1);
+    }
+
+    #[test]
+    fn injected_connect_error() {
+        let db = MockDatabase::new();
+        db.fail_connect("connection refused");
+        let mut conn = db.connection();
+        assert_eq!(
+            conn.connect(),
+            Err(DbError::Connection("connection refused".to_string()))
+        );
+        assert_eq!(conn.execute_query("SELECT 1"), Err(DbError::NotConnected));
+        assert!(db.calls().is_empty());
+    }
+
+    #[test]
+    fn injected_delay() {
+        let db = MockDatabase::new();
+        db.when(Matcher::exact("SELECT 1"))
+            .delay(Duration::from_millis(50))
+            .affects(0);
+        let mut conn = connected(&db);
+        let started = Instant::now();
+        conn.execute_query("SELECT 1").unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn assertions_pass_on_matching_calls() {
+        let db = MockDatabase::new();
+        db.when(Matcher::regex(".")).affects(0);
+        let mut conn = connected(&db);
+        for query in ["SELECT 1", "UPDATE t SET x = 1", "SELECT 1"] {
+            conn.execute_query(query).unwrap();
+        }
+        db.assert_connected();
+        db.assert_called_times("SELECT 1", 2);
+        db.assert_called_in_order(&[Matcher::exact("SELECT 1"), Matcher::regex("^UPDATE")]);
+        db.assert_not_called(&Matcher::regex("^DELETE"));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected `SELECT 1` to run 1 time(s), but it ran 2 time(s)")]
+    fn assert_called_times_panics_on_wrong_count() {
+        let db = MockDatabase::new();
+        db.when(Matcher::exact("SELECT 1")).affects(0);
+        let mut conn = connected(&db);
+        conn.execute_query("SELECT 1").unwrap();
+        conn.execute_query("SELECT 1").unwrap();
+        db.assert_called_times("SELECT 1", 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected /^SELECT/ to run after /^UPDATE/, but it did not")]
+    fn assert_called_in_order_panics_on_wrong_order() {
+        let db = MockDatabase::new();
+        db.when(Matcher::regex(".")).affects(0);
+        let mut conn = connected(&db);
+        conn.execute_query("SELECT 1").unwrap();
+        conn.execute_query("UPDATE t SET x = 1").unwrap();
+        db.assert_called_in_order(&[Matcher::regex("^UPDATE"), Matcher::regex("^SELECT")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected /^DELETE/ to run 0 time(s), but it ran 1 time(s)")]
+    fn assert_not_called_panics_when_called() {
+        let db = MockDatabase::new();
+        db.when(Matcher::regex("^DELETE")).affects(1);
+        connected(&db).execute_query("DELETE FROM users").unwrap();
+        db.assert_not_called(&Matcher::regex("^DELETE"));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::dialect::{Dialect, MySqlDialect};
+use crate::driver::{DriverError, Drivers};
+use crate::query::{DbError, QueryResult, Value};
+use crate::transaction::IsolationLevel;
+use crate::url::ConnectionUrl;
+use crate::{describe, mysql_begin, simulate, ConnectionFactory, DatabaseConnection};
+
+// Plugin: MariaDB, installed next to the built-in drivers. It is an ordinary module, but it
+// reuses MySQL's dialect and transaction syntax instead of adding cases to them
+
+struct MariaDbConnection {
+    url: ConnectionUrl,
+}
+
+impl DatabaseConnection for MariaDbConnection {
+    fn connect(&mut self) -> Result<(), DbError> {
+        println!(
+            "Connecting to MariaDB database {}...",
+            describe(&self.url, 3306)
+        );
+        Ok(())
+    }
+
+    // MariaDB speaks MySQL's dialect
+    fn dialect(&self) -> &'static dyn Dialect {
+        &MySqlDialect
+    }
+
+    fn execute_with(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult, DbError> {
+        simulate(sql, params)
+    }
+
+    fn isolation_levels(&self) -> &'static [IsolationLevel] {
+        &[
+            IsolationLevel::ReadUncommitted,
+            IsolationLevel::ReadCommitted,
+            IsolationLevel::RepeatableRead,
+            IsolationLevel::Serializable,
+        ]
+    }
+
+    // MariaDB shares MySQL's transaction syntax
+    fn begin_statements(&self, level: Option<IsolationLevel>) -> Vec<String> {
+        mysql_begin(level)
+    }
+}
+
+struct MariaDbConnectionFactory;
+
+impl ConnectionFactory for MariaDbConnectionFactory {
+    fn default_url(&self) -> &'static str {
+        "mariadb://root@localhost:3306/app"
+    }
+
+    fn create_connection(&self, url: &ConnectionUrl) -> Box<dyn DatabaseConnection> {
+        Box::new(MariaDbConnection { url: url.clone() })
+    }
+}
+
+pub fn register(drivers: &mut Drivers) -> Result<(), DriverError> {
+    drivers.install("mariadb", Box::new(MariaDbConnectionFactory))
+}
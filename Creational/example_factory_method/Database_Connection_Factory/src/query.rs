@@ -0,0 +1,351 @@
+use crate::driver::DriverError;
+use crate::url::UrlError;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
+pub enum DbError {
+    Url(UrlError),
+    Driver(DriverError),
+    NotConnected,
+    Connection(String),
+    Query { query: String, message: String },
+    Column { column: String, message: String },
+    Bind(String),
+    Unsupported(String),
+    InvalidStatement(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Url(e) => write!(f, "invalid connection URL: {}", e),
+            DbError::Driver(e) => write!(f, "{}", e),
+            DbError::NotConnected => write!(f, "not connected; call connect() first"),
+            DbError::Connection(message) => write!(f, "connection failed: {}", message),
+            DbError::Query { query, message } => {
+                write!(f, "query failed: {} (query: {})", message, query)
+            }
+            DbError::Column { column, message } => write!(f, "column `{}`: {}", column, message),
+            DbError::Bind(message) => write!(f, "cannot bind parameter: {}", message),
+            DbError::Unsupported(message) => write!(f, "unsupported: {}", message),
+            DbError::InvalidStatement(message) => write!(f, "invalid statement: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<UrlError> for DbError {
+    fn from(e: UrlError) -> Self {
+        DbError::Url(e)
+    }
+}
+
+impl From<DriverError> for DbError {
+    fn from(e: DriverError) -> Self {
+        DbError::Driver(e)
+    }
+}
+
+// A single column value, mirroring SQLite's storage classes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Integer(_) => "INTEGER",
+            Value::Real(_) => "REAL",
+            Value::Text(_) => "TEXT",
+            Value::Blob(_) => "BLOB",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Real(r) => write!(f, "{}", r),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Blob(bytes) => write!(f, "<{} bytes>", bytes.len()),
+        }
+    }
+}
+
+// Conversion out of a `Value`, for `Row::get_as`
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+fn mismatch<T>(expected: &str, value: &Value) -> Result<T, String> {
+    Err(format!(
+        "expected {}, found {}",
+        expected,
+        value.type_name()
+    ))
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(i) => Ok(*i),
+            other => mismatch("INTEGER", other),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Real(r) => Ok(*r),
+            Value::Integer(i) => Ok(*i as f64),
+            other => mismatch("REAL", other),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(i) => Ok(*i != 0),
+            other => mismatch("INTEGER (boolean)", other),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Text(s) => Ok(s.clone()),
+            other => mismatch("TEXT", other),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Blob(bytes) => Ok(bytes.clone()),
+            other => mismatch("BLOB", other),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+// Conversion into a `Value`, for binding statement parameters
+pub trait ToValue {
+    fn to_value(self) -> Value;
+}
+
+impl ToValue for Value {
+    fn to_value(self) -> Value {
+        self
+    }
+}
+
+impl ToValue for i64 {
+    fn to_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl ToValue for i32 {
+    fn to_value(self) -> Value {
+        Value::Integer(self.into())
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(self) -> Value {
+        Value::Real(self)
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(self) -> Value {
+        Value::Integer(self.into())
+    }
+}
+
+impl ToValue for &str {
+    fn to_value(self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+impl ToValue for String {
+    fn to_value(self) -> Value {
+        Value::Text(self)
+    }
+}
+
+impl ToValue for Vec<u8> {
+    fn to_value(self) -> Value {
+        Value::Blob(self)
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(self) -> Value {
+        self.map_or(Value::Null, T::to_value)
+    }
+}
+
+// One result row; the column names are shared by every row of a result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: Rc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn new(columns: Rc<[String]>, values: Vec<Value>) -> Self {
+        Row { columns, values }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        let index = self.columns.iter().position(|name| name == column)?;
+        self.values.get(index)
+    }
+
+    pub fn get_as<T: FromValue>(&self, column: &str) -> Result<T, DbError> {
+        let value = self.get(column).ok_or_else(|| DbError::Column {
+            column: column.to_string(),
+            message: "no such column in the result".to_string(),
+        })?;
+        T::from_value(value).map_err(|message| DbError::Column {
+            column: column.to_string(),
+            message,
+        })
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+}
+
+// Statements that produce rows (SELECT, PRAGMA, ... RETURNING) return `Rows`; everything else
+// returns how many rows it changed
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryResult {
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Row>,
+    },
+    Affected(u64),
+}
+
+impl QueryResult {
+    // Builds a row set by hand, e.g. for scripted results. Panics if a row does not have one
+    // value per column
+    pub fn rows(columns: &[&str], rows: Vec<Vec<Value>>) -> Self {
+        for (i, values) in rows.iter().enumerate() {
+            assert_eq!(
+                values.len(),
+                columns.len(),
+                "row {} has {} value(s) for {} column(s)",
+                i + 1,
+                values.len(),
+                columns.len()
+            );
+        }
+        let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+        let shared: Rc<[String]> = columns.clone().into();
+        let rows = rows
+            .into_iter()
+            .map(|values| Row::new(Rc::clone(&shared), values))
+            .collect();
+        QueryResult::Rows { columns, rows }
+    }
+}
+
+// Rows print as an aligned table
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (columns, rows) = match self {
+            QueryResult::Affected(n) => return write!(f, "{} row(s) affected", n),
+            QueryResult::Rows { columns, rows } => (columns, rows),
+        };
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.values().iter().map(Value::to_string).collect())
+            .collect();
+        let widths: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |values: &[String]| -> String {
+            let padded: Vec<String> = values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{:<width$}", value, width = width))
+                .collect();
+            padded.join(" | ").trim_end().to_string()
+        };
+        writeln!(f, "{}", line(columns))?;
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        writeln!(f, "{}", rule.join("-+-"))?;
+        for row in &cells {
+            writeln!(f, "{}", line(row))?;
+        }
+        write!(f, "({} row(s))", rows.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_print_as_an_aligned_table() {
+        let result = QueryResult::rows(
+            &["id", "name"],
+            vec![
+                vec![Value::Integer(1), Value::Text("alice".to_string())],
+                vec![Value::Integer(22), Value::Null],
+            ],
+        );
+        assert_eq!(
+            result.to_string(),
+            "id | name\n---+------\n1  | alice\n22 | NULL\n(2 row(s))"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "row 2 has 1 value(s) for 2 column(s)")]
+    fn rows_must_have_one_value_per_column() {
+        QueryResult::rows(
+            &["id", "name"],
+            vec![
+                vec![Value::Integer(1), Value::Text("alice".to_string())],
+                vec![Value::Integer(2)],
+            ],
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::query::{ToValue, Value};
+
+// A neutral statement representation: built once, rendered to each backend's SQL by its
+// `Dialect`
+//
+//   Select::from("users")
+//       .filter(Condition::eq("active", true).and(Condition::lt("score", 8.0)))
+//       .order_by("score", Order::Desc)
+//       .limit(10)
+
+// A value in a statement. Values become bound parameters; booleans are written as literals,
+// since that is where the dialects differ
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Param(Value),
+    Bool(bool),
+}
+
+impl From<bool> for Term {
+    fn from(value: bool) -> Self {
+        Term::Bool(value)
+    }
+}
+
+impl From<Value> for Term {
+    fn from(value: Value) -> Self {
+        Term::Param(value)
+    }
+}
+
+impl From<i64> for Term {
+    fn from(value: i64) -> Self {
+        Term::Param(value.to_value())
+    }
+}
+
+impl From<i32> for Term {
+    fn from(value: i32) -> Self {
+        Term::Param(value.to_value())
+    }
+}
+
+impl From<f64> for Term {
+    fn from(value: f64) -> Self {
+        Term::Param(value.to_value())
+    }
+}
+
+impl From<&str> for Term {
+    fn from(value: &str) -> Self {
+        Term::Param(value.to_value())
+    }
+}
+
+impl From<String> for Term {
+    fn from(value: String) -> Self {
+        Term::Param(value.to_value())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+}
+
+impl Op {
+    pub fn sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Like => "LIKE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare(String, Op, Term),
+    IsNull(String),
+    In(String, Vec<Term>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn compare(column: &str, op: Op, value: impl Into<Term>) -> Self {
+        Condition::Compare(column.to_string(), op, value.into())
+    }
+
+    // Comparing with `Value::Null` is rendered as `IS NULL`, since `= NULL` matches nothing
+    pub fn eq(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Eq, value)
+    }
+
+    // Rendered as `IS NOT NULL` for `Value::Null`
+    pub fn ne(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Ne, value)
+    }
+
+    pub fn lt(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Lt, value)
+    }
+
+    pub fn le(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Le, value)
+    }
+
+    pub fn gt(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Gt, value)
+    }
+
+    pub fn ge(column: &str, value: impl Into<Term>) -> Self {
+        Self::compare(column, Op::Ge, value)
+    }
+
+    pub fn like(column: &str, pattern: &str) -> Self {
+        Self::compare(column, Op::Like, pattern)
+    }
+
+    pub fn is_null(column: &str) -> Self {
+        Condition::IsNull(column.to_string())
+    }
+
+    pub fn is_in<T: Into<Term>>(column: &str, values: impl IntoIterator<Item = T>) -> Self {
+        Condition::In(
+            column.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    pub fn and(self, other: Condition) -> Self {
+        match self {
+            Condition::And(mut all) => {
+                all.push(other);
+                Condition::And(all)
+            }
+            this => Condition::And(vec![this, other]),
+        }
+    }
+
+    pub fn or(self, other: Condition) -> Self {
+        match self {
+            Condition::Or(mut any) => {
+                any.push(other);
+                Condition::Or(any)
+            }
+            this => Condition::Or(vec![this, other]),
+        }
+    }
+
+    pub fn not(self) -> Self {
+        Condition::Not(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub table: String,
+    // Empty selects every column
+    pub columns: Vec<String>,
+    pub filter: Option<Condition>,
+    pub order_by: Vec<(String, Order)>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl Select {
+    pub fn from(table: &str) -> Self {
+        Select {
+            table: table.to_string(),
+            columns: Vec::new(),
+            filter: None,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        }
+    }
+
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.filter = Some(condition);
+        self
+    }
+
+    pub fn order_by(mut self, column: &str, order: Order) -> Self {
+        self.order_by.push((column.to_string(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+}
+
+// Rows that already exist (by the `conflict` columns) get the `update` columns overwritten
+// with the new values instead; with no `update` columns they are left alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upsert {
+    pub conflict: Vec<String>,
+    pub update: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Term>>,
+    pub upsert: Option<Upsert>,
+}
+
+impl Insert {
+    pub fn into(table: &str, columns: &[&str]) -> Self {
+        Insert {
+            table: table.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+            upsert: None,
+        }
+    }
+
+    pub fn values(mut self, row: Vec<Term>) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    pub fn on_conflict(mut self, conflict: &[&str], update: &[&str]) -> Self {
+        self.upsert = Some(Upsert {
+            conflict: conflict.iter().map(|c| c.to_string()).collect(),
+            update: update.iter().map(|c| c.to_string()).collect(),
+        });
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub table: String,
+    pub set: Vec<(String, Term)>,
+    pub filter: Option<Condition>,
+}
+
+impl Update {
+    pub fn table(table: &str) -> Self {
+        Update {
+            table: table.to_string(),
+            set: Vec::new(),
+            filter: None,
+        }
+    }
+
+    pub fn set(mut self, column: &str, value: impl Into<Term>) -> Self {
+        self.set.push((column.to_string(), value.into()));
+        self
+    }
+
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.filter = Some(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub table: String,
+    pub filter: Option<Condition>,
+}
+
+impl Delete {
+    pub fn from(table: &str) -> Self {
+        Delete {
+            table: table.to_string(),
+            filter: None,
+        }
+    }
+
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.filter = Some(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Select(Select),
+    Insert(Insert),
+    Update(Update),
+    Delete(Delete),
+}
+
+impl From<Select> for Command {
+    fn from(select: Select) -> Self {
+        Command::Select(select)
+    }
+}
+
+impl From<Insert> for Command {
+    fn from(insert: Insert) -> Self {
+        Command::Insert(insert)
+    }
+}
+
+impl From<Update> for Command {
+    fn from(update: Update) -> Self {
+        Command::Update(update)
+    }
+}
+
+impl From<Delete> for Command {
+    fn from(delete: Delete) -> Self {
+        Command::Delete(delete)
+    }
+}
@@ -0,0 +1,334 @@
+use crate::dialect::{Dialect, SqliteDialect};
+use crate::query::{DbError, QueryResult, Row, Value};
+use crate::transaction::IsolationLevel;
+use crate::url::ConnectionUrl;
+use crate::{print_query, ConnectionFactory, DatabaseConnection};
+use rusqlite::types::{self, ValueRef};
+use rusqlite::{params_from_iter, Connection, OpenFlags};
+use std::rc::Rc;
+
+// ConcreteProduct: SQLiteConnection, backed by an embedded SQLite database. The database is
+// opened on `connect`, so creating a connection never touches the filesystem
+pub struct SQLiteConnection {
+    url: ConnectionUrl,
+    conn: Option<Connection>,
+}
+
+impl SQLiteConnection {
+    pub fn new(url: &ConnectionUrl) -> Self {
+        SQLiteConnection {
+            url: url.clone(),
+            conn: None,
+        }
+    }
+
+    // `?mode=ro` opens read-only, `?mode=rw` requires an existing file; the default creates it
+    fn flags(&self) -> Result<OpenFlags, DbError> {
+        let base = OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let mut flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+        for (key, value) in &self.url.options {
+            flags = match (key.as_str(), value.as_str()) {
+                ("mode", "ro") => OpenFlags::SQLITE_OPEN_READ_ONLY,
+                ("mode", "rw") => OpenFlags::SQLITE_OPEN_READ_WRITE,
+                ("mode", "rwc") => flags,
+                _ => {
+                    return Err(DbError::Connection(format!(
+                        "unsupported SQLite option `{}={}`",
+                        key, value
+                    )))
+                }
+            };
+        }
+        Ok(base | flags)
+    }
+}
+
+fn to_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::Integer(i),
+        ValueRef::Real(r) => Value::Real(r),
+        ValueRef::Text(text) => Value::Text(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(bytes) => Value::Blob(bytes.to_vec()),
+    }
+}
+
+fn to_sql(value: &Value) -> types::Value {
+    match value {
+        Value::Null => types::Value::Null,
+        Value::Integer(i) => types::Value::Integer(*i),
+        Value::Real(r) => types::Value::Real(*r),
+        Value::Text(s) => types::Value::Text(s.clone()),
+        Value::Blob(bytes) => types::Value::Blob(bytes.clone()),
+    }
+}
+
+fn run(conn: &Connection, query: &str, params: &[Value]) -> rusqlite::Result<QueryResult> {
+    let mut statement = conn.prepare(query)?;
+    let params = params_from_iter(params.iter().map(to_sql));
+    if statement.column_count() == 0 {
+        return statement
+            .execute(params)
+            .map(|n| QueryResult::Affected(n as u64));
+    }
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let shared: Rc<[String]> = columns.clone().into();
+    let mut rows = Vec::new();
+    let mut cursor = statement.query(params)?;
+    while let Some(row) = cursor.next()? {
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(to_value))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.push(Row::new(Rc::clone(&shared), values));
+    }
+    Ok(QueryResult::Rows { columns, rows })
+}
+
+impl DatabaseConnection for SQLiteConnection {
+    fn connect(&mut self) -> Result<(), DbError> {
+        let path = self.url.database.as_deref().unwrap_or(":memory:");
+        println!("Connecting to SQLite database {}...", path);
+        let conn = Connection::open_with_flags(path, self.flags()?)
+            .map_err(|e| DbError::Connection(format!("{}: {}", path, e)))?;
+        self.conn = Some(conn);
+        Ok(())
+    }
+
+    fn dialect(&self) -> &'static dyn Dialect {
+        &SqliteDialect
+    }
+
+    fn execute_with(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult, DbError> {
+        print_query(sql, params);
+        let conn = self.conn.as_ref().ok_or(DbError::NotConnected)?;
+        run(conn, sql, params).map_err(|e| DbError::Query {
+            query: sql.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    // SQLite compiles the statement, so syntax errors and unknown tables surface at `prepare`
+    fn parameter_count(&mut self, sql: &str) -> Result<usize, DbError> {
+        let conn = self.conn.as_ref().ok_or(DbError::NotConnected)?;
+        let statement = conn.prepare(sql).map_err(|e| DbError::Query {
+            query: sql.to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(statement.parameter_count())
+    }
+
+    // Transactions in SQLite are always serializable
+    fn isolation_levels(&self) -> &'static [IsolationLevel] {
+        &[IsolationLevel::Serializable]
+    }
+
+    fn begin_statements(&self, _level: Option<IsolationLevel>) -> Vec<String> {
+        vec!["BEGIN".to_string()]
+    }
+}
+
+// ConcreteCreator: SQLiteConnectionFactory. The URL names a database file, or `:memory:` for
+// a private in-memory database per connection
+pub struct SQLiteConnectionFactory;
+
+impl ConnectionFactory for SQLiteConnectionFactory {
+    fn default_url(&self) -> &'static str {
+        "sqlite::memory:"
+    }
+
+    fn create_connection(&self, url: &ConnectionUrl) -> Box<dyn DatabaseConnection> {
+        Box::new(SQLiteConnection::new(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn connection(url: &str) -> SQLiteConnection {
+        SQLiteConnection::new(&ConnectionUrl::parse(url).unwrap())
+    }
+
+    fn memory() -> SQLiteConnection {
+        let mut conn = connection("sqlite::memory:");
+        conn.connect().unwrap();
+        conn
+    }
+
+    // A database file in the temp directory holding one row in `users`
+    fn database_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("sqlite-test-{}-{}.db", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        let mut conn = connection(&format!("sqlite://{}", path.display()));
+        conn.connect().unwrap();
+        conn.execute_query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")
+            .unwrap();
+        conn.execute_query("INSERT INTO users (name) VALUES ('ada')")
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn statements_without_columns_report_affected_rows() {
+        let mut conn = memory();
+        let created = conn.execute_query("CREATE TABLE users (id INTEGER, name TEXT)");
+        assert!(matches!(created, Ok(QueryResult::Affected(0))));
+
+        let inserted = conn.execute_with(
+            "INSERT INTO users VALUES (?, ?), (?, ?)",
+            &[
+                Value::Integer(1),
+                Value::Text("ada".to_string()),
+                Value::Integer(2),
+                Value::Null,
+            ],
+        );
+        assert!(matches!(inserted, Ok(QueryResult::Affected(2))));
+    }
+
+    #[test]
+    fn queries_return_columns_and_rows() {
+        let mut conn = memory();
+        let result = conn
+            .execute_query(
+                "SELECT 1 AS id, 'ada' AS name, 2.5 AS score, NULL AS note, x'ff' AS raw",
+            )
+            .unwrap();
+        let QueryResult::Rows { columns, rows } = result else {
+            panic!("expected rows, got {:?}", result);
+        };
+        assert_eq!(columns, ["id", "name", "score", "note", "raw"]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0].values(),
+            [
+                Value::Integer(1),
+                Value::Text("ada".to_string()),
+                Value::Real(2.5),
+                Value::Null,
+                Value::Blob(vec![0xff]),
+            ]
+        );
+        assert_eq!(rows[0].get_as::<String>("name").unwrap(), "ada");
+        assert_eq!(rows[0].get_as::<Option<String>>("note").unwrap(), None);
+    }
+
+    #[test]
+    fn a_select_matching_nothing_is_still_a_row_set() {
+        let mut conn = memory();
+        conn.execute_query("CREATE TABLE users (id INTEGER)")
+            .unwrap();
+        let result = conn.execute_query("SELECT id FROM users").unwrap();
+        let QueryResult::Rows { columns, rows } = result else {
+            panic!("expected rows, got {:?}", result);
+        };
+        assert_eq!(columns, ["id"]);
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn get_as_reports_type_mismatches_and_unknown_columns() {
+        let mut conn = memory();
+        let result = conn
+            .execute_query("SELECT 'ada' AS name, NULL AS age")
+            .unwrap();
+        let QueryResult::Rows { rows, .. } = result else {
+            panic!("expected rows, got {:?}", result);
+        };
+        match rows[0].get_as::<i64>("name") {
+            Err(DbError::Column { column, message }) => {
+                assert_eq!(column, "name");
+                assert_eq!(message, "expected INTEGER, found TEXT");
+            }
+            other => panic!("expected a column error, got {:?}", other),
+        }
+        assert!(matches!(
+            rows[0].get_as::<i64>("age"),
+            Err(DbError::Column { .. })
+        ));
+        match rows[0].get_as::<String>("email") {
+            Err(DbError::Column { column, message }) => {
+                assert_eq!(column, "email");
+                assert_eq!(message, "no such column in the result");
+            }
+            other => panic!("expected a column error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_only_mode_rejects_writes() {
+        let path = database_file("ro");
+        let mut conn = connection(&format!("sqlite://{}?mode=ro", path.display()));
+        conn.connect().unwrap();
+
+        let rows = conn.execute_query("SELECT name FROM users").unwrap();
+        assert!(matches!(rows, QueryResult::Rows { ref rows, .. } if rows.len() == 1));
+        match conn.execute_query("INSERT INTO users (name) VALUES ('bob')") {
+            Err(DbError::Query { message, .. }) => {
+                assert!(message.contains("readonly"), "{}", message)
+            }
+            other => panic!("expected a query error, got {:?}", other),
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_write_mode_needs_an_existing_file() {
+        let path =
+            std::env::temp_dir().join(format!("sqlite-test-{}-missing.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut conn = connection(&format!("sqlite://{}?mode=rw", path.display()));
+        assert!(matches!(conn.connect(), Err(DbError::Connection(_))));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unsupported_options_fail_connect() {
+        for url in [
+            "sqlite::memory:?cache=shared",
+            "sqlite::memory:?mode=memory",
+        ] {
+            let mut conn = connection(url);
+            match conn.connect() {
+                Err(DbError::Connection(message)) => {
+                    assert!(
+                        message.starts_with("unsupported SQLite option"),
+                        "{}",
+                        message
+                    )
+                }
+                other => panic!("{}: expected a connection error, got {:?}", url, other),
+            }
+        }
+    }
+
+    #[test]
+    fn using_a_connection_before_connect_is_an_error() {
+        let mut conn = connection("sqlite::memory:");
+        assert!(matches!(
+            conn.execute_query("SELECT 1"),
+            Err(DbError::NotConnected)
+        ));
+        assert!(matches!(
+            conn.parameter_count("SELECT ?"),
+            Err(DbError::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn parameter_count_compiles_the_statement() {
+        let mut conn = memory();
+        assert_eq!(conn.parameter_count("SELECT ?, ?").unwrap(), 2);
+        assert!(matches!(
+            conn.parameter_count("SELECT * FROM missing"),
+            Err(DbError::Query { .. })
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::query::{DbError, QueryResult, ToValue, Value};
+use crate::DatabaseConnection;
+
+// Replaces each `?` placeholder in `sql` with `placeholder(n)` (1-based), skipping quoted
+// strings, identifiers and comments. Returns the new SQL and the number of placeholders
+pub fn rewrite_placeholders(sql: &str, placeholder: impl Fn(usize) -> String) -> (String, usize) {
+    let mut out = String::with_capacity(sql.len());
+    let mut count = 0;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '?' => {
+                count += 1;
+                out += &placeholder(count);
+                continue;
+            }
+            '\'' | '"' | '`' => {
+                out.push(c);
+                // A doubled quote inside the string is an escaped quote, which this skips too
+                for inner in chars.by_ref() {
+                    out.push(inner);
+                    if inner == c {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                out.push(c);
+                for inner in chars.by_ref() {
+                    out.push(inner);
+                    if inner == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            // Block comments do not nest in MySQL or SQLite, so the first `*/` ends one
+            '/' if chars.peek() == Some(&'*') => {
+                out.push(c);
+                out.extend(chars.next());
+                let mut previous = c;
+                for inner in chars.by_ref() {
+                    out.push(inner);
+                    if previous == '*' && inner == '/' {
+                        break;
+                    }
+                    previous = inner;
+                }
+                continue;
+            }
+            _ => out.push(c),
+        }
+    }
+    (out, count)
+}
+
+pub fn count_placeholders(sql: &str) -> usize {
+    rewrite_placeholders(sql, |_| String::new()).1
+}
+
+// A prepared statement: SQL with `?` placeholders and typed parameters bound by position
+// (1-based). Parameters stay bound between runs, so one statement can execute many times.
+// The `?`s are written in the backend's own placeholder syntax (e.g. `$1`) when it runs
+//
+//   let mut insert = connection.prepare("INSERT INTO users (name, score) VALUES (?, ?)")?;
+//   insert.bind(1, "dave")?.bind(2, Some(6.5))?.execute()?;
+pub struct Statement<'c> {
+    connection: &'c mut dyn DatabaseConnection,
+    sql: String,
+    params: Vec<Option<Value>>,
+}
+
+impl<'c> Statement<'c> {
+    pub(crate) fn new(connection: &'c mut dyn DatabaseConnection, sql: &str, count: usize) -> Self {
+        Statement {
+            connection,
+            sql: sql.to_string(),
+            params: vec![None; count],
+        }
+    }
+
+    pub fn bind<T: ToValue>(&mut self, index: usize, value: T) -> Result<&mut Self, DbError> {
+        let count = self.params.len();
+        let slot = index
+            .checked_sub(1)
+            .and_then(|i| self.params.get_mut(i))
+            .ok_or_else(|| {
+                DbError::Bind(format!(
+                    "index {} is out of range; the statement takes {} parameter(s)",
+                    index, count
+                ))
+            })?;
+        *slot = Some(value.to_value());
+        Ok(self)
+    }
+
+    pub fn clear_bindings(&mut self) {
+        self.params.iter_mut().for_each(|param| *param = None);
+    }
+
+    pub fn execute(&mut self) -> Result<QueryResult, DbError> {
+        let params = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                param
+                    .clone()
+                    .ok_or_else(|| DbError::Bind(format!("parameter {} is not bound", i + 1)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let dialect = self.connection.dialect();
+        let (sql, _) = rewrite_placeholders(&self.sql, |n| dialect.placeholder(n));
+        self.connection.execute_with(&sql, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(sql: &str) -> (String, usize) {
+        rewrite_placeholders(sql, |n| format!("${}", n))
+    }
+
+    #[test]
+    fn placeholders_are_numbered_in_order() {
+        assert_eq!(
+            numbered("SELECT * FROM t WHERE a = ? AND b IN (?, ?)"),
+            (
+                "SELECT * FROM t WHERE a = $1 AND b IN ($2, $3)".to_string(),
+                3
+            )
+        );
+        assert_eq!(numbered("SELECT 1"), ("SELECT 1".to_string(), 0));
+    }
+
+    #[test]
+    fn quoted_question_marks_are_left_alone() {
+        assert_eq!(
+            numbered(r#"SELECT '?', "a?", `b?`, 'it''s ?' FROM t WHERE c = ?"#),
+            (
+                r#"SELECT '?', "a?", `b?`, 'it''s ?' FROM t WHERE c = $1"#.to_string(),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn line_comments_are_left_alone() {
+        assert_eq!(
+            numbered("SELECT ? -- why?\nFROM t WHERE a = ?"),
+            ("SELECT $1 -- why?\nFROM t WHERE a = $2".to_string(), 2)
+        );
+        assert_eq!(numbered("SELECT 1 -- ?"), ("SELECT 1 -- ?".to_string(), 0));
+    }
+
+    #[test]
+    fn block_comments_are_left_alone() {
+        assert_eq!(
+            numbered("SELECT /* ? */ ? FROM t /* multi\nline? */ WHERE a = ?"),
+            (
+                "SELECT /* ? */ $1 FROM t /* multi\nline? */ WHERE a = $2".to_string(),
+                2
+            )
+        );
+        // `/*/` does not close the comment it opens
+        assert_eq!(
+            numbered("SELECT /*/ ? */ ?"),
+            ("SELECT /*/ ? */ $1".to_string(), 1)
+        );
+        assert_eq!(numbered("SELECT 1 /* ?"), ("SELECT 1 /* ?".to_string(), 0));
+    }
+
+    #[test]
+    fn division_is_not_a_comment() {
+        assert_eq!(
+            numbered("SELECT a / ? FROM t"),
+            ("SELECT a / $1 FROM t".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn count_matches_the_rewrite() {
+        assert_eq!(
+            count_placeholders("INSERT INTO t VALUES (?, '?', ?) /* ? */"),
+            2
+        );
+    }
+}
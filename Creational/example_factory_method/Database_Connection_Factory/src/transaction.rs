@@ -0,0 +1,285 @@
+use crate::query::DbError;
+use crate::DatabaseConnection;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The standard `START TRANSACTION ISOLATION LEVEL ...`; backends with other syntax override
+// `DatabaseConnection::begin_statements`
+pub fn standard_begin(level: Option<IsolationLevel>) -> Vec<String> {
+    match level {
+        None => vec!["BEGIN".to_string()],
+        Some(level) => vec![format!("START TRANSACTION ISOLATION LEVEL {}", level)],
+    }
+}
+
+// Guard for an open transaction. `commit` or `rollback` end it; dropping it without either
+// rolls back, and so does a failed `commit`, since some databases (SQLite on a deferred
+// constraint, for one) leave the transaction open when COMMIT fails. Derefs to the
+// connection, so statements run inside the transaction:
+//
+//   let mut tx = connection.begin()?;
+//   tx.execute_query("UPDATE ...")?;
+//   tx.commit()?;
+pub(crate) struct Transaction<'c> {
+    connection: &'c mut dyn DatabaseConnection,
+    savepoints: usize,
+    finished: bool,
+}
+
+impl<'c> Transaction<'c> {
+    pub(crate) fn begin(
+        connection: &'c mut dyn DatabaseConnection,
+        level: Option<IsolationLevel>,
+    ) -> Result<Self, DbError> {
+        if let Some(level) = level {
+            let supported = connection.isolation_levels();
+            if !supported.contains(&level) {
+                let names: Vec<String> = supported.iter().map(ToString::to_string).collect();
+                return Err(DbError::Unsupported(format!(
+                    "isolation level {}; this backend supports {}",
+                    level,
+                    names.join(", ")
+                )));
+            }
+        }
+        for statement in connection.begin_statements(level) {
+            connection.execute_query(&statement)?;
+        }
+        Ok(Transaction {
+            connection,
+            savepoints: 0,
+            finished: false,
+        })
+    }
+
+    pub fn savepoint(&mut self) -> Result<Savepoint<'_>, DbError> {
+        Savepoint::create(&mut *self.connection, &mut self.savepoints)
+    }
+
+    pub fn commit(mut self) -> Result<(), DbError> {
+        self.connection.execute_query("COMMIT")?;
+        self.finished = true;
+        Ok(())
+    }
+
+    pub fn rollback(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.connection.execute_query("ROLLBACK").map(|_| ())
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Nothing to report a failure to; the database discards the transaction anyway
+            // once the connection closes
+            let _ = self.connection.execute_query("ROLLBACK");
+        }
+    }
+}
+
+impl<'c> Deref for Transaction<'c> {
+    type Target = dyn DatabaseConnection + 'c;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.connection
+    }
+}
+
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.connection
+    }
+}
+
+// Guard for a savepoint inside a transaction (or inside another savepoint). `release` keeps
+// its changes; `rollback`, dropping it or a failed `release` undoes everything since it was
+// created, while the enclosing transaction carries on
+pub(crate) struct Savepoint<'t> {
+    connection: &'t mut dyn DatabaseConnection,
+    counter: &'t mut usize,
+    name: String,
+    finished: bool,
+}
+
+impl<'t> Savepoint<'t> {
+    fn create(
+        connection: &'t mut dyn DatabaseConnection,
+        counter: &'t mut usize,
+    ) -> Result<Self, DbError> {
+        *counter += 1;
+        let name = format!("sp{}", counter);
+        connection.execute_query(&format!("SAVEPOINT {}", name))?;
+        Ok(Savepoint {
+            connection,
+            counter,
+            name,
+            finished: false,
+        })
+    }
+
+    pub fn savepoint(&mut self) -> Result<Savepoint<'_>, DbError> {
+        Savepoint::create(&mut *self.connection, &mut *self.counter)
+    }
+
+    pub fn release(mut self) -> Result<(), DbError> {
+        let release = format!("RELEASE SAVEPOINT {}", self.name);
+        self.connection.execute_query(&release)?;
+        self.finished = true;
+        Ok(())
+    }
+
+    pub fn rollback(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.undo()
+    }
+
+    fn undo(&mut self) -> Result<(), DbError> {
+        let rollback = format!("ROLLBACK TO SAVEPOINT {}", self.name);
+        self.connection.execute_query(&rollback)?;
+        let release = format!("RELEASE SAVEPOINT {}", self.name);
+        self.connection.execute_query(&release).map(|_| ())
+    }
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.undo();
+        }
+    }
+}
+
+impl<'t> Deref for Savepoint<'t> {
+    type Target = dyn DatabaseConnection + 't;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.connection
+    }
+}
+
+impl DerefMut for Savepoint<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.connection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::{Matcher, MockDatabase};
+    use crate::query::{DbError, QueryResult, Value};
+    use crate::sqlite::SQLiteConnection;
+    use crate::url::ConnectionUrl;
+    use crate::DatabaseConnection;
+
+    fn sqlite() -> Box<dyn DatabaseConnection> {
+        let url = ConnectionUrl::parse("sqlite::memory:").unwrap();
+        let mut conn: Box<dyn DatabaseConnection> = Box::new(SQLiteConnection::new(&url));
+        conn.connect().unwrap();
+        conn
+    }
+
+    fn count(conn: &mut dyn DatabaseConnection, table: &str) -> i64 {
+        let query = format!("SELECT COUNT(*) AS n FROM {}", table);
+        match conn.execute_query(&query).unwrap() {
+            QueryResult::Rows { rows, .. } => rows[0].get_as("n").unwrap(),
+            other => panic!("expected rows, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_commit_rolls_back() {
+        let mut conn = sqlite();
+        for statement in [
+            "PRAGMA foreign_keys = ON",
+            "CREATE TABLE teams (id INTEGER PRIMARY KEY)",
+            "CREATE TABLE players (id INTEGER PRIMARY KEY, team INTEGER \
+             REFERENCES teams (id) DEFERRABLE INITIALLY DEFERRED)",
+        ] {
+            conn.execute_query(statement).unwrap();
+        }
+        let mut tx = conn.begin().unwrap();
+        // Deferred, so the missing team only fails the COMMIT, which SQLite leaves open
+        tx.execute_with(
+            "INSERT INTO players (id, team) VALUES (1, ?)",
+            &[Value::Integer(9)],
+        )
+        .unwrap();
+        match tx.commit() {
+            Err(DbError::Query { message, .. }) => assert!(message.contains("FOREIGN KEY")),
+            other => panic!("expected the COMMIT to fail, got {:?}", other),
+        }
+        assert_eq!(count(&mut *conn, "players"), 0);
+        // No transaction is left open
+        let tx = conn.begin().unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn commit_keeps_changes() {
+        let mut conn = sqlite();
+        conn.execute_query("CREATE TABLE t (x INTEGER)").unwrap();
+        let mut tx = conn.begin().unwrap();
+        tx.execute_query("INSERT INTO t VALUES (1)").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(count(&mut *conn, "t"), 1);
+    }
+
+    #[test]
+    fn dropped_savepoint_undoes_only_its_changes() {
+        let mut conn = sqlite();
+        conn.execute_query("CREATE TABLE t (x INTEGER)").unwrap();
+        let mut tx = conn.begin().unwrap();
+        tx.execute_query("INSERT INTO t VALUES (1)").unwrap();
+        {
+            let mut sp = tx.savepoint().unwrap();
+            sp.execute_query("INSERT INTO t VALUES (2)").unwrap();
+        }
+        tx.commit().unwrap();
+        assert_eq!(count(&mut *conn, "t"), 1);
+    }
+
+    #[test]
+    fn failed_release_rolls_back_to_the_savepoint() {
+        let db = MockDatabase::new();
+        db.when(Matcher::exact("RELEASE SAVEPOINT sp1"))
+            .fails("disk I/O error");
+        let mut conn: Box<dyn DatabaseConnection> = Box::new(db.connection());
+        conn.connect().unwrap();
+        let mut tx = conn.begin().unwrap();
+        let sp = tx.savepoint().unwrap();
+        assert!(sp.release().is_err());
+        tx.rollback().unwrap();
+        assert_eq!(
+            db.calls(),
+            [
+                "BEGIN",
+                "SAVEPOINT sp1",
+                "RELEASE SAVEPOINT sp1",
+                "ROLLBACK TO SAVEPOINT sp1",
+                "RELEASE SAVEPOINT sp1",
+                "ROLLBACK",
+            ]
+        );
+    }
+}
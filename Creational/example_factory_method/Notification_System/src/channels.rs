@@ -0,0 +1,108 @@
+use crate::{Notification, NotificationFactory};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    Unknown { channel: String, known: Vec<String> },
+    Duplicate(String),
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelError::Unknown { channel, known } => write!(
+                f,
+                "unknown channel `{}`; available: {}",
+                channel,
+                known.join(", ")
+            ),
+            ChannelError::Duplicate(channel) => {
+                write!(f, "channel `{}` is already registered", channel)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {}
+
+// Every channel a notification can go out on, by the name used on the command line and in
+// `notifications.conf`. Names are matched ignoring case and surrounding spaces
+#[derive(Default)]
+pub struct Channels {
+    factories: BTreeMap<String, Box<dyn NotificationFactory>>,
+}
+
+impl Channels {
+    pub fn register(
+        &mut self,
+        channel: &str,
+        factory: Box<dyn NotificationFactory>,
+    ) -> Result<(), ChannelError> {
+        let key = channel.trim().to_ascii_lowercase();
+        if self.factories.contains_key(&key) {
+            return Err(ChannelError::Duplicate(key));
+        }
+        self.factories.insert(key, factory);
+        Ok(())
+    }
+
+    pub fn create(&self, channel: &str) -> Result<Box<dyn Notification>, ChannelError> {
+        match self.factories.get(&channel.trim().to_ascii_lowercase()) {
+            Some(factory) => Ok(factory.create_notification()),
+            None => Err(ChannelError::Unknown {
+                channel: channel.to_string(),
+                known: self.names(),
+            }),
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_channels, plugin, PushNotificationFactory};
+
+    #[test]
+    fn channels_are_found_ignoring_case() {
+        let channels = default_channels();
+        assert!(channels.create("Email").is_ok());
+        assert!(channels.create(" SMS ").is_ok());
+        assert_eq!(channels.names(), ["email", "push", "sms"]);
+    }
+
+    #[test]
+    fn a_channel_cannot_be_registered_twice() {
+        let mut channels = default_channels();
+        assert_eq!(
+            channels.register("Push", Box::new(PushNotificationFactory)),
+            Err(ChannelError::Duplicate("push".to_string()))
+        );
+        plugin::register(&mut channels).unwrap();
+        assert!(plugin::register(&mut channels).is_err());
+    }
+
+    #[test]
+    fn unknown_channels_list_the_known_ones() {
+        match default_channels().create("fax") {
+            Err(e) => {
+                assert_eq!(
+                    e,
+                    ChannelError::Unknown {
+                        channel: "fax".to_string(),
+                        known: vec!["email".to_string(), "push".to_string(), "sms".to_string()],
+                    }
+                );
+                assert_eq!(
+                    e.to_string(),
+                    "unknown channel `fax`; available: email, push, sms"
+                );
+            }
+            Ok(_) => panic!("`fax` should not be a channel"),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::Notification;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What the provider said when it accepted a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub message_id: String,
+    pub response: String,
+}
+
+impl Delivery {
+    // Numbers messages across all channels: "email-000001", "sms-000002", ...
+    pub fn accepted(channel: &str, response: &str) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Delivery {
+            message_id: format!("{}-{:06}", channel, id),
+            response: response.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    // Worth retrying: timeouts, rate limits, provider outages
+    Transient(String),
+    // Retrying will not help: the provider rejected the message
+    Permanent(String),
+    // The recipient is malformed for this channel; nothing was sent
+    InvalidRecipient { recipient: String, reason: String },
+}
+
+impl DeliveryError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, DeliveryError::Transient(_))
+    }
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::Transient(response) => write!(f, "transient failure: {}", response),
+            DeliveryError::Permanent(response) => write!(f, "permanent failure: {}", response),
+            DeliveryError::InvalidRecipient { recipient, reason } => {
+                write!(f, "invalid recipient `{}`: {}", recipient, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+// Exponential backoff: the n-th retry waits `base_delay * 2^(n-1)`, capped at `max_delay`,
+// then moved by up to `jitter` (a fraction, 0.0..=1.0) either way so that clients which
+// failed together do not all retry together
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    // Including the first attempt
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // The wait before retry number `retry` (1-based); `random` is uniform in 0.0..1.0
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let backoff = self.base_delay.saturating_mul(1 << exponent);
+        let capped = backoff.min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        // In f64, since jitter can push a `max_delay` near `Duration::MAX` out of range
+        let secs = capped.as_secs_f64() * (1.0 + jitter * (2.0 * random - 1.0));
+        Duration::try_from_secs_f64(secs).unwrap_or(self.max_delay)
+    }
+}
+
+// xorshift64*: plenty for spreading retries out, and reproducible with a fixed seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn with_seed(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::with_seed(nanos)
+    }
+
+    // Uniform in 0.0..1.0
+    pub fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+}
+
+// A message that could not be delivered, kept for inspection or a later resend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub channel: String,
+    pub recipient: String,
+    pub message: String,
+    pub attempts: u32,
+    pub error: DeliveryError,
+}
+
+impl fmt::Display for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} to {} after {} attempt(s): {} (message: {:?})",
+            self.channel, self.recipient, self.attempts, self.error, self.message
+        )
+    }
+}
+
+// Sends through any `Notification`, retrying transient failures under its policy. Every
+// message that finally fails, for whatever reason, lands in the dead-letter list
+pub struct Dispatcher {
+    policy: RetryPolicy,
+    rng: Rng,
+    dead_letters: Vec<DeadLetter>,
+}
+
+impl Dispatcher {
+    pub fn new(policy: RetryPolicy, rng: Rng) -> Self {
+        Dispatcher {
+            policy,
+            rng,
+            dead_letters: Vec::new(),
+        }
+    }
+
+    pub fn send(
+        &mut self,
+        channel: &str,
+        notification: &dyn Notification,
+        recipient: &str,
+        message: &str,
+    ) -> Result<Delivery, DeliveryError> {
+        let mut attempt = 1;
+        loop {
+            let error = match notification.send(recipient, message) {
+                Ok(delivery) => return Ok(delivery),
+                Err(error) => error,
+            };
+            if !error.is_transient() || attempt >= self.policy.max_attempts {
+                self.dead_letters.push(DeadLetter {
+                    channel: channel.to_string(),
+                    recipient: recipient.to_string(),
+                    message: message.to_string(),
+                    attempts: attempt,
+                    error: error.clone(),
+                });
+                return Err(error);
+            }
+            let delay = self.policy.delay(attempt, self.rng.next_f64());
+            println!(
+                "{}: attempt {} failed ({}); retrying in {} ms",
+                channel,
+                attempt,
+                error,
+                delay.as_millis()
+            );
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    pub fn dead_letters(&self) -> &[DeadLetter] {
+        &self.dead_letters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn delay_doubles_each_retry() {
+        let policy = policy(0.0);
+        let delays: Vec<u128> = (1..=4).map(|n| policy.delay(n, 0.5).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800]);
+    }
+
+    #[test]
+    fn delay_is_capped_at_max_delay() {
+        let policy = policy(0.0);
+        assert_eq!(policy.delay(5, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX, 0.5), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn huge_max_delay_does_not_overflow() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(u64::MAX / 2),
+            max_delay: Duration::MAX,
+            ..policy(1.0)
+        };
+        // Jitter past `Duration::MAX` falls back to `max_delay`
+        assert_eq!(policy.delay(u32::MAX, 0.999), Duration::MAX);
+        assert_eq!(policy.delay(u32::MAX, 0.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = policy(0.2);
+        let mut rng = Rng::with_seed(42);
+        let delays: Vec<Duration> = (0..1000).map(|_| policy.delay(3, rng.next_f64())).collect();
+        let (low, high) = (Duration::from_millis(320), Duration::from_millis(480));
+        assert!(delays.iter().all(|d| (low..=high).contains(d)));
+        // Spread out, not all landing on one value
+        let min = delays.iter().min().unwrap();
+        let max = delays.iter().max().unwrap();
+        assert!(*max - *min > Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jitter_extremes() {
+        let policy = policy(0.2);
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(80));
+        assert_eq!(policy.delay(1, 0.5), Duration::from_millis(100));
+    }
+}
@@ -1,36 +1,96 @@
+mod channels;
+mod delivery;
+mod plugin;
+
+use channels::Channels;
+use delivery::{Delivery, DeliveryError, Dispatcher, RetryPolicy, Rng};
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::time::Duration;
+
 // Define the Product trait
 trait Notification {
-    fn send(&self, recipient: &str, message: &str);
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError>;
+}
+
+fn invalid(recipient: &str, reason: &str) -> DeliveryError {
+    DeliveryError::InvalidRecipient {
+        recipient: recipient.to_string(),
+        reason: reason.to_string(),
+    }
 }
 
-// ConcreteProduct: EmailNotification
+// ConcreteProduct: EmailNotification. The providers are simulated; addresses under the
+// reserved `.invalid` domain bounce
 struct EmailNotification;
 
 impl Notification for EmailNotification {
-    fn send(&self, recpient: &str, message: &str) {
-        println!("Email sent to {}: {}", recpient, message);
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError> {
+        let domain = match recipient.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && !domain.contains('@') => domain,
+            _ => {
+                return Err(invalid(
+                    recipient,
+                    "expected an address like name@example.com",
+                ))
+            }
+        };
+        if !domain.contains('.') || recipient.contains(char::is_whitespace) {
+            return Err(invalid(
+                recipient,
+                "expected an address like name@example.com",
+            ));
+        }
+        if domain.ends_with(".invalid") {
+            return Err(DeliveryError::Permanent(format!(
+                "550 5.1.1 <{}>: recipient address rejected",
+                recipient
+            )));
+        }
+        println!("Email sent to {}: {}", recipient, message);
+        Ok(Delivery::accepted("email", "250 2.0.0 OK: queued"))
     }
 }
 
-// ConcreteProduct: SMSNotification
+// ConcreteProduct: SMSNotification. Numbers in the +1 555 range are throttled by the
+// (simulated) carrier
 struct SMSNotification;
 
 impl Notification for SMSNotification {
-    fn send(&self, recipient: &str, message: &str) {
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError> {
+        let digits = recipient.strip_prefix('+').unwrap_or("");
+        if !(8..=15).contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid(
+                recipient,
+                "expected an E.164 number like +447700900123",
+            ));
+        }
+        if digits.starts_with("1555") {
+            return Err(DeliveryError::Transient(
+                "429 Too Many Requests: carrier throttling".to_string(),
+            ));
+        }
         println!("SMS sent to {}: {}", recipient, message);
+        Ok(Delivery::accepted("sms", "202 Accepted"))
     }
 }
 
-// ConcreteProduct: PushNotification
+// ConcreteProduct: PushNotification; recipients are hexadecimal device tokens
 struct PushNotification;
 
 impl Notification for PushNotification {
-    fn send(&self, recipient: &str, message: &str) {
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError> {
+        if recipient.len() < 8 || !recipient.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid(recipient, "expected a hexadecimal device token"));
+        }
         println!("Push notification sent to {}: {}", recipient, message);
+        Ok(Delivery::accepted("push", "200 OK"))
     }
 }
 
-
 // Creator
 trait NotificationFactory {
     fn create_notification(&self) -> Box<dyn Notification>;
@@ -63,19 +123,172 @@ impl NotificationFactory for PushNotificationFactory {
     }
 }
 
+// The built-in channels; `plugin::register` adds the rest
+fn default_channels() -> Channels {
+    let mut channels = Channels::default();
+    let factories: [(&str, Box<dyn NotificationFactory>); 3] = [
+        ("email", Box::new(EmailNotificationFactory)),
+        ("sms", Box::new(SMSNotificationFactory)),
+        ("push", Box::new(PushNotificationFactory)),
+    ];
+    for (channel, factory) in factories {
+        channels.register(channel, factory).unwrap();
+    }
+    channels
+}
+
+// The value of `key = value` in a config file. Blank lines and lines starting with `#` are
+// skipped, the first line with the key wins and a value may be wrapped in double quotes
+fn config_value(contents: &str, key: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value)
+                .to_string()
+        })
+}
+
+// Reads `channel = <name>` from a config file such as `notifications.conf`
+fn configured_channel(path: &str) -> io::Result<Option<String>> {
+    Ok(config_value(&fs::read_to_string(path)?, "channel"))
+}
+
+// A channel whose provider is down for its first few attempts, to show retries recovering
+struct Flaky {
+    inner: Box<dyn Notification>,
+    outages: Cell<u32>,
+}
+
+impl Notification for Flaky {
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError> {
+        if self.outages.get() > 0 {
+            self.outages.set(self.outages.get() - 1);
+            return Err(DeliveryError::Transient(
+                "503 Service Unavailable".to_string(),
+            ));
+        }
+        self.inner.send(recipient, message)
+    }
+}
+
+// A recipient in the form each channel expects
+fn recipient_for(channel: &str) -> &'static str {
+    match channel {
+        "sms" => "+447700900123",
+        "push" => "a3f9c2e17b4d8e60",
+        "slack" => "#general",
+        _ => "user@example.com",
+    }
+}
+
 // Client code
+// Usage: Notification_System [channel...] | --config <file>
 fn main() {
-    let email_factory = EmailNotificationFactory;
-    let sms_factory = SMSNotificationFactory;
-    let push_factory = PushNotificationFactory;
-
-    let notifications: Vec<Box<dyn Notification>> = vec![
-        email_factory.create_notification(),
-        sms_factory.create_notification(),
-        push_factory.create_notification(),
-    ];
+    let mut registered = default_channels();
+    plugin::register(&mut registered).unwrap();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let channels = match args.as_slice() {
+        [] => registered.names(),
+        [flag, path] if flag == "--config" => match configured_channel(path) {
+            Ok(Some(channel)) => vec![channel],
+            Ok(None) => {
+                eprintln!("{}: missing `channel = <name>`", path);
+                process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(2);
+            }
+        },
+        channels => channels.to_vec(),
+    };
+
+    let notifications: Vec<Box<dyn Notification>> = channels
+        .iter()
+        .map(|channel| registered.create(channel))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(20),
+        ..RetryPolicy::default()
+    };
+    let mut dispatcher = Dispatcher::new(policy, Rng::from_clock());
+    for (channel, notification) in channels.iter().zip(&notifications) {
+        let recipient = recipient_for(channel);
+        let message = "This is a notification message.";
+        match dispatcher.send(channel, notification.as_ref(), recipient, message) {
+            Ok(delivery) => println!("  -> {} ({})", delivery.message_id, delivery.response),
+            Err(e) => println!("  -> {} failed: {}", channel, e),
+        }
+    }
+
+    if args.is_empty() {
+        println!();
+        let email = registered.create("email").unwrap();
+        let sms = registered.create("sms").unwrap();
+        let failures = [
+            ("email", email.as_ref(), "not-an-address"),
+            ("email", email.as_ref(), "ghost@example.invalid"),
+            ("sms", sms.as_ref(), "+15550100"),
+        ];
+        for (channel, notification, recipient) in failures {
+            if let Err(e) = dispatcher.send(channel, notification, recipient, "Your order shipped")
+            {
+                println!("  -> {} failed: {}", channel, e);
+            }
+        }
+
+        let flaky = Flaky {
+            inner: registered.create("push").unwrap(),
+            outages: Cell::new(2),
+        };
+        match dispatcher.send("push", &flaky, recipient_for("push"), "Back online") {
+            Ok(delivery) => println!("  -> {} ({})", delivery.message_id, delivery.response),
+            Err(e) => println!("  -> push failed: {}", e),
+        }
+
+        println!();
+        println!("Dead letters:");
+        for letter in dispatcher.dead_letters() {
+            println!("  {}", letter);
+        }
+        println!();
+
+        if let Err(e) = registered.create("pager") {
+            println!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for notification in notifications {
-        notification.send("user@example.com", "This is a notification message.");
+    #[test]
+    fn config_value_skips_comments_and_strips_quotes() {
+        let contents = "# channel = commented\n\n  channel = \"quoted\"  \nchannel = second\n";
+        assert_eq!(
+            config_value(contents, "channel"),
+            Some("quoted".to_string())
+        );
+        assert_eq!(
+            config_value("channel=bare", "channel"),
+            Some("bare".to_string())
+        );
+        assert_eq!(config_value("channels = other", "channel"), None);
+        assert_eq!(config_value("", "channel"), None);
     }
 }
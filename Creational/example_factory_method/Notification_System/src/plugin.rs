@@ -0,0 +1,33 @@
+use crate::channels::{ChannelError, Channels};
+use crate::delivery::{Delivery, DeliveryError};
+use crate::{Notification, NotificationFactory};
+
+// Plugin: Slack, kept apart from the built-in channels. It is compiled in, but needs nothing
+// from `main` beyond the two traits, and `main` only calls `register`
+
+struct SlackNotification;
+
+impl Notification for SlackNotification {
+    fn send(&self, recipient: &str, message: &str) -> Result<Delivery, DeliveryError> {
+        if recipient.len() < 2 || !recipient.starts_with(['#', '@']) {
+            return Err(DeliveryError::InvalidRecipient {
+                recipient: recipient.to_string(),
+                reason: "expected a #channel or @user".to_string(),
+            });
+        }
+        println!("Slack message posted to {}: {}", recipient, message);
+        Ok(Delivery::accepted("slack", "ok"))
+    }
+}
+
+struct SlackNotificationFactory;
+
+impl NotificationFactory for SlackNotificationFactory {
+    fn create_notification(&self) -> Box<dyn Notification> {
+        Box::new(SlackNotification)
+    }
+}
+
+pub fn register(channels: &mut Channels) -> Result<(), ChannelError> {
+    channels.register("slack", Box::new(SlackNotificationFactory))
+}
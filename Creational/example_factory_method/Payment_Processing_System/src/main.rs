@@ -1,4 +1,13 @@
-// Define the Product trait, common operations that all payment methods should support. 
+mod plugin;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::process;
+
+// Define the Product trait, common operations that all payment methods should support.
 trait PaymentMethod {
     fn process_payment(&self, amount: f64);
 }
@@ -22,7 +31,7 @@ impl PaymentMethod for PaypalPayment {
 }
 
 // ConcreteProduct: Bitcoin, specific implementation for payments
-struct BitcoinPayment; 
+struct BitcoinPayment;
 
 impl PaymentMethod for BitcoinPayment {
     fn process_payment(&self, amount: f64) {
@@ -30,7 +39,6 @@ impl PaymentMethod for BitcoinPayment {
     }
 }
 
-
 // Creator, method for creating payment methods
 trait PaymentFactory {
     fn create_payment(&self) -> Box<dyn PaymentMethod>;
@@ -63,18 +71,203 @@ impl PaymentFactory for BitcoinPaymentFactory {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PaymentError {
+    UnknownMethod(String, Vec<String>),
+    DuplicateMethod(String),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaymentError::UnknownMethod(method, accepted) => write!(
+                f,
+                "unknown payment method `{}`; accepted: {}",
+                method,
+                accepted.join(", ")
+            ),
+            PaymentError::DuplicateMethod(method) => {
+                write!(f, "payment method `{}` is already accepted", method)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+// The payment methods the checkout accepts, by the names used on the command line and in
+// `payment.conf`. Names are matched ignoring case and surrounding spaces
+#[derive(Default)]
+struct PaymentMethods {
+    factories: BTreeMap<String, Box<dyn PaymentFactory>>,
+}
+
+impl PaymentMethods {
+    fn accept(
+        &mut self,
+        method: &str,
+        factory: Box<dyn PaymentFactory>,
+    ) -> Result<(), PaymentError> {
+        let key = method.trim().to_ascii_lowercase();
+        if self.factories.contains_key(&key) {
+            return Err(PaymentError::DuplicateMethod(key));
+        }
+        self.factories.insert(key, factory);
+        Ok(())
+    }
+
+    fn create(&self, method: &str) -> Result<Box<dyn PaymentMethod>, PaymentError> {
+        match self.factories.get(&method.trim().to_ascii_lowercase()) {
+            Some(factory) => Ok(factory.create_payment()),
+            None => Err(PaymentError::UnknownMethod(
+                method.to_string(),
+                self.names(),
+            )),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+}
+
+// The built-in payment methods; `plugin::register` adds the rest
+fn default_methods() -> PaymentMethods {
+    let mut methods = PaymentMethods::default();
+    methods
+        .accept("credit_card", Box::new(CreditCardPaymentFactory))
+        .unwrap();
+    methods
+        .accept("paypal", Box::new(PaypalPaymentFactory))
+        .unwrap();
+    methods
+        .accept("bitcoin", Box::new(BitcoinPaymentFactory))
+        .unwrap();
+    methods
+}
+
+// The value of `key = value` in a config file. Blank lines and lines starting with `#` are
+// skipped, the first line with the key wins and a value may be wrapped in double quotes
+fn config_value(contents: &str, key: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value)
+                .to_string()
+        })
+}
+
+// `method = <name>` from a config file such as `payment.conf`
+fn configured_method(path: &str) -> io::Result<Option<String>> {
+    Ok(config_value(&fs::read_to_string(path)?, "method"))
+}
+
+// Usage: Payment_Processing_System [method...] | --config <file>
 fn main() {
-    let credit_card_factory = CreditCardPaymentFactory;
-    let paypal_factory = PaypalPaymentFactory;
-    let bitcoin_factory = BitcoinPaymentFactory;
+    let mut accepted = default_methods();
+    plugin::register(&mut accepted).unwrap();
 
-    let payments = vec![
-        credit_card_factory.create_payment(),
-        paypal_factory.create_payment(),
-        bitcoin_factory.create_payment(),
-    ];
+    let args: Vec<String> = env::args().skip(1).collect();
+    let methods = match args.as_slice() {
+        [] => accepted.names(),
+        [flag, path] if flag == "--config" => match configured_method(path) {
+            Ok(Some(method)) => vec![method],
+            Ok(None) => {
+                eprintln!("{}: missing `method = <name>`", path);
+                process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(2);
+            }
+        },
+        methods => methods.to_vec(),
+    };
+
+    let payments: Vec<Box<dyn PaymentMethod>> = methods
+        .iter()
+        .map(|method| accepted.create(method))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
 
     for payment in payments {
         payment.process_payment(100.0);
     }
+
+    if args.is_empty() {
+        if let Err(e) = accepted.create("cash") {
+            println!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepted_methods_are_found_ignoring_case() {
+        let methods = default_methods();
+        assert!(methods.create("PayPal").is_ok());
+        assert!(methods.create(" bitcoin ").is_ok());
+        assert_eq!(methods.names(), ["bitcoin", "credit_card", "paypal"]);
+    }
+
+    #[test]
+    fn a_method_cannot_be_accepted_twice() {
+        let mut methods = default_methods();
+        assert_eq!(
+            methods.accept("PAYPAL", Box::new(BitcoinPaymentFactory)),
+            Err(PaymentError::DuplicateMethod("paypal".to_string()))
+        );
+        plugin::register(&mut methods).unwrap();
+        assert!(plugin::register(&mut methods).is_err());
+    }
+
+    #[test]
+    fn unknown_methods_list_the_accepted_ones() {
+        match default_methods().create("cash") {
+            Err(e) => {
+                assert_eq!(
+                    e,
+                    PaymentError::UnknownMethod(
+                        "cash".to_string(),
+                        vec![
+                            "bitcoin".to_string(),
+                            "credit_card".to_string(),
+                            "paypal".to_string()
+                        ]
+                    )
+                );
+                assert_eq!(
+                    e.to_string(),
+                    "unknown payment method `cash`; accepted: bitcoin, credit_card, paypal"
+                );
+            }
+            Ok(_) => panic!("`cash` should not be accepted"),
+        }
+    }
+
+    #[test]
+    fn config_value_skips_comments_and_strips_quotes() {
+        let contents = "# method = commented\n\n  method = \"quoted\"  \nmethod = second\n";
+        assert_eq!(config_value(contents, "method"), Some("quoted".to_string()));
+        assert_eq!(
+            config_value("method=bare", "method"),
+            Some("bare".to_string())
+        );
+        assert_eq!(config_value("methods = other", "method"), None);
+        assert_eq!(config_value("", "method"), None);
+    }
 }
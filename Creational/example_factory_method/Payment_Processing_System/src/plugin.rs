@@ -0,0 +1,24 @@
+use crate::{PaymentError, PaymentFactory, PaymentMethod, PaymentMethods};
+
+// Plugin: Apple Pay, added next to the built-in methods rather than among them; `main` only
+// calls `register`
+
+struct ApplePayPayment;
+
+impl PaymentMethod for ApplePayPayment {
+    fn process_payment(&self, amount: f64) {
+        println!("Processing Apple Pay payment for amount: {}", amount);
+    }
+}
+
+struct ApplePayPaymentFactory;
+
+impl PaymentFactory for ApplePayPaymentFactory {
+    fn create_payment(&self) -> Box<dyn PaymentMethod> {
+        Box::new(ApplePayPayment)
+    }
+}
+
+pub fn register(methods: &mut PaymentMethods) -> Result<(), PaymentError> {
+    methods.accept("apple_pay", Box::new(ApplePayPaymentFactory))
+}
@@ -0,0 +1,264 @@
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    String,
+    Integer,
+}
+
+impl FieldKind {
+    fn json_type(&self) -> &'static str {
+        match self {
+            FieldKind::String => "string",
+            FieldKind::Integer => "integer",
+        }
+    }
+}
+
+// Describes one configuration setting; the JSON Schema and everything rendered from it
+// (Markdown reference, sample config, validation) are derived from these specs
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub description: &'static str,
+    pub default: Option<Value>,
+    pub example: Value,
+    pub minimum: Option<u64>,
+    pub maximum: Option<u64>,
+}
+
+pub fn json_schema(title: &str, description: &str, fields: &[FieldSpec]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for field in fields {
+        let mut property = Map::new();
+        property.insert("type".into(), json!(field.kind.json_type()));
+        property.insert("description".into(), json!(field.description));
+        if let Some(default) = &field.default {
+            property.insert("default".into(), default.clone());
+        } else {
+            required.push(json!(field.name));
+        }
+        if let Some(minimum) = field.minimum {
+            property.insert("minimum".into(), json!(minimum));
+        }
+        if let Some(maximum) = field.maximum {
+            property.insert("maximum".into(), json!(maximum));
+        }
+        property.insert("examples".into(), json!([field.example]));
+        properties.insert(field.name.to_string(), Value::Object(property));
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": title,
+        "description": description,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn properties(schema: &Value) -> Vec<(&String, &Value)> {
+    schema["properties"]
+        .as_object()
+        .map(|props| props.iter().collect())
+        .unwrap_or_default()
+}
+
+fn is_required(schema: &Value, name: &str) -> bool {
+    schema["required"]
+        .as_array()
+        .map(|required| required.iter().any(|r| r == name))
+        .unwrap_or(false)
+}
+
+fn range_text(property: &Value) -> String {
+    match (property["minimum"].as_u64(), property["maximum"].as_u64()) {
+        (Some(min), Some(max)) => format!("{} ..= {}", min, max),
+        (Some(min), None) => format!(">= {}", min),
+        (None, Some(max)) => format!("<= {}", max),
+        (None, None) => "-".to_string(),
+    }
+}
+
+// Values are rendered as TOML literals: strings quoted, numbers as-is
+fn toml_literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+pub fn markdown_reference(schema: &Value) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "# {} reference\n\n",
+        schema["title"].as_str().unwrap_or("Configuration")
+    ));
+    if let Some(description) = schema["description"].as_str() {
+        out.push_str(description);
+        out.push_str("\n\n");
+    }
+    out.push_str("| Key | Type | Required | Default | Range | Description |\n");
+    out.push_str("|-----|------|----------|---------|-------|-------------|\n");
+    for (name, property) in properties(schema) {
+        let default = if property["default"].is_null() {
+            "-".to_string()
+        } else {
+            format!("`{}`", toml_literal(&property["default"]))
+        };
+        out.push_str(&format!(
+            "| `{}` | {} | {} | {} | {} | {} |\n",
+            name,
+            property["type"].as_str().unwrap_or("-"),
+            if is_required(schema, name) {
+                "yes"
+            } else {
+                "no"
+            },
+            default,
+            range_text(property),
+            property["description"].as_str().unwrap_or(""),
+        ));
+    }
+    out
+}
+
+pub fn sample_toml(schema: &Value) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "# Sample {} file, generated from the configuration schema\n",
+        schema["title"].as_str().unwrap_or("configuration")
+    ));
+    for (name, property) in properties(schema) {
+        out.push('\n');
+        if let Some(description) = property["description"].as_str() {
+            out.push_str(&format!("# {}\n", description));
+        }
+        let range = range_text(property);
+        if range != "-" {
+            out.push_str(&format!("# Range: {}\n", range));
+        }
+        if is_required(schema, name) {
+            out.push_str("# Required\n");
+            out.push_str(&format!(
+                "{} = {}\n",
+                name,
+                toml_literal(&property["examples"][0])
+            ));
+        } else {
+            out.push_str(&format!(
+                "# Optional, defaults to {}\n",
+                toml_literal(&property["default"])
+            ));
+            out.push_str(&format!(
+                "{} = {}\n",
+                name,
+                toml_literal(&property["default"])
+            ));
+        }
+    }
+    out
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    MissingField(String),
+    UnknownField(String),
+    WrongType {
+        field: String,
+        expected: &'static str,
+    },
+    OutOfRange {
+        field: String,
+        value: i64,
+        range: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::MissingField(field) => write!(f, "missing required field `{}`", field),
+            ValidationError::UnknownField(field) => write!(f, "unknown field `{}`", field),
+            ValidationError::WrongType { field, expected } => {
+                write!(f, "field `{}` must be of type {}", field, expected)
+            }
+            ValidationError::OutOfRange {
+                field,
+                value,
+                range,
+            } => write!(
+                f,
+                "field `{}` is {}, which is outside the allowed range {}",
+                field, value, range
+            ),
+        }
+    }
+}
+
+// Checks a parsed TOML table against the schema and reports every problem at once
+pub fn validate(schema: &Value, table: &toml::value::Table) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for key in table.keys() {
+        if schema["properties"].get(key).is_none() {
+            errors.push(ValidationError::UnknownField(key.clone()));
+        }
+    }
+
+    for (name, property) in properties(schema) {
+        let value = match table.get(name) {
+            Some(value) => value,
+            None => {
+                if is_required(schema, name) {
+                    errors.push(ValidationError::MissingField(name.clone()));
+                }
+                continue;
+            }
+        };
+
+        match property["type"].as_str() {
+            Some("string") if !value.is_str() => {
+                errors.push(ValidationError::WrongType {
+                    field: name.clone(),
+                    expected: "string",
+                });
+            }
+            Some("integer") => {
+                let number = match value.as_integer() {
+                    Some(number) => number,
+                    None => {
+                        errors.push(ValidationError::WrongType {
+                            field: name.clone(),
+                            expected: "integer",
+                        });
+                        continue;
+                    }
+                };
+                let below = property["minimum"]
+                    .as_i64()
+                    .map(|min| number < min)
+                    .unwrap_or(false);
+                let above = property["maximum"]
+                    .as_i64()
+                    .map(|max| number > max)
+                    .unwrap_or(false);
+                if below || above {
+                    errors.push(ValidationError::OutOfRange {
+                        field: name.clone(),
+                        value: number,
+                        range: range_text(property),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    errors
+}
@@ -0,0 +1,517 @@
+use crate::pool::{PoolConfig, PoolError, PoolMetrics, Slot, WaitHistogram};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// AsyncManager: the async counterpart of `Manager`; creation and health checks may await I/O,
+// while recycle and destroy stay synchronous because they run from `Drop`
+pub trait AsyncManager<T>: Send + Sync {
+    fn create(&self) -> BoxFuture<'_, Result<T, PoolError>>;
+
+    fn validate<'a>(&'a self, _resource: &'a T) -> BoxFuture<'a, bool> {
+        Box::pin(async { true })
+    }
+
+    fn recycle(&self, _resource: &mut T) -> bool {
+        true
+    }
+
+    fn destroy(&self, resource: T) {
+        drop(resource);
+    }
+}
+
+struct AsyncPoolState<T> {
+    idle: VecDeque<Slot<T>>,
+    total: usize, // idle + checked out + being created
+    in_use: usize,
+    waiters: usize,
+    created: u64,
+    destroyed: u64,
+    timeouts: u64,
+    wait_times: WaitHistogram,
+}
+
+// Every resource that is checked out or being created holds one permit. Tokio's semaphore
+// queues acquirers in FIFO order, which gives the same fairness as the blocking pool
+struct AsyncShared<T> {
+    config: PoolConfig,
+    manager: Box<dyn AsyncManager<T>>,
+    state: Mutex<AsyncPoolState<T>>,
+    permits: Arc<Semaphore>,
+}
+
+// Undoes a counter change unless disarmed, so a future dropped at an `.await` never leaks a slot
+struct Rollback<T: Send + Sync + 'static> {
+    shared: Arc<AsyncShared<T>>,
+    undo: Option<fn(&mut AsyncPoolState<T>)>,
+}
+
+impl<T: Send + Sync + 'static> Rollback<T> {
+    fn new(
+        shared: &Arc<AsyncShared<T>>,
+        apply: fn(&mut AsyncPoolState<T>),
+        undo: fn(&mut AsyncPoolState<T>),
+    ) -> Self {
+        apply(&mut shared.state.lock().unwrap());
+        Rollback {
+            shared: shared.clone(),
+            undo: Some(undo),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.undo = None;
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for Rollback<T> {
+    fn drop(&mut self) {
+        if let Some(undo) = self.undo.take() {
+            undo(&mut self.shared.state.lock().unwrap());
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> AsyncShared<T> {
+    fn retire(&self, state: &mut AsyncPoolState<T>) {
+        state.total -= 1;
+        state.destroyed += 1;
+    }
+
+    // Opens one extra idle resource if a permit is free and the pool still has room
+    async fn add_resource(self: Arc<Self>) -> bool {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => return false,
+        };
+        if self.state.lock().unwrap().total >= self.config.max_size {
+            return false;
+        }
+        let reservation = Rollback::new(&self, |s| s.total += 1, |s| s.total -= 1);
+        match self.manager.create().await {
+            Ok(resource) => {
+                reservation.disarm();
+                let mut state = self.state.lock().unwrap();
+                state.created += 1;
+                state.idle.push_back(Slot::new(resource));
+                drop(state);
+                drop(permit);
+                true
+            }
+            Err(e) => {
+                println!("Could not create replacement resource: {}", e);
+                false
+            }
+        }
+    }
+
+    fn spawn_replacement(self: &Arc<Self>) {
+        if let Ok(handle) = Handle::try_current() {
+            handle.spawn(self.clone().add_resource());
+        }
+    }
+
+    fn release(self: &Arc<Self>, mut slot: Slot<T>, permit: OwnedSemaphorePermit) {
+        let reusable = self.manager.recycle(&mut slot.resource);
+        if reusable && self.config.test_on_return {
+            if let Ok(handle) = Handle::try_current() {
+                let shared = self.clone();
+                handle.spawn(async move {
+                    let valid = shared.manager.validate(&slot.resource).await;
+                    shared.finish_release(slot, valid, permit);
+                });
+                return;
+            }
+        }
+        self.finish_release(slot, reusable, permit);
+    }
+
+    // The resource goes back to the idle queue before the permit is released, so the
+    // next waiter woken by the semaphore finds it there
+    fn finish_release(
+        self: &Arc<Self>,
+        mut slot: Slot<T>,
+        reusable: bool,
+        permit: OwnedSemaphorePermit,
+    ) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= 1;
+        if reusable && !slot.outlived(&self.config, now) {
+            slot.idle_since = now;
+            state.idle.push_back(slot);
+            drop(state);
+            drop(permit);
+            return;
+        }
+        self.retire(&mut state);
+        drop(state);
+        drop(permit);
+        self.manager.destroy(slot.resource);
+        self.spawn_replacement();
+    }
+
+    // Retires resources past their max lifetime, trims idle resources above `min_size`,
+    // then tops the pool back up to `min_size`
+    async fn run_maintenance(self: Arc<Self>) {
+        let now = Instant::now();
+        let mut retired = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            let mut kept = VecDeque::new();
+            while let Some(slot) = state.idle.pop_front() {
+                let expired = slot.outlived(&self.config, now)
+                    || (state.total > self.config.min_size && slot.idled_out(&self.config, now));
+                if expired {
+                    self.retire(&mut state);
+                    retired.push(slot.resource);
+                } else {
+                    kept.push_back(slot);
+                }
+            }
+            state.idle = kept;
+        }
+        for resource in retired {
+            self.manager.destroy(resource);
+        }
+
+        while self.state.lock().unwrap().total < self.config.min_size {
+            if !self.clone().add_resource().await {
+                break;
+            }
+        }
+    }
+}
+
+pub struct AsyncPool<T: Send + Sync + 'static> {
+    shared: Arc<AsyncShared<T>>,
+}
+
+// Cloning shares the same pool, so handles can be moved into spawned tasks
+impl<T: Send + Sync + 'static> Clone for AsyncPool<T> {
+    fn clone(&self) -> Self {
+        AsyncPool {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+// Handed out by `get`; owns its permit, so it can be moved into spawned tasks and
+// gives the resource back to the pool when dropped there
+pub struct AsyncPooled<T: Send + Sync + 'static> {
+    shared: Arc<AsyncShared<T>>,
+    slot: Option<Slot<T>>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<T: Send + Sync + 'static> Deref for AsyncPooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.slot.as_ref().unwrap().resource
+    }
+}
+
+impl<T: Send + Sync + 'static> DerefMut for AsyncPooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.slot.as_mut().unwrap().resource
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for AsyncPooled<T> {
+    fn drop(&mut self) {
+        if let (Some(slot), Some(permit)) = (self.slot.take(), self.permit.take()) {
+            self.shared.release(slot, permit);
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> AsyncPool<T> {
+    // Must be called from within a tokio runtime, which also runs the maintenance task
+    pub async fn with_config(
+        config: PoolConfig,
+        manager: Box<dyn AsyncManager<T>>,
+    ) -> AsyncPool<T> {
+        assert!(
+            config.min_size <= config.max_size && config.max_size > 0,
+            "pool needs 0 <= min_size <= max_size and max_size > 0"
+        );
+        let mut idle = VecDeque::new();
+        for _ in 0..config.min_size {
+            match manager.create().await {
+                Ok(resource) => idle.push_back(Slot::new(resource)),
+                Err(e) => println!("Could not pre-create resource: {}", e),
+            }
+        }
+        let shared = Arc::new(AsyncShared {
+            state: Mutex::new(AsyncPoolState {
+                total: idle.len(),
+                in_use: 0,
+                waiters: 0,
+                created: idle.len() as u64,
+                idle,
+                destroyed: 0,
+                timeouts: 0,
+                wait_times: WaitHistogram::default(),
+            }),
+            permits: Arc::new(Semaphore::new(config.max_size)),
+            config,
+            manager,
+        });
+        AsyncPool::spawn_maintenance(Arc::downgrade(&shared));
+        AsyncPool { shared }
+    }
+
+    // The maintenance task only holds a weak reference and stops once the pool is dropped
+    fn spawn_maintenance(shared: Weak<AsyncShared<T>>) {
+        tokio::spawn(async move {
+            loop {
+                let interval = match shared.upgrade() {
+                    Some(shared) => shared.config.maintenance_interval,
+                    None => return,
+                };
+                tokio::time::sleep(interval).await;
+                match shared.upgrade() {
+                    Some(shared) => shared.run_maintenance().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    pub async fn get(&self) -> Result<AsyncPooled<T>, PoolError> {
+        self.get_timeout(self.shared.config.checkout_timeout).await
+    }
+
+    // Cancellation safe: dropping this future at any `.await` (including via the timeout)
+    // releases whatever permit, reservation or resource it was holding
+    pub async fn get_timeout(&self, timeout: Duration) -> Result<AsyncPooled<T>, PoolError> {
+        let started = Instant::now();
+        match tokio::time::timeout(timeout, self.checkout(started)).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.shared.state.lock().unwrap().timeouts += 1;
+                Err(PoolError::Timeout(timeout))
+            }
+        }
+    }
+
+    async fn checkout(&self, started: Instant) -> Result<AsyncPooled<T>, PoolError> {
+        let shared = &self.shared;
+        let waiting = Rollback::new(shared, |s| s.waiters += 1, |s| s.waiters -= 1);
+        let mut permit = shared
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pool semaphore is never closed");
+        drop(waiting);
+
+        loop {
+            let (slot, retired) = {
+                let mut state = shared.state.lock().unwrap();
+                match state.idle.pop_front() {
+                    Some(slot) if slot.outlived(&shared.config, Instant::now()) => {
+                        shared.retire(&mut state);
+                        (None, Some(slot))
+                    }
+                    Some(slot) => {
+                        state.in_use += 1;
+                        (Some(slot), None)
+                    }
+                    None => (None, None),
+                }
+            };
+            if let Some(slot) = retired {
+                shared.manager.destroy(slot.resource);
+                continue;
+            }
+
+            if let Some(slot) = slot {
+                // Wrapped before validating so that cancellation hands it straight back
+                let mut pooled = self.wrap(slot, permit);
+                if !shared.config.test_on_checkout || shared.manager.validate(&*pooled).await {
+                    shared
+                        .state
+                        .lock()
+                        .unwrap()
+                        .wait_times
+                        .record(started.elapsed());
+                    return Ok(pooled);
+                }
+                let slot = pooled.slot.take().unwrap();
+                permit = pooled.permit.take().unwrap();
+                {
+                    let mut state = shared.state.lock().unwrap();
+                    state.in_use -= 1;
+                    shared.retire(&mut state);
+                }
+                shared.manager.destroy(slot.resource);
+                continue;
+            }
+
+            let reservation = Rollback::new(shared, |s| s.total += 1, |s| s.total -= 1);
+            let resource = shared.manager.create().await?;
+            reservation.disarm();
+            {
+                let mut state = shared.state.lock().unwrap();
+                state.created += 1;
+                state.in_use += 1;
+                state.wait_times.record(started.elapsed());
+            }
+            return Ok(self.wrap(Slot::new(resource), permit));
+        }
+    }
+
+    fn wrap(&self, slot: Slot<T>, permit: OwnedSemaphorePermit) -> AsyncPooled<T> {
+        AsyncPooled {
+            shared: self.shared.clone(),
+            slot: Some(slot),
+            permit: Some(permit),
+        }
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let state = self.shared.state.lock().unwrap();
+        PoolMetrics {
+            in_use: state.in_use,
+            idle: state.idle.len(),
+            waiters: state.waiters,
+            total_created: state.created,
+            total_destroyed: state.destroyed,
+            timeouts: state.timeouts,
+            wait_times: state.wait_times.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Opening a connection takes `connect_delay`, like a slow network handshake
+    struct SlowManager {
+        connect_delay: Duration,
+        next_id: AtomicUsize,
+    }
+
+    impl AsyncManager<usize> for SlowManager {
+        fn create(&self) -> BoxFuture<'_, Result<usize, PoolError>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.connect_delay).await;
+                Ok(self.next_id.fetch_add(1, Ordering::SeqCst))
+            })
+        }
+    }
+
+    const MAX_SIZE: usize = 2;
+
+    async fn pool(connect_delay: Duration) -> AsyncPool<usize> {
+        let config = PoolConfig {
+            min_size: 0,
+            max_size: MAX_SIZE,
+            maintenance_interval: Duration::from_secs(3600),
+            ..PoolConfig::default()
+        };
+        let manager = SlowManager {
+            connect_delay,
+            next_id: AtomicUsize::new(0),
+        };
+        AsyncPool::with_config(config, Box::new(manager)).await
+    }
+
+    fn free_permits(pool: &AsyncPool<usize>) -> usize {
+        pool.shared.permits.available_permits()
+    }
+
+    fn total(pool: &AsyncPool<usize>) -> usize {
+        pool.shared.state.lock().unwrap().total
+    }
+
+    #[tokio::test]
+    async fn checkout_that_times_out_leaks_nothing() {
+        let pool = pool(Duration::ZERO).await;
+        let held: Vec<_> = vec![pool.get().await.unwrap(), pool.get().await.unwrap()];
+
+        let result = pool.get_timeout(Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(PoolError::Timeout(_))));
+        let metrics = pool.metrics();
+        assert_eq!(metrics.timeouts, 1);
+        assert_eq!(metrics.waiters, 0);
+        assert_eq!(metrics.in_use, MAX_SIZE);
+        assert_eq!(free_permits(&pool), 0);
+
+        drop(held);
+        let metrics = pool.metrics();
+        assert_eq!(metrics.in_use, 0);
+        assert_eq!(metrics.idle, MAX_SIZE);
+        assert_eq!(free_permits(&pool), MAX_SIZE);
+    }
+
+    #[tokio::test]
+    async fn dropped_waiter_leaves_the_queue() {
+        let pool = pool(Duration::ZERO).await;
+        let held: Vec<_> = vec![pool.get().await.unwrap(), pool.get().await.unwrap()];
+
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.get().await.map(|conn| *conn) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.metrics().waiters, 1);
+
+        waiter.abort();
+        assert!(waiter.await.unwrap_err().is_cancelled());
+        assert_eq!(pool.metrics().waiters, 0);
+
+        // The permits freed here must not be handed to the dropped waiter
+        drop(held);
+        assert_eq!(free_permits(&pool), MAX_SIZE);
+        let conn = pool.get_timeout(Duration::from_millis(20)).await.unwrap();
+        assert_eq!(free_permits(&pool), MAX_SIZE - 1);
+        drop(conn);
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.in_use, 0);
+        assert_eq!(metrics.idle, MAX_SIZE);
+        assert_eq!(metrics.timeouts, 0);
+    }
+
+    #[tokio::test]
+    async fn future_dropped_mid_create_releases_its_reservation() {
+        let pool = pool(Duration::from_millis(200)).await;
+
+        let checkout = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.get().await.map(|conn| *conn) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(total(&pool), 1);
+        assert_eq!(free_permits(&pool), MAX_SIZE - 1);
+
+        checkout.abort();
+        assert!(checkout.await.unwrap_err().is_cancelled());
+        assert_eq!(total(&pool), 0);
+        assert_eq!(free_permits(&pool), MAX_SIZE);
+        let metrics = pool.metrics();
+        assert_eq!(metrics.in_use, 0);
+        assert_eq!(metrics.idle, 0);
+        assert_eq!(metrics.total_created, 0);
+
+        // The same holds when the timeout is what drops it
+        let result = pool.get_timeout(Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(PoolError::Timeout(_))));
+        assert_eq!(total(&pool), 0);
+        assert_eq!(free_permits(&pool), MAX_SIZE);
+        assert_eq!(pool.metrics().timeouts, 1);
+    }
+}
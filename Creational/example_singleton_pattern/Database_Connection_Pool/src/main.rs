@@ -1,15 +1,129 @@
-use std::collections::VecDeque;
+mod async_pool;
+mod pool;
+
+use async_pool::{AsyncManager, AsyncPool, BoxFuture};
+use pool::{Manager, Pool, PoolConfig, PoolError};
+use std::ptr::addr_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::sync::Once;
-use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 struct DbConnection {
     // Mock structure for a database connection
+    id: usize,
+    database_up: Arc<AtomicBool>,
+    broken: AtomicBool,
+}
+
+impl DbConnection {
+    fn query(&self, sql: &str) {
+        println!("[conn {}] executing: {}", self.id, sql);
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        if !self.database_up.load(Ordering::SeqCst) {
+            return Err("database is unreachable".to_string());
+        }
+        if self.is_broken() {
+            return Err(format!("connection {} is broken", self.id));
+        }
+        Ok(())
+    }
+
+    // Marks the connection as failed, as a driver would after a socket error mid-query
+    fn simulate_failure(&self) {
+        self.broken.store(true, Ordering::SeqCst);
+    }
+
+    fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::SeqCst)
+    }
+}
+
+// Fake database whose availability can be switched off to make connections fail
+struct MockConnectionManager {
+    next_id: AtomicUsize,
+    database_up: Arc<AtomicBool>,
+    connect_delay: Duration,
+}
+
+impl MockConnectionManager {
+    fn new() -> Self {
+        MockConnectionManager {
+            next_id: AtomicUsize::new(1),
+            database_up: Arc::new(AtomicBool::new(true)),
+            connect_delay: Duration::ZERO,
+        }
+    }
+
+    // Simulates a slow network handshake when opening connections
+    fn with_connect_delay(mut self, delay: Duration) -> Self {
+        self.connect_delay = delay;
+        self
+    }
+
+    fn open(&self) -> Result<DbConnection, PoolError> {
+        if !self.database_up.load(Ordering::SeqCst) {
+            return Err(PoolError::CreateFailed("connection refused".to_string()));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        println!("Opening connection {}", id);
+        Ok(DbConnection {
+            id,
+            database_up: self.database_up.clone(),
+            broken: AtomicBool::new(false),
+        })
+    }
+
+    fn database_switch(&self) -> Arc<AtomicBool> {
+        self.database_up.clone()
+    }
+}
+
+impl Manager<DbConnection> for MockConnectionManager {
+    fn create(&self) -> Result<DbConnection, PoolError> {
+        thread::sleep(self.connect_delay);
+        self.open()
+    }
+
+    fn validate(&self, conn: &DbConnection) -> bool {
+        conn.ping().is_ok()
+    }
+
+    fn recycle(&self, conn: &mut DbConnection) -> bool {
+        !conn.is_broken()
+    }
+
+    fn destroy(&self, conn: DbConnection) {
+        println!("Closing connection {}", conn.id);
+    }
 }
 
-struct ConnectionPool {
-    connections: Mutex<VecDeque<DbConnection>>,
+impl AsyncManager<DbConnection> for MockConnectionManager {
+    fn create(&self) -> BoxFuture<'_, Result<DbConnection, PoolError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.connect_delay).await;
+            self.open()
+        })
+    }
+
+    fn validate<'a>(&'a self, conn: &'a DbConnection) -> BoxFuture<'a, bool> {
+        Box::pin(async move { conn.ping().is_ok() })
+    }
+
+    fn recycle(&self, conn: &mut DbConnection) -> bool {
+        !conn.is_broken()
+    }
+
+    fn destroy(&self, conn: DbConnection) {
+        println!("Closing connection {}", conn.id);
+    }
 }
 
+type ConnectionPool = Pool<DbConnection>;
+
 impl ConnectionPool {
     fn new() -> Arc<ConnectionPool> {
         static mut SINGLETON: Option<Arc<ConnectionPool>> = None;
@@ -17,44 +131,206 @@ impl ConnectionPool {
 
         unsafe {
             ONCE.call_once(|| {
-                let pool = ConnectionPool {
-                    connections: Mutex::new(VecDeque::new()),
-                };
+                let pool = ConnectionPool::with_config(
+                    PoolConfig::default(),
+                    Box::new(MockConnectionManager::new()),
+                );
                 SINGLETON = Some(Arc::new(pool));
             });
-            SINGLETON.clone().unwrap()
+            (*addr_of!(SINGLETON)).clone().unwrap()
         }
     }
+}
 
-    fn get_connection(&self) -> Option<DbConnection> {
-        let mut connections = self.connections.lock().unwrap();
-        connections.pop_front()
-    }
+// Pooling scratch buffers: returned buffers are cleared, oversized ones are dropped
+struct BufferManager {
+    capacity: usize,
+}
 
-    fn return_connection(&self, conn: DbConnection) {
-        let mut connections = self.connections.lock().unwrap();
-        connections.push_back(conn);
+impl Manager<Vec<u8>> for BufferManager {
+    fn create(&self) -> Result<Vec<u8>, PoolError> {
+        Ok(Vec::with_capacity(self.capacity))
     }
 
-    fn add_connection(&self, conn: DbConnection) {
-        let mut connections = self.connections.lock().unwrap();
-        connections.push_back(conn);
+    fn recycle(&self, buffer: &mut Vec<u8>) -> bool {
+        buffer.clear();
+        buffer.capacity() <= self.capacity * 4
     }
 }
 
 fn main() {
     let pool = ConnectionPool::new();
 
-    // Simulate adding connectionis to the pool
-    pool.add_connection(DbConnection {});
-    pool.add_connection(DbConnection {});
+    // Simulate getting a connection from the pool; it goes back automatically at end of scope
+    match pool.get() {
+        Ok(conn) => {
+            println!("Got a connection from the pool");
+            conn.query("SELECT 1");
+        }
+        Err(e) => println!("No available connections: {}", e),
+    }
+
+    // A small bounded pool shared by more workers than it has connections
+    let small_pool = Arc::new(ConnectionPool::with_config(
+        PoolConfig {
+            min_size: 0,
+            max_size: 2,
+            checkout_timeout: Duration::from_millis(250),
+            ..PoolConfig::default()
+        },
+        Box::new(MockConnectionManager::new()),
+    ));
+
+    let workers: Vec<_> = (0..4)
+        .map(|worker| {
+            let pool = small_pool.clone();
+            thread::spawn(move || match pool.get() {
+                Ok(conn) => {
+                    conn.query(&format!("SELECT * FROM jobs WHERE worker = {}", worker));
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => println!("Worker {} gave up: {}", worker, e),
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    // Hold every connection so the next caller times out
+    let _first = small_pool.get().unwrap();
+    let _second = small_pool.get().unwrap();
+    match small_pool.get_timeout(Duration::from_millis(50)) {
+        Ok(_) => println!("Unexpectedly got a third connection"),
+        Err(e) => println!("Third checkout failed: {}", e),
+    }
+    println!("Small pool: {}", small_pool.metrics());
+
+    // Health checks, idle eviction and max lifetime against a database we can switch off
+    let manager = MockConnectionManager::new();
+    let database_up = manager.database_switch();
+    let healthy_pool = ConnectionPool::with_config(
+        PoolConfig {
+            min_size: 1,
+            max_size: 3,
+            checkout_timeout: Duration::from_millis(100),
+            idle_timeout: Some(Duration::from_millis(200)),
+            max_lifetime: Some(Duration::from_millis(600)),
+            maintenance_interval: Duration::from_millis(50),
+            ..PoolConfig::default()
+        },
+        Box::new(manager),
+    );
+
+    // A connection that breaks while in use is discarded and replaced in the background
+    {
+        let conn = healthy_pool.get().unwrap();
+        conn.query("UPDATE accounts SET balance = balance - 10");
+        conn.simulate_failure();
+    }
+    thread::sleep(Duration::from_millis(20));
+    println!("After broken return: {}", healthy_pool.metrics());
+
+    // While the database is down, checkout validation rejects idle connections
+    database_up.store(false, Ordering::SeqCst);
+    match healthy_pool.get() {
+        Ok(_) => println!("Unexpectedly got a connection while the database is down"),
+        Err(e) => println!("Checkout while database is down: {}", e),
+    }
+    database_up.store(true, Ordering::SeqCst);
+
+    // Grow to max_size, then let the extra connections idle out down to min_size
+    {
+        let _a = healthy_pool.get().unwrap();
+        let _b = healthy_pool.get().unwrap();
+        let _c = healthy_pool.get().unwrap();
+        println!("During burst: {}", healthy_pool.metrics());
+    }
+    thread::sleep(Duration::from_millis(350));
+    println!("After idle timeout: {}", healthy_pool.metrics());
+
+    // The survivor is recycled once it reaches its max lifetime
+    thread::sleep(Duration::from_millis(600));
+    println!("After max lifetime: {}", healthy_pool.metrics());
+    drop(healthy_pool);
+
+    // The same pool works for any expensive resource, e.g. reusable byte buffers
+    let buffers = Pool::with_config(
+        PoolConfig {
+            min_size: 2,
+            max_size: 4,
+            ..PoolConfig::default()
+        },
+        Box::new(BufferManager { capacity: 1024 }),
+    );
+    {
+        let mut small = buffers.get().unwrap();
+        small.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let mut huge = buffers.get().unwrap();
+        huge.resize(64 * 1024, 0);
+    }
+    thread::sleep(Duration::from_millis(20));
+    println!("Buffer pool: {}", buffers.metrics());
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async_pool_demo());
+}
+
+async fn async_pool_demo() {
+    let pool = AsyncPool::with_config(
+        PoolConfig {
+            min_size: 0,
+            max_size: 2,
+            checkout_timeout: Duration::from_millis(500),
+            ..PoolConfig::default()
+        },
+        Box::new(MockConnectionManager::new().with_connect_delay(Duration::from_millis(100))),
+    )
+    .await;
+
+    // Guards move into spawned tasks and return their connection when the task drops them
+    let tasks: Vec<_> = (0..4)
+        .map(|task| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                match pool.get().await {
+                    Ok(conn) => {
+                        conn.query(&format!("SELECT * FROM events WHERE task = {}", task));
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    Err(e) => println!("Task {} gave up: {}", task, e),
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    println!("Async pool after tasks: {}", pool.metrics());
+
+    // A waiter cancelled while queued never takes a slot
+    let held = pool.get().await.unwrap();
+    let _also_held = pool.get().await.unwrap();
+    let cancelled = tokio::time::timeout(Duration::from_millis(20), pool.get()).await;
+    println!("Queued checkout cancelled: {}", cancelled.is_err());
+    drop(held);
 
-    // Simulate getting a connection from the pool
-    if let Some(conn) = pool.get_connection() {
-        println!("Got a connection from the pool");
-        // Use the connection...
-        pool.return_connection(conn);
-    } else {
-        println!("No available connections");
+    // Cancelled in the middle of a slow connect: the reserved slot is freed again
+    let fresh_pool = AsyncPool::with_config(
+        PoolConfig {
+            min_size: 0,
+            max_size: 1,
+            ..PoolConfig::default()
+        },
+        Box::new(MockConnectionManager::new().with_connect_delay(Duration::from_millis(100))),
+    )
+    .await;
+    let cancelled = tokio::time::timeout(Duration::from_millis(30), fresh_pool.get()).await;
+    println!("Slow connect cancelled: {}", cancelled.is_err());
+    match fresh_pool.get_timeout(Duration::from_millis(300)).await {
+        Ok(conn) => conn.query("SELECT 'slot was not leaked'"),
+        Err(e) => println!("Slot leaked: {}", e),
     }
+    println!("Fresh pool: {}", fresh_pool.metrics());
 }
@@ -0,0 +1,701 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum PoolError {
+    Timeout(Duration),
+    CreateFailed(String),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolError::Timeout(waited) => {
+                write!(f, "timed out after {:?} waiting for a resource", waited)
+            }
+            PoolError::CreateFailed(reason) => write!(f, "failed to create resource: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+// Manager: lifecycle hooks the pool calls for the resources it owns
+pub trait Manager<T>: Send + Sync {
+    fn create(&self) -> Result<T, PoolError>;
+
+    // Health check run on checkout (and on return when `test_on_return` is set)
+    fn validate(&self, _resource: &T) -> bool {
+        true
+    }
+
+    // Resets a returned resource for its next user; returning false discards it instead
+    fn recycle(&self, _resource: &mut T) -> bool {
+        true
+    }
+
+    fn destroy(&self, resource: T) {
+        drop(resource);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub min_size: usize,
+    pub max_size: usize,
+    pub checkout_timeout: Duration,
+    pub test_on_checkout: bool,
+    pub test_on_return: bool,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    pub maintenance_interval: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            min_size: 1,
+            max_size: 10,
+            checkout_timeout: Duration::from_secs(30),
+            test_on_checkout: true,
+            test_on_return: false,
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            maintenance_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+const WAIT_BUCKETS_MS: [u64; 7] = [1, 5, 10, 50, 100, 500, 1000];
+
+// Counts checkouts by how long they waited; the last bucket catches everything slower
+#[derive(Debug, Clone, Default)]
+pub struct WaitHistogram {
+    counts: [u64; WAIT_BUCKETS_MS.len() + 1],
+}
+
+impl WaitHistogram {
+    pub fn record(&mut self, waited: Duration) {
+        let millis = waited.as_millis() as u64;
+        let bucket = WAIT_BUCKETS_MS
+            .iter()
+            .position(|bound| millis < *bound)
+            .unwrap_or(WAIT_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+    }
+}
+
+impl fmt::Display for WaitHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buckets: Vec<String> = WAIT_BUCKETS_MS
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(bound, count)| format!("<{}ms: {}", bound, count))
+            .collect();
+        buckets.push(format!(
+            ">={}ms: {}",
+            WAIT_BUCKETS_MS[WAIT_BUCKETS_MS.len() - 1],
+            self.counts[WAIT_BUCKETS_MS.len()]
+        ));
+        write!(f, "{}", buckets.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolMetrics {
+    pub in_use: usize,
+    pub idle: usize,
+    pub waiters: usize,
+    pub total_created: u64,
+    pub total_destroyed: u64,
+    pub timeouts: u64,
+    pub wait_times: WaitHistogram,
+}
+
+impl fmt::Display for PoolMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "in use: {}, idle: {}, waiters: {}, created: {}, destroyed: {}, timeouts: {}\n  wait times: {}",
+            self.in_use,
+            self.idle,
+            self.waiters,
+            self.total_created,
+            self.total_destroyed,
+            self.timeouts,
+            self.wait_times
+        )
+    }
+}
+
+// A resource owned by the pool, together with the timestamps used for eviction
+pub struct Slot<T> {
+    pub resource: T,
+    pub created_at: Instant,
+    pub idle_since: Instant,
+}
+
+impl<T> Slot<T> {
+    pub fn new(resource: T) -> Self {
+        let now = Instant::now();
+        Slot {
+            resource,
+            created_at: now,
+            idle_since: now,
+        }
+    }
+
+    pub fn outlived(&self, config: &PoolConfig, now: Instant) -> bool {
+        config
+            .max_lifetime
+            .map(|max| now.duration_since(self.created_at) >= max)
+            .unwrap_or(false)
+    }
+
+    pub fn idled_out(&self, config: &PoolConfig, now: Instant) -> bool {
+        config
+            .idle_timeout
+            .map(|max| now.duration_since(self.idle_since) >= max)
+            .unwrap_or(false)
+    }
+}
+
+struct PoolState<T> {
+    idle: VecDeque<Slot<T>>,
+    total: usize, // idle + checked out + being created
+    in_use: usize,
+    waiters: VecDeque<u64>,
+    next_ticket: u64,
+    created: u64,
+    destroyed: u64,
+    timeouts: u64,
+    wait_times: WaitHistogram,
+}
+
+// A caller's place in the checkout queue. If `get_timeout` unwinds (say `Manager::validate`
+// panics) while the ticket is still queued, dropping this gives up the place, so the callers
+// behind it are not stuck waiting on a head that never leaves, and retires the resource that
+// was being validated
+struct Waiter<'a, T: Send + 'static> {
+    shared: &'a Shared<T>,
+    ticket: u64,
+    queued: bool,
+    validating: bool,
+}
+
+impl<T: Send + 'static> Drop for Waiter<'_, T> {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        state.waiters.retain(|t| *t != self.ticket);
+        if self.validating {
+            self.shared.retire(&mut state);
+        }
+        self.shared.available.notify_all();
+    }
+}
+
+// Everything the pool shares with its background maintenance and replacement threads
+struct Shared<T> {
+    config: PoolConfig,
+    manager: Box<dyn Manager<T>>,
+    state: Mutex<PoolState<T>>,
+    available: Condvar,
+}
+
+impl<T: Send + 'static> Shared<T> {
+    // Bookkeeping only; callers hand the resource to `Manager::destroy` once the lock is released
+    fn retire(&self, state: &mut PoolState<T>) {
+        state.total -= 1;
+        state.destroyed += 1;
+        self.available.notify_all();
+    }
+
+    // Opens one extra idle resource if the pool still has room
+    fn add_resource(&self) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.total >= self.config.max_size {
+                return false;
+            }
+            state.total += 1;
+        }
+        let created = self.manager.create();
+        let mut state = self.state.lock().unwrap();
+        self.available.notify_all();
+        match created {
+            Ok(resource) => {
+                state.created += 1;
+                state.idle.push_back(Slot::new(resource));
+                true
+            }
+            Err(e) => {
+                state.total -= 1;
+                println!("Could not create replacement resource: {}", e);
+                false
+            }
+        }
+    }
+
+    fn spawn_replacement(self: &Arc<Self>) {
+        let shared = self.clone();
+        thread::spawn(move || {
+            shared.add_resource();
+        });
+    }
+
+    fn release(self: &Arc<Self>, mut slot: Slot<T>) {
+        let reusable = self.manager.recycle(&mut slot.resource)
+            && (!self.config.test_on_return || self.manager.validate(&slot.resource));
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= 1;
+        if reusable && !slot.outlived(&self.config, now) {
+            slot.idle_since = now;
+            state.idle.push_back(slot);
+            self.available.notify_all();
+            return;
+        }
+        self.retire(&mut state);
+        drop(state);
+        self.manager.destroy(slot.resource);
+        self.spawn_replacement();
+    }
+
+    // Retires resources past their max lifetime, trims idle resources above `min_size`,
+    // then tops the pool back up to `min_size`
+    fn run_maintenance(&self) {
+        let now = Instant::now();
+        let mut retired = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            let mut kept = VecDeque::new();
+            while let Some(slot) = state.idle.pop_front() {
+                let expired = slot.outlived(&self.config, now)
+                    || (state.total > self.config.min_size && slot.idled_out(&self.config, now));
+                if expired {
+                    self.retire(&mut state);
+                    retired.push(slot.resource);
+                } else {
+                    kept.push_back(slot);
+                }
+            }
+            state.idle = kept;
+        }
+        for resource in retired {
+            self.manager.destroy(resource);
+        }
+
+        while self.state.lock().unwrap().total < self.config.min_size {
+            if !self.add_resource() {
+                break;
+            }
+        }
+    }
+}
+
+pub struct Pool<T: Send + 'static> {
+    shared: Arc<Shared<T>>,
+}
+
+// Handed out by `get`; gives the resource back to the pool when dropped
+pub struct Pooled<'a, T: Send + 'static> {
+    pool: &'a Pool<T>,
+    slot: Option<Slot<T>>,
+}
+
+impl<T: Send + 'static> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.slot.as_ref().unwrap().resource
+    }
+}
+
+impl<T: Send + 'static> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.slot.as_mut().unwrap().resource
+    }
+}
+
+impl<T: Send + 'static> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            self.pool.shared.release(slot);
+        }
+    }
+}
+
+impl<T: Send + 'static> Pool<T> {
+    pub fn with_config(config: PoolConfig, manager: Box<dyn Manager<T>>) -> Pool<T> {
+        assert!(
+            config.min_size <= config.max_size && config.max_size > 0,
+            "pool needs 0 <= min_size <= max_size and max_size > 0"
+        );
+        let mut idle = VecDeque::new();
+        for _ in 0..config.min_size {
+            match manager.create() {
+                Ok(resource) => idle.push_back(Slot::new(resource)),
+                Err(e) => println!("Could not pre-create resource: {}", e),
+            }
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(PoolState {
+                total: idle.len(),
+                in_use: 0,
+                created: idle.len() as u64,
+                idle,
+                waiters: VecDeque::new(),
+                next_ticket: 0,
+                destroyed: 0,
+                timeouts: 0,
+                wait_times: WaitHistogram::default(),
+            }),
+            config,
+            manager,
+            available: Condvar::new(),
+        });
+        Pool::spawn_maintenance(Arc::downgrade(&shared));
+        Pool { shared }
+    }
+
+    // The maintenance thread only holds a weak reference and stops once the pool is dropped
+    fn spawn_maintenance(shared: Weak<Shared<T>>) {
+        thread::spawn(move || loop {
+            let interval = match shared.upgrade() {
+                Some(shared) => shared.config.maintenance_interval,
+                None => return,
+            };
+            thread::sleep(interval);
+            match shared.upgrade() {
+                Some(shared) => shared.run_maintenance(),
+                None => return,
+            }
+        });
+    }
+
+    pub fn get(&self) -> Result<Pooled<'_, T>, PoolError> {
+        self.get_timeout(self.shared.config.checkout_timeout)
+    }
+
+    // Waiters are served strictly in arrival order: only the head of the queue may take
+    // a resource, so a late caller can never overtake one that has been waiting longer
+    pub fn get_timeout(&self, timeout: Duration) -> Result<Pooled<'_, T>, PoolError> {
+        let shared = &self.shared;
+        let config = &shared.config;
+        let started = Instant::now();
+        // A timeout too large to add up, such as `Duration::MAX`, waits for as long as it takes
+        let deadline = started.checked_add(timeout);
+        let ticket = {
+            let mut state = shared.state.lock().unwrap();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiters.push_back(ticket);
+            ticket
+        };
+        let mut waiter = Waiter {
+            shared,
+            ticket,
+            queued: true,
+            validating: false,
+        };
+        let mut state = shared.state.lock().unwrap();
+
+        loop {
+            let at_front = state.waiters.front() == Some(&ticket);
+            if at_front {
+                if let Some(slot) = state.idle.pop_front() {
+                    if slot.outlived(config, Instant::now()) {
+                        shared.retire(&mut state);
+                        drop(state);
+                        shared.manager.destroy(slot.resource);
+                        state = shared.state.lock().unwrap();
+                        continue;
+                    }
+                    // Validate without holding the lock; staying at the head of the queue
+                    // keeps everyone else waiting behind us meanwhile
+                    if config.test_on_checkout {
+                        drop(state);
+                        waiter.validating = true;
+                        let valid = shared.manager.validate(&slot.resource);
+                        waiter.validating = false;
+                        if !valid {
+                            shared.manager.destroy(slot.resource);
+                            state = shared.state.lock().unwrap();
+                            shared.retire(&mut state);
+                            continue;
+                        }
+                        state = shared.state.lock().unwrap();
+                    }
+                    state.waiters.pop_front();
+                    waiter.queued = false;
+                    state.in_use += 1;
+                    state.wait_times.record(started.elapsed());
+                    shared.available.notify_all();
+                    return Ok(self.wrap(slot));
+                }
+                if state.total < config.max_size {
+                    state.waiters.pop_front();
+                    waiter.queued = false;
+                    state.total += 1;
+                    shared.available.notify_all();
+                    drop(state);
+                    return self.create_resource(started);
+                }
+            }
+
+            let Some(deadline) = deadline else {
+                state = shared.available.wait(state).unwrap();
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                state.waiters.retain(|t| *t != ticket);
+                waiter.queued = false;
+                state.timeouts += 1;
+                shared.available.notify_all();
+                return Err(PoolError::Timeout(timeout));
+            }
+            state = shared
+                .available
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    // Called with a slot already reserved in `total`; the manager runs without the lock held
+    fn create_resource(&self, started: Instant) -> Result<Pooled<'_, T>, PoolError> {
+        let created = self.shared.manager.create();
+        let mut state = self.shared.state.lock().unwrap();
+        self.shared.available.notify_all();
+        match created {
+            Ok(resource) => {
+                state.created += 1;
+                state.in_use += 1;
+                state.wait_times.record(started.elapsed());
+                Ok(self.wrap(Slot::new(resource)))
+            }
+            Err(e) => {
+                state.total -= 1;
+                Err(e)
+            }
+        }
+    }
+
+    fn wrap(&self, slot: Slot<T>) -> Pooled<'_, T> {
+        Pooled {
+            pool: self,
+            slot: Some(slot),
+        }
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let state = self.shared.state.lock().unwrap();
+        PoolMetrics {
+            in_use: state.in_use,
+            idle: state.idle.len(),
+            waiters: state.waiters.len(),
+            total_created: state.created,
+            total_destroyed: state.destroyed,
+            timeouts: state.timeouts,
+            wait_times: state.wait_times.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct FakeConn {
+        id: usize,
+        broken: Arc<AtomicBool>,
+    }
+
+    // Hands out connections whose health the test controls through `break_connection`
+    #[derive(Default)]
+    struct FakeManager {
+        next_id: AtomicUsize,
+        handles: Mutex<Vec<Arc<AtomicBool>>>,
+        // Makes the next health check panic
+        crash_validate: AtomicBool,
+    }
+
+    impl FakeManager {
+        fn break_connection(&self, id: usize) {
+            self.handles.lock().unwrap()[id].store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl Manager<FakeConn> for Arc<FakeManager> {
+        fn create(&self) -> Result<FakeConn, PoolError> {
+            let broken = Arc::new(AtomicBool::new(false));
+            self.handles.lock().unwrap().push(broken.clone());
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            Ok(FakeConn { id, broken })
+        }
+
+        fn validate(&self, conn: &FakeConn) -> bool {
+            if self.crash_validate.swap(false, Ordering::SeqCst) {
+                panic!("health check crashed");
+            }
+            !conn.broken.load(Ordering::SeqCst)
+        }
+
+        fn recycle(&self, conn: &mut FakeConn) -> bool {
+            !conn.broken.load(Ordering::SeqCst)
+        }
+    }
+
+    // Background maintenance is off unless a test turns it on
+    fn config() -> PoolConfig {
+        PoolConfig {
+            min_size: 1,
+            max_size: 3,
+            checkout_timeout: Duration::from_millis(200),
+            idle_timeout: None,
+            max_lifetime: None,
+            maintenance_interval: Duration::from_secs(3600),
+            ..PoolConfig::default()
+        }
+    }
+
+    fn pool(config: PoolConfig) -> (Pool<FakeConn>, Arc<FakeManager>) {
+        let manager = Arc::new(FakeManager::default());
+        (
+            Pool::with_config(config, Box::new(manager.clone())),
+            manager,
+        )
+    }
+
+    // Replacements and maintenance run on background threads
+    fn wait_until(pool: &Pool<FakeConn>, done: impl Fn(&PoolMetrics) -> bool) -> PoolMetrics {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            let metrics = pool.metrics();
+            if done(&metrics) || Instant::now() >= deadline {
+                return metrics;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn checkout_validation_discards_broken_connection() {
+        let (pool, manager) = pool(config());
+        manager.break_connection(0);
+
+        let conn = pool.get().unwrap();
+        assert_eq!(conn.id, 1);
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.total_created, 2);
+        assert_eq!(metrics.total_destroyed, 1);
+        assert_eq!(metrics.in_use, 1);
+        assert_eq!(metrics.idle, 0);
+    }
+
+    #[test]
+    fn broken_connection_returned_by_caller_is_replaced() {
+        let (pool, manager) = pool(config());
+        let conn = pool.get().unwrap();
+        manager.break_connection(conn.id);
+        drop(conn);
+
+        let metrics = wait_until(&pool, |m| m.idle == 1);
+        assert_eq!(metrics.idle, 1);
+        assert_eq!(metrics.in_use, 0);
+        assert_eq!(metrics.total_created, 2);
+        assert_eq!(metrics.total_destroyed, 1);
+        assert_eq!(pool.get().unwrap().id, 1);
+    }
+
+    #[test]
+    fn idle_connections_above_min_size_are_evicted() {
+        let (pool, _) = pool(PoolConfig {
+            idle_timeout: Some(Duration::from_millis(20)),
+            maintenance_interval: Duration::from_millis(10),
+            ..config()
+        });
+        let held: Vec<_> = (0..3).map(|_| pool.get().unwrap()).collect();
+        drop(held);
+
+        let metrics = wait_until(&pool, |m| m.idle == 1);
+        assert_eq!(metrics.idle, 1);
+        assert_eq!(metrics.total_created, 3);
+        assert_eq!(metrics.total_destroyed, 2);
+    }
+
+    #[test]
+    fn connections_past_max_lifetime_are_recycled() {
+        let lifetime = Duration::from_millis(30);
+        let (pool, _) = pool(PoolConfig {
+            max_lifetime: Some(lifetime),
+            ..config()
+        });
+
+        // An idle connection that has outlived its lifetime is replaced at checkout
+        thread::sleep(lifetime);
+        let conn = pool.get().unwrap();
+        assert_eq!(conn.id, 1);
+        assert_eq!(pool.metrics().total_destroyed, 1);
+
+        // One that outlives it while checked out is closed when returned
+        thread::sleep(lifetime);
+        drop(conn);
+        let metrics = wait_until(&pool, |m| m.idle == 1);
+        assert_eq!(metrics.idle, 1);
+        assert_eq!(metrics.total_created, 3);
+        assert_eq!(metrics.total_destroyed, 2);
+    }
+
+    #[test]
+    fn an_unbounded_timeout_waits_until_a_connection_is_returned() {
+        let (pool, _) = pool(PoolConfig {
+            max_size: 1,
+            ..config()
+        });
+        assert_eq!(pool.get_timeout(Duration::MAX).unwrap().id, 0);
+
+        let held = pool.get().unwrap();
+        thread::scope(|scope| {
+            let waiting = scope.spawn(|| pool.get_timeout(Duration::MAX).map(|conn| conn.id));
+            wait_until(&pool, |m| m.waiters == 1);
+            drop(held);
+            assert_eq!(waiting.join().unwrap().unwrap(), 0);
+        });
+        assert_eq!(pool.metrics().timeouts, 0);
+    }
+
+    #[test]
+    fn a_panicking_health_check_gives_up_its_place_in_the_queue() {
+        let (pool, manager) = pool(PoolConfig {
+            max_size: 1,
+            ..config()
+        });
+        manager.crash_validate.store(true, Ordering::SeqCst);
+        let crashed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pool.get().map(|conn| conn.id)
+        }));
+        assert!(crashed.is_err());
+
+        // The connection being checked is gone, so its slot is free for a new one
+        let metrics = pool.metrics();
+        assert_eq!(metrics.waiters, 0);
+        assert_eq!(metrics.in_use, 0);
+        assert_eq!(metrics.total_destroyed, 1);
+        assert_eq!(pool.get().unwrap().id, 1);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{AccessError, AccessPolicy, AuditLog, Permission};
+use std::collections::HashMap;
+use std::ptr::addr_of;
+use std::sync::Once;
+use std::sync::{Arc, Mutex};
+
+// The singleton itself is private to this module, so the only way in is a `ConfigProxy`
+struct ConfigManager {
+    settings: Mutex<HashMap<String, String>>,
+}
+
+impl ConfigManager {
+    fn new() -> Arc<ConfigManager> {
+        static mut SINGLETON: Option<Arc<ConfigManager>> = None;
+        static ONCE: Once = Once::new();
+
+        unsafe {
+            ONCE.call_once(|| {
+                let mut settings = HashMap::new();
+                settings.insert("db_host".to_string(), "localhost".to_string());
+                settings.insert("db_port".to_string(), "5432".to_string());
+                settings.insert("api_key".to_string(), "123456".to_string());
+
+                let config = ConfigManager {
+                    settings: Mutex::new(settings),
+                };
+                SINGLETON = Some(Arc::new(config));
+            });
+
+            (*addr_of!(SINGLETON)).clone().unwrap()
+        }
+    }
+
+    fn get_setting(&self, key: &str) -> Option<String> {
+        let settings = self.settings.lock().unwrap();
+        settings.get(key).cloned()
+    }
+
+    fn set_setting(&self, key: &str, value: &str) {
+        let mut settings = self.settings.lock().unwrap();
+        settings.insert(key.to_string(), value.to_string());
+    }
+}
+
+// Proxy: guards the ConfigManager singleton on behalf of a single caller
+pub(crate) struct ConfigProxy {
+    caller: String,
+    config: Arc<ConfigManager>,
+    policy: Arc<AccessPolicy>,
+    audit: Arc<AuditLog>,
+}
+
+impl ConfigProxy {
+    pub(crate) fn new(caller: &str, policy: Arc<AccessPolicy>, audit: Arc<AuditLog>) -> Self {
+        ConfigProxy {
+            caller: caller.to_string(),
+            config: ConfigManager::new(),
+            policy,
+            audit,
+        }
+    }
+
+    pub(crate) fn get_setting(&self, key: &str) -> Result<String, AccessError> {
+        let allowed = self.policy.is_allowed(&self.caller, key, Permission::Read);
+        if self.policy.is_secret(key) {
+            self.audit
+                .record(&self.caller, Permission::Read, key, allowed);
+        }
+        if !allowed {
+            return Err(AccessError::ReadDenied {
+                caller: self.caller.clone(),
+                key: key.to_string(),
+            });
+        }
+        self.config
+            .get_setting(key)
+            .ok_or_else(|| AccessError::NotFound(key.to_string()))
+    }
+
+    pub(crate) fn set_setting(&self, key: &str, value: &str) -> Result<(), AccessError> {
+        let allowed = self.policy.is_allowed(&self.caller, key, Permission::Write);
+        self.audit
+            .record(&self.caller, Permission::Write, key, allowed);
+        if !allowed {
+            return Err(AccessError::WriteDenied {
+                caller: self.caller.clone(),
+                key: key.to_string(),
+            });
+        }
+        self.config.set_setting(key, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Grant;
+
+    // The singleton is shared by every test, so each test touches its own keys
+    fn proxy(caller: &str, policy: &Arc<AccessPolicy>, audit: &Arc<AuditLog>) -> ConfigProxy {
+        ConfigProxy::new(caller, policy.clone(), audit.clone())
+    }
+
+    fn audited(audit: &AuditLog) -> Vec<(String, Permission, String, bool)> {
+        let entries = audit.entries().into_iter();
+        entries
+            .map(|e| (e.caller, e.permission, e.key, e.allowed))
+            .collect()
+    }
+
+    #[test]
+    fn grants_cover_keys_by_prefix() {
+        let policy = Arc::new(
+            AccessPolicy::new()
+                .grant("admin", Grant::new("", &[Permission::Write]))
+                .grant("plugin", Grant::new("prefix_", &[Permission::Read])),
+        );
+        let audit = Arc::new(AuditLog::default());
+        let admin = proxy("admin", &policy, &audit);
+        admin.set_setting("prefix_a", "1").unwrap();
+        admin.set_setting("prefixb", "2").unwrap();
+
+        let plugin = proxy("plugin", &policy, &audit);
+        assert_eq!(plugin.get_setting("prefix_a"), Ok("1".to_string()));
+        assert!(plugin.get_setting("prefixb").is_err());
+        // Read-only: the grant does not extend to writes, even under its prefix
+        assert!(plugin.set_setting("prefix_a", "3").is_err());
+        // Write-only: `admin` cannot read back what it wrote
+        assert!(admin.get_setting("prefix_a").is_err());
+    }
+
+    #[test]
+    fn denied_access_returns_typed_errors() {
+        let policy = Arc::new(
+            AccessPolicy::new().grant("reader", Grant::new("errors_", &[Permission::Read])),
+        );
+        let audit = Arc::new(AuditLog::default());
+        let reader = proxy("reader", &policy, &audit);
+        assert_eq!(
+            reader.get_setting("db_host"),
+            Err(AccessError::ReadDenied {
+                caller: "reader".to_string(),
+                key: "db_host".to_string(),
+            })
+        );
+        assert_eq!(
+            reader.set_setting("errors_a", "1"),
+            Err(AccessError::WriteDenied {
+                caller: "reader".to_string(),
+                key: "errors_a".to_string(),
+            })
+        );
+        assert_eq!(
+            reader.get_setting("errors_missing"),
+            Err(AccessError::NotFound("errors_missing".to_string()))
+        );
+        // A caller with no grants at all
+        let stranger = proxy("stranger", &policy, &audit);
+        assert!(matches!(
+            stranger.get_setting("errors_missing"),
+            Err(AccessError::ReadDenied { .. })
+        ));
+    }
+
+    #[test]
+    fn writes_are_audited_whether_allowed_or_not() {
+        let policy = Arc::new(
+            AccessPolicy::new()
+                .grant("writer", Grant::new("audit_", &[Permission::Write]))
+                .grant("reader", Grant::new("audit_", &[Permission::Read])),
+        );
+        let audit = Arc::new(AuditLog::default());
+        proxy("writer", &policy, &audit)
+            .set_setting("audit_a", "1")
+            .unwrap();
+        let reader = proxy("reader", &policy, &audit);
+        assert!(reader.set_setting("audit_a", "2").is_err());
+        // Reads of ordinary keys are not audited
+        reader.get_setting("audit_a").unwrap();
+        assert_eq!(
+            audited(&audit),
+            [
+                (
+                    "writer".to_string(),
+                    Permission::Write,
+                    "audit_a".to_string(),
+                    true
+                ),
+                (
+                    "reader".to_string(),
+                    Permission::Write,
+                    "audit_a".to_string(),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn secret_reads_are_audited_whether_allowed_or_not() {
+        let policy = Arc::new(
+            AccessPolicy::new()
+                .secret("secret_token")
+                .grant("admin", Grant::new("secret_", &[Permission::Write]))
+                .grant("billing", Grant::new("secret_token", &[Permission::Read])),
+        );
+        let audit = Arc::new(AuditLog::default());
+        proxy("admin", &policy, &audit)
+            .set_setting("secret_token", "s3cret")
+            .unwrap();
+        assert_eq!(
+            proxy("billing", &policy, &audit).get_setting("secret_token"),
+            Ok("s3cret".to_string())
+        );
+        assert!(proxy("plugin", &policy, &audit)
+            .get_setting("secret_token")
+            .is_err());
+        assert_eq!(
+            audited(&audit)[1..],
+            [
+                (
+                    "billing".to_string(),
+                    Permission::Read,
+                    "secret_token".to_string(),
+                    true
+                ),
+                (
+                    "plugin".to_string(),
+                    Permission::Read,
+                    "secret_token".to_string(),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn proxies_share_the_singleton() {
+        let policy = Arc::new(AccessPolicy::new().grant(
+            "core",
+            Grant::new("shared_", &[Permission::Read, Permission::Write]),
+        ));
+        let audit = Arc::new(AuditLog::default());
+        proxy("core", &policy, &audit)
+            .set_setting("shared_a", "1")
+            .unwrap();
+        assert_eq!(
+            proxy("core", &policy, &audit).get_setting("shared_a"),
+            Ok("1".to_string())
+        );
+        assert!(Arc::ptr_eq(&ConfigManager::new(), &ConfigManager::new()));
+    }
+}
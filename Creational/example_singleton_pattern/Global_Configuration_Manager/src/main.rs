@@ -1,57 +1,195 @@
-use std::collections::HashMap;
-use std::sync::Once;
+mod config;
+
+use config::ConfigProxy;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Permission {
+    Read,
+    Write,
+}
 
-struct ConfigManager {
-    settings: Mutex<HashMap<String, String>>,
+// A grant gives a caller read and/or write access to every key starting with `key_prefix`
+#[derive(Debug, Clone)]
+struct Grant {
+    key_prefix: String,
+    permissions: Vec<Permission>,
 }
 
-impl ConfigManager {
-    fn new() -> Arc<ConfigManager> {
-        static mut SINGLETON: Option<Arc<ConfigManager>> = None;
-        static ONCE: Once = Once::new();
+impl Grant {
+    fn new(key_prefix: &str, permissions: &[Permission]) -> Self {
+        Grant {
+            key_prefix: key_prefix.to_string(),
+            permissions: permissions.to_vec(),
+        }
+    }
+
+    fn allows(&self, key: &str, permission: Permission) -> bool {
+        key.starts_with(&self.key_prefix) && self.permissions.contains(&permission)
+    }
+}
 
-        unsafe {
-            ONCE.call_once(|| {
-                let mut settings = HashMap::new();
-                settings.insert("db_host".to_string(), "localhost".to_string());
-                settings.insert("db_port".to_string(), "5432".to_string());
-                settings.insert("api_key".to_string(), "123456".to_string());
+// Who may touch which keys, and which keys hold secrets
+#[derive(Default)]
+struct AccessPolicy {
+    grants: HashMap<String, Vec<Grant>>, // caller -> grants
+    secret_keys: HashSet<String>,
+}
 
-                let config = ConfigManager {
-                    settings: Mutex::new(settings),
-                };
-                SINGLETON = Some(Arc::new(config));
-            });
+impl AccessPolicy {
+    fn new() -> Self {
+        AccessPolicy::default()
+    }
+
+    fn grant(mut self, caller: &str, grant: Grant) -> Self {
+        self.grants
+            .entry(caller.to_string())
+            .or_default()
+            .push(grant);
+        self
+    }
+
+    fn secret(mut self, key: &str) -> Self {
+        self.secret_keys.insert(key.to_string());
+        self
+    }
+
+    fn is_allowed(&self, caller: &str, key: &str, permission: Permission) -> bool {
+        self.grants
+            .get(caller)
+            .map(|grants| grants.iter().any(|g| g.allows(key, permission)))
+            .unwrap_or(false)
+    }
+
+    fn is_secret(&self, key: &str) -> bool {
+        self.secret_keys.contains(key)
+    }
+}
 
-            SINGLETON.clone().unwrap()
+#[derive(Debug, PartialEq, Eq)]
+enum AccessError {
+    ReadDenied { caller: String, key: String },
+    WriteDenied { caller: String, key: String },
+    NotFound(String),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessError::ReadDenied { caller, key } => {
+                write!(f, "'{}' is not allowed to read '{}'", caller, key)
+            }
+            AccessError::WriteDenied { caller, key } => {
+                write!(f, "'{}' is not allowed to write '{}'", caller, key)
+            }
+            AccessError::NotFound(key) => write!(f, "setting '{}' does not exist", key),
         }
     }
+}
 
-    fn get_setting(&self, key: &str) -> Option<String> {
-        let settings = self.settings.lock().unwrap();
-        settings.get(key).cloned()
+impl std::error::Error for AccessError {}
+
+#[derive(Debug, Clone)]
+struct AuditEntry {
+    timestamp: SystemTime,
+    caller: String,
+    permission: Permission,
+    key: String,
+    allowed: bool,
+}
+
+// Shared audit trail of every write and every secret read, including denied attempts
+#[derive(Default)]
+struct AuditLog {
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    fn record(&self, caller: &str, permission: Permission, key: &str, allowed: bool) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(AuditEntry {
+            timestamp: SystemTime::now(),
+            caller: caller.to_string(),
+            permission,
+            key: key.to_string(),
+            allowed,
+        });
     }
 
-    fn set_setting(&self, key: &str, value: &str) {
-        let mut settings = self.settings.lock().unwrap();
-        settings.insert(key.to_string(), value.to_string());
+    fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.lock().unwrap().clone()
     }
 }
 
 fn main() {
-    let config_manager = ConfigManager::new();
-    println!(
-        "DB Host: {}",
-        config_manager.get_setting("db_host").unwrap()
+    // The ConfigManager singleton is only reachable through a proxy, which exposes the slice
+    // of configuration each caller needs
+    let policy = Arc::new(
+        AccessPolicy::new()
+            .secret("api_key")
+            .grant(
+                "core",
+                Grant::new("", &[Permission::Read, Permission::Write]),
+            )
+            .grant("db_plugin", Grant::new("db_", &[Permission::Read]))
+            .grant("billing_plugin", Grant::new("api_key", &[Permission::Read])),
     );
+    let audit = Arc::new(AuditLog::default());
 
-    config_manager.set_setting("db_host", "127.0.0.1");
+    let core = ConfigProxy::new("core", policy.clone(), audit.clone());
+    let db_plugin = ConfigProxy::new("db_plugin", policy.clone(), audit.clone());
+    let billing_plugin = ConfigProxy::new("billing_plugin", policy.clone(), audit.clone());
+
+    // Every proxy shares the one ConfigManager
+    println!("DB Host: {}", core.get_setting("db_host").unwrap());
+    core.set_setting("db_host", "127.0.0.1").unwrap();
     println!(
-        "Updated DB Host: {}",
-        config_manager.get_setting("db_host").unwrap()
+        "Updated DB Host, as db_plugin sees it: {}",
+        db_plugin.get_setting("db_host").unwrap()
     );
 
-    let another = ConfigManager::new();
-    println!("another {}", another.get_setting("db_host").unwrap());
+    match db_plugin.get_setting("db_port") {
+        Ok(port) => println!("db_plugin read db_port: {}", port),
+        Err(e) => println!("Error: {}", e),
+    }
+    match db_plugin.get_setting("api_key") {
+        Ok(key) => println!("db_plugin read api_key: {}", key),
+        Err(e) => println!("Error: {}", e),
+    }
+    match db_plugin.set_setting("db_host", "10.0.0.1") {
+        Ok(()) => println!("db_plugin updated db_host"),
+        Err(e) => println!("Error: {}", e),
+    }
+    match billing_plugin.get_setting("api_key") {
+        Ok(key) => println!("billing_plugin read api_key: {}", key),
+        Err(e) => println!("Error: {}", e),
+    }
+    match core.set_setting("db_host", "db.internal") {
+        Ok(()) => println!("core updated db_host"),
+        Err(e) => println!("Error: {}", e),
+    }
+    match core.get_setting("cache_size") {
+        Ok(size) => println!("core read cache_size: {}", size),
+        Err(e) => println!("Error: {}", e),
+    }
+
+    println!("Audit trail:");
+    for entry in audit.entries() {
+        let since_epoch = entry
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        println!(
+            "  [{}.{:03}] {} {:?} {} -> {}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            entry.caller,
+            entry.permission,
+            entry.key,
+            if entry.allowed { "allowed" } else { "denied" }
+        );
+    }
 }
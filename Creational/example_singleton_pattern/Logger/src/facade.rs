@@ -0,0 +1,336 @@
+use crate::format::Record;
+use crate::level::Level;
+use crate::Logger;
+use std::fmt;
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record as SpanRecord};
+use tracing::subscriber::{Interest, SetGlobalDefaultError};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+// Adapter: lets crates that log through the `log` facade write to our Logger. The target
+// (usually the module path, e.g. `hyper::client`) is used as the module, so the Logger's
+// filter decides for dependencies exactly like for our own messages
+pub struct LogBridge {
+    logger: Arc<Logger>,
+}
+
+pub fn install_log(logger: Arc<Logger>) -> Result<(), log::SetLoggerError> {
+    log::set_boxed_logger(Box::new(LogBridge { logger }))?;
+    // Let every record through the facade; the Logger's own filter is the only one
+    log::set_max_level(log::LevelFilter::Trace);
+    Ok(())
+}
+
+fn from_log(level: log::Level) -> Level {
+    match level {
+        log::Level::Trace => Level::Trace,
+        log::Level::Debug => Level::Debug,
+        log::Level::Info => Level::Info,
+        log::Level::Warn => Level::Warn,
+        log::Level::Error => Level::Error,
+    }
+}
+
+struct KeyValues(Vec<(String, String)>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KeyValues {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.logger
+            .enabled(from_log(metadata.level()), metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut fields = KeyValues(Vec::new());
+        let _ = record.key_values().visit(&mut fields);
+        self.logger.submit(Record::new(
+            from_log(record.level()),
+            record.target(),
+            &record.args().to_string(),
+            fields.0,
+        ));
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
+
+// Adapter: a `tracing_subscriber` layer that writes events to our Logger. Each event carries
+// the path of the spans it happened in (`span=request:query`) and their fields, prefixed with
+// the span name (`request.id=7`)
+pub struct LoggerLayer {
+    logger: Arc<Logger>,
+}
+
+impl LoggerLayer {
+    pub fn new(logger: Arc<Logger>) -> Self {
+        LoggerLayer { logger }
+    }
+}
+
+pub fn install_tracing(logger: Arc<Logger>) -> Result<(), SetGlobalDefaultError> {
+    let subscriber = tracing_subscriber::registry().with(LoggerLayer::new(logger));
+    tracing::subscriber::set_global_default(subscriber)
+}
+
+fn from_tracing(level: &tracing::Level) -> Level {
+    match *level {
+        tracing::Level::TRACE => Level::Trace,
+        tracing::Level::DEBUG => Level::Debug,
+        tracing::Level::INFO => Level::Info,
+        tracing::Level::WARN => Level::Warn,
+        tracing::Level::ERROR => Level::Error,
+    }
+}
+
+// Collects field values as text; the event's `message` field is kept apart
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    values: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn push(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            self.values.retain(|(key, _)| key != field.name());
+            self.values.push((field.name().to_string(), value));
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{:?}", value));
+    }
+}
+
+impl<S> Layer<S> for LoggerLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    // The filter can change at runtime, so tracing must not cache our answer per callsite
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    // Spans are always recorded, so their fields are available to the events inside them
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.is_span()
+            || self
+                .logger
+                .enabled(from_tracing(metadata.level()), metadata.target())
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &SpanRecord<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut own = Fields::default();
+        event.record(&mut own);
+
+        let mut path = Vec::new();
+        let mut fields = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                path.push(span.name());
+                if let Some(span_fields) = span.extensions().get::<Fields>() {
+                    for (key, value) in &span_fields.values {
+                        fields.push((format!("{}.{}", span.name(), key), value.clone()));
+                    }
+                }
+            }
+        }
+        if !path.is_empty() {
+            fields.insert(0, ("span".to_string(), path.join(":")));
+        }
+        fields.extend(own.values);
+
+        self.logger.submit(Record::new(
+            from_tracing(metadata.level()),
+            metadata.target(),
+            own.message.as_deref().unwrap_or_default(),
+            fields,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::HumanFormat;
+    use crate::sink::MemorySink;
+    use crate::writer::{BackgroundWriter, WriterConfig};
+    use std::sync::{Mutex, OnceLock, RwLock};
+
+    // A Logger of its own, outside the singleton, writing to `memory`
+    fn logger(filter: &str, memory: &MemorySink) -> Arc<Logger> {
+        Arc::new(Logger {
+            writer: BackgroundWriter::start(
+                vec![Box::new(memory.clone())],
+                Box::new(HumanFormat),
+                WriterConfig::default(),
+            ),
+            filter: RwLock::new(filter.parse().unwrap()),
+            stopped: Mutex::new(None),
+        })
+    }
+
+    // The `log` facade takes a single logger per process, so every test shares this one
+    fn log_output() -> &'static MemorySink {
+        static MEMORY: OnceLock<MemorySink> = OnceLock::new();
+        MEMORY.get_or_init(|| {
+            let memory = MemorySink::new(100);
+            install_log(logger("info,facade::noisy=warn", &memory)).unwrap();
+            memory
+        })
+    }
+
+    fn line_with(memory: &MemorySink, text: &str) -> Option<String> {
+        memory.lines().into_iter().find(|line| line.contains(text))
+    }
+
+    #[test]
+    fn log_records_reach_the_logger_with_their_key_values() {
+        let memory = log_output();
+        log::info!(target: "facade::client", retries = 3, peer = "db-1"; "connection reset");
+        log::logger().flush();
+
+        let line = line_with(memory, "connection reset").unwrap();
+        assert!(line.contains(" INFO "), "{}", line);
+        assert!(
+            line.ends_with("facade::client: connection reset retries=3 peer=db-1"),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn log_records_go_through_the_logger_filter() {
+        let memory = log_output();
+        log::info!(target: "facade::noisy", "below the module level");
+        log::warn!(target: "facade::noisy", "at the module level");
+        log::logger().flush();
+
+        assert!(line_with(memory, "below the module level").is_none());
+        assert!(line_with(memory, "at the module level").is_some());
+    }
+
+    #[test]
+    fn tracing_events_carry_their_span_path_and_fields() {
+        let memory = MemorySink::new(10);
+        let logger = logger("info,app::db=debug", &memory);
+        let subscriber = tracing_subscriber::registry().with(LoggerLayer::new(Arc::clone(&logger)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", id = 7, path = "/users/42");
+            request.in_scope(|| {
+                tracing::info!(target: "app::handler", user = 42, "loading user");
+                tracing::debug_span!("query", table = "users").in_scope(|| {
+                    tracing::debug!(target: "app::db", rows = 1, "query finished");
+                });
+                tracing::debug!(target: "app::handler", "below the handler level");
+            });
+        });
+        logger.flush();
+
+        let lines = memory.lines();
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert!(
+            lines[0].ends_with(
+                "app::handler: loading user span=request request.id=7 \
+                 request.path=/users/42 user=42"
+            ),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].ends_with(
+                "app::db: query finished span=request:query request.id=7 \
+                 request.path=/users/42 query.table=users rows=1"
+            ),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn span_fields_recorded_later_reach_the_events_after_them() {
+        let memory = MemorySink::new(10);
+        let logger = logger("info", &memory);
+        let subscriber = tracing_subscriber::registry().with(LoggerLayer::new(Arc::clone(&logger)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("job", status = tracing::field::Empty);
+            let _entered = span.enter();
+            tracing::info!("started");
+            span.record("status", "done");
+            tracing::info!("finished");
+        });
+        logger.flush();
+
+        let lines = memory.lines();
+        assert!(lines[0].ends_with("started span=job"), "{}", lines[0]);
+        assert!(
+            lines[1].ends_with("finished span=job job.status=done"),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn tracing_follows_filter_changes_at_runtime() {
+        let memory = MemorySink::new(10);
+        let logger = logger("info", &memory);
+        let subscriber = tracing_subscriber::registry().with(LoggerLayer::new(Arc::clone(&logger)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for attempt in 0..2 {
+                tracing::debug!(target: "app", attempt, "retrying");
+                logger.set_filter("debug").unwrap();
+            }
+        });
+        logger.flush();
+
+        let lines = memory.lines();
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        assert!(lines[0].ends_with("retrying attempt=1"), "{}", lines[0]);
+    }
+}
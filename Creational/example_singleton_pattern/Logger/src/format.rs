@@ -0,0 +1,140 @@
+use crate::level::Level;
+use std::fmt::Write;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Everything known about one log call, captured on the calling thread
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub level: Level,
+    pub module: String,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+    pub timestamp: SystemTime,
+    pub thread: String,
+}
+
+impl Record {
+    pub fn new(level: Level, module: &str, message: &str, fields: Vec<(String, String)>) -> Self {
+        let current = thread::current();
+        let thread = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+        Record {
+            level,
+            module: module.to_string(),
+            message: message.to_string(),
+            fields,
+            timestamp: SystemTime::now(),
+            thread,
+        }
+    }
+}
+
+// Strategy: turns a record into one line of output (without the trailing newline)
+pub trait Format: Send + Sync {
+    fn format(&self, record: &Record) -> String;
+}
+
+// 2026-10-19T08:30:00.123Z INFO  [main] db: connected host=localhost port=5432
+pub struct HumanFormat;
+
+impl Format for HumanFormat {
+    fn format(&self, record: &Record) -> String {
+        let mut line = format!(
+            "{} {:<5} [{}] {}: {}",
+            rfc3339(record.timestamp),
+            record.level,
+            record.thread,
+            record.module,
+            record.message
+        );
+        for (key, value) in &record.fields {
+            if value.contains(' ') || value.is_empty() {
+                write!(line, " {}={:?}", key, value).unwrap();
+            } else {
+                write!(line, " {}={}", key, value).unwrap();
+            }
+        }
+        line
+    }
+}
+
+// One JSON object per line, ready for log shippers
+pub struct JsonFormat;
+
+impl Format for JsonFormat {
+    fn format(&self, record: &Record) -> String {
+        let mut line = format!(
+            "{{\"ts\":\"{}\",\"level\":\"{}\",\"thread\":{},\"module\":{},\"msg\":{}",
+            rfc3339(record.timestamp),
+            record.level.as_str(),
+            json_string(&record.thread),
+            json_string(&record.module),
+            json_string(&record.message)
+        );
+        if !record.fields.is_empty() {
+            line.push_str(",\"fields\":{");
+            for (i, (key, value)) in record.fields.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                write!(line, "{}:{}", json_string(key), json_string(value)).unwrap();
+            }
+            line.push('}');
+        }
+        line.push('}');
+        line
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// UTC timestamp with millisecond precision, e.g. 2026-10-19T08:30:00.123Z
+pub fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's `civil_from_days`
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
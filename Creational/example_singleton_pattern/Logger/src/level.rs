@@ -0,0 +1,143 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+// `Off` disables a module entirely; any other value is the minimum level that gets through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelFilter {
+    Off,
+    Level(Level),
+}
+
+impl LevelFilter {
+    pub fn allows(&self, level: Level) -> bool {
+        match self {
+            LevelFilter::Off => false,
+            LevelFilter::Level(min) => level >= *min,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseFilterError(String);
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid log filter: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+impl FromStr for LevelFilter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(LevelFilter::Off),
+            "trace" => Ok(LevelFilter::Level(Level::Trace)),
+            "debug" => Ok(LevelFilter::Level(Level::Debug)),
+            "info" => Ok(LevelFilter::Level(Level::Info)),
+            "warn" => Ok(LevelFilter::Level(Level::Warn)),
+            "error" => Ok(LevelFilter::Level(Level::Error)),
+            other => Err(ParseFilterError(format!("unknown level `{}`", other))),
+        }
+    }
+}
+
+// Default level plus per-module overrides, parsed from strings like "info,db=debug,http=off".
+// A directive applies to its module and every submodule (`db` also covers `db::pool`);
+// the most specific directive wins
+#[derive(Debug, Clone)]
+pub struct Filter {
+    default: LevelFilter,
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn new(default: LevelFilter) -> Self {
+        Filter {
+            default,
+            directives: Vec::new(),
+        }
+    }
+
+    pub fn set_default(&mut self, level: LevelFilter) {
+        self.default = level;
+    }
+
+    pub fn module(mut self, module: &str, level: LevelFilter) -> Self {
+        self.directives.retain(|(m, _)| m != module);
+        self.directives.push((module.to_string(), level));
+        // Longest module path first, so the first match is the most specific one
+        self.directives
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        self
+    }
+
+    pub fn level_for(&self, module: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|(prefix, _)| {
+                module == prefix
+                    || (module.starts_with(prefix.as_str())
+                        && module[prefix.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    pub fn enabled(&self, level: Level, module: &str) -> bool {
+        self.level_for(module).allows(level)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = Filter::new(LevelFilter::Level(Level::Info));
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let module = module.trim();
+                    if module.is_empty() {
+                        return Err(ParseFilterError(format!(
+                            "missing module name in `{}`",
+                            directive
+                        )));
+                    }
+                    filter = filter.module(module, level.parse()?);
+                }
+                None => filter.default = directive.parse()?,
+            }
+        }
+        Ok(filter)
+    }
+}
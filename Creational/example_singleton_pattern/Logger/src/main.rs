@@ -1,38 +1,215 @@
-use std::fs::OpenOptions;
-use std::io::Write;
+mod facade;
+mod format;
+mod level;
+mod rotation;
+mod sink;
+mod writer;
+
+use format::{Format, HumanFormat, JsonFormat, Record};
+use level::{Filter, Level, LevelFilter, ParseFilterError};
+use rotation::{Naming, RotationConfig, Trigger};
+use sink::{ConsoleSink, FileSink, MemorySink, Sink, Stream, SyslogUdpSink};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::ptr::addr_of;
 use std::sync::Once;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+use writer::{write_to_sinks, BackgroundWriter, Output, OverflowPolicy, WriterConfig};
 
 struct Logger {
-    file: Mutex<std::fs::File>,
+    writer: BackgroundWriter,
+    filter: RwLock<Filter>,
+    // Sinks and format handed back by the writer thread after `shutdown`; later records are
+    // written synchronously instead of being lost
+    stopped: Mutex<Option<Output>>,
 }
 
 impl Logger {
+    // Starts from `LOG` (e.g. "info,db=debug"), `LOG_FORMAT` ("human" or "json"),
+    // `LOG_ROTATE` ("never", "hourly", "daily" or "size:<bytes>")
+    // and `LOG_OVERFLOW` ("block", "drop-newest" or "drop-oldest")
     fn new() -> Arc<Logger> {
         static mut SINGLETON: Option<Arc<Logger>> = None;
         static ONCE: Once = Once::new();
 
         unsafe {
             ONCE.call_once(|| {
-                let file = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .append(true)
-                    .open("log.txt")
-                    .unwrap();
+                let rotation = RotationConfig {
+                    trigger: env::var("LOG_ROTATE")
+                        .ok()
+                        .and_then(|trigger| trigger.parse().ok())
+                        .unwrap_or(Trigger::Never),
+                    ..RotationConfig::default()
+                };
+                let file = FileSink::new("log.txt", rotation).unwrap();
+                let filter = env::var("LOG")
+                    .ok()
+                    .and_then(|spec| spec.parse().ok())
+                    .unwrap_or_else(|| Filter::new(LevelFilter::Level(Level::Info)));
+                let format: Box<dyn Format> = match env::var("LOG_FORMAT").as_deref() {
+                    Ok("json") => Box::new(JsonFormat),
+                    _ => Box::new(HumanFormat),
+                };
+                let overflow = match env::var("LOG_OVERFLOW").as_deref() {
+                    Ok("drop-newest") => OverflowPolicy::DropNewest,
+                    Ok("drop-oldest") => OverflowPolicy::DropOldest,
+                    _ => OverflowPolicy::Block,
+                };
+                let writer = BackgroundWriter::start(
+                    vec![Box::new(file)],
+                    format,
+                    WriterConfig {
+                        overflow,
+                        ..WriterConfig::default()
+                    },
+                );
                 let logger = Logger {
-                    file: Mutex::new(file),
+                    writer,
+                    filter: RwLock::new(filter),
+                    stopped: Mutex::new(None),
                 };
                 SINGLETON = Some(Arc::new(logger));
             });
 
-            SINGLETON.clone().unwrap()
+            (*addr_of!(SINGLETON)).clone().unwrap()
+        }
+    }
+
+    fn set_filter(&self, spec: &str) -> Result<(), ParseFilterError> {
+        let filter = spec.parse()?;
+        *self.filter.write().unwrap() = filter;
+        Ok(())
+    }
+
+    fn set_level(&self, level: LevelFilter) {
+        self.filter.write().unwrap().set_default(level);
+    }
+
+    // Replaces every sink; records logged before this call still go to the old ones
+    fn set_sinks(&self, sinks: Vec<Box<dyn Sink>>) {
+        let mut stopped = self.stopped.lock().unwrap();
+        match stopped.as_mut() {
+            Some((current, _)) => *current = sinks,
+            None => {
+                self.writer.set_sinks(sinks);
+            }
+        }
+    }
+
+    fn add_sink(&self, sink: Box<dyn Sink>) {
+        let mut stopped = self.stopped.lock().unwrap();
+        match stopped.as_mut() {
+            Some((current, _)) => current.push(sink),
+            None => {
+                self.writer.add_sink(sink);
+            }
+        }
+    }
+
+    // Shortcut for logging to a single rotating file. Flushes first, so a pending rotation of
+    // the current file cannot rename it from under the new sink
+    fn set_output(&self, path: impl AsRef<Path>, rotation: RotationConfig) -> io::Result<()> {
+        self.flush();
+        self.set_sinks(vec![Box::new(FileSink::new(path, rotation)?)]);
+        Ok(())
+    }
+
+    fn set_overflow(&self, overflow: OverflowPolicy) {
+        self.writer.set_overflow(overflow);
+    }
+
+    // Returns once everything logged so far has reached the sinks
+    fn flush(&self) {
+        if let Some((sinks, _)) = self.stopped.lock().unwrap().as_mut() {
+            for sink in sinks.iter_mut() {
+                let _ = sink.flush();
+            }
+            return;
+        }
+        self.writer.flush();
+    }
+
+    // Delivers everything still queued and stops the writer thread
+    fn shutdown(&self) {
+        let mut stopped = self.stopped.lock().unwrap();
+        if let Some(output) = self.writer.shutdown() {
+            *stopped = Some(output);
+        }
+    }
+
+    fn dropped(&self) -> u64 {
+        self.writer.dropped()
+    }
+
+    // Like `set_sinks`: records logged before this call keep the old format
+    fn set_format(&self, format: Box<dyn Format>) {
+        let mut stopped = self.stopped.lock().unwrap();
+        match stopped.as_mut() {
+            Some((_, current)) => *current = format,
+            None => {
+                self.writer.set_format(format);
+            }
+        }
+    }
+
+    fn enabled(&self, level: Level, module: &str) -> bool {
+        self.filter.read().unwrap().enabled(level, module)
+    }
+
+    fn log_record(
+        &self,
+        level: Level,
+        module: &str,
+        message: &str,
+        fields: &[(&str, &dyn fmt::Display)],
+    ) {
+        if !self.enabled(level, module) {
+            return;
+        }
+        let fields = fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self.submit(Record::new(level, module, message, fields));
+    }
+
+    // Queues a record that already passed the filter
+    fn submit(&self, record: Record) {
+        if let Err(record) = self.writer.send(record) {
+            if let Some((sinks, format)) = self.stopped.lock().unwrap().as_mut() {
+                write_to_sinks(sinks, &**format, &record);
+            }
         }
     }
 
     fn log(&self, message: &str) {
-        let mut file = self.file.lock().unwrap();
-        writeln!(file, "{}", message).unwrap();
+        self.log_record(Level::Info, "app", message, &[]);
+    }
+
+    fn trace(&self, module: &str, message: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        self.log_record(Level::Trace, module, message, fields);
+    }
+
+    fn debug(&self, module: &str, message: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        self.log_record(Level::Debug, module, message, fields);
+    }
+
+    fn info(&self, module: &str, message: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        self.log_record(Level::Info, module, message, fields);
+    }
+
+    fn warn(&self, module: &str, message: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        self.log_record(Level::Warn, module, message, fields);
+    }
+
+    fn error(&self, module: &str, message: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        self.log_record(Level::Error, module, message, fields);
     }
 }
 
@@ -42,4 +219,179 @@ fn main() {
 
     let another_reference = Logger::new();
     another_reference.log("This is the second log message");
+
+    // Structured records with per-module levels
+    logger.set_filter("info,db=debug,http=warn").unwrap();
+    logger.debug("db", "opening pool", &[("size", &4)]);
+    logger.trace("db::pool", "dropped: trace is below debug", &[]);
+    logger.info(
+        "db::pool",
+        "connected",
+        &[("host", &"localhost"), ("port", &5432)],
+    );
+    logger.info("http", "dropped: http only logs warnings", &[]);
+    logger.warn(
+        "http",
+        "slow request",
+        &[("path", &"/orders"), ("ms", &1250)],
+    );
+
+    let worker = thread::Builder::new()
+        .name("worker-1".to_string())
+        .spawn(|| {
+            Logger::new().info("jobs", "job finished", &[("job_id", &42)]);
+        })
+        .unwrap();
+    worker.join().unwrap();
+
+    // Switch to JSON Lines and raise the global minimum level at runtime
+    logger.set_format(Box::new(JsonFormat));
+    logger.error(
+        "payments",
+        "charge failed",
+        &[("order", &"A-1001"), ("reason", &"card \"declined\"")],
+    );
+    logger.set_level(LevelFilter::Level(Level::Error));
+    logger.info("payments", "dropped: below the new minimum level", &[]);
+    logger.set_format(Box::new(HumanFormat));
+
+    if let Err(e) = logger.set_filter("info,db=loud") {
+        logger.error("app", "could not apply filter", &[("error", &e)]);
+    }
+
+    // Size-based rotation with gzip and a retention of three files, under concurrent writers
+    logger.set_level(LevelFilter::Level(Level::Info));
+    logger
+        .set_output(
+            "logs/app.log",
+            RotationConfig {
+                trigger: Trigger::Size(2048),
+                naming: Naming::Index,
+                compress: true,
+                max_files: Some(3),
+                max_age: Some(Duration::from_secs(7 * 24 * 3600)),
+            },
+        )
+        .unwrap();
+    let writers: Vec<_> = (0..4)
+        .map(|n| {
+            thread::spawn(move || {
+                let logger = Logger::new();
+                for i in 0..40 {
+                    logger.info("load", "request served", &[("writer", &n), ("i", &i)]);
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    // Daily rotation with timestamped names takes effect from the next write on
+    logger
+        .set_output(
+            "logs/app.log",
+            RotationConfig {
+                trigger: Trigger::Daily,
+                naming: Naming::Timestamp,
+                ..RotationConfig::default()
+            },
+        )
+        .unwrap();
+    logger.info("app", "switched to daily rotation", &[]);
+    logger.flush();
+
+    let mut files: Vec<_> = fs::read_dir("logs")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    println!("Log files: {}", files.join(", "));
+
+    // Fan out to stderr, an in-memory ring buffer and syslog over UDP
+    let syslog_server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    syslog_server
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let memory = MemorySink::new(16);
+    logger.set_sinks(vec![
+        Box::new(ConsoleSink::new(Stream::Stderr)),
+        Box::new(memory.clone()),
+        Box::new(SyslogUdpSink::new(syslog_server.local_addr().unwrap(), "logger-demo").unwrap()),
+    ]);
+    logger.add_sink(Box::new(
+        FileSink::new("log.txt", RotationConfig::default()).unwrap(),
+    ));
+    logger.warn("app", "disk almost full", &[("free_mb", &512)]);
+    logger.flush();
+    println!("Memory sink holds: {:?}", memory.lines());
+    let mut datagram = [0u8; 1024];
+    if let Ok(len) = syslog_server.recv(&mut datagram) {
+        println!(
+            "Syslog received: {}",
+            String::from_utf8_lossy(&datagram[..len])
+        );
+    }
+
+    // Dependencies logging through the `log` and `tracing` facades share the same filter
+    facade::install_log(Arc::clone(&logger)).unwrap();
+    facade::install_tracing(Arc::clone(&logger)).unwrap();
+    logger.set_filter("info,hyper=warn,app::db=debug").unwrap();
+    log::info!(target: "hyper::client", "dropped: hyper only logs warnings");
+    log::warn!(target: "hyper::client", retries = 3; "connection reset");
+    let request = tracing::info_span!("request", id = 7, path = "/users/42");
+    request.in_scope(|| {
+        tracing::info!(target: "app::handler", user = 42, "loading user");
+        tracing::debug_span!("query", table = "users").in_scope(|| {
+            tracing::debug!(target: "app::db", rows = 1, "query finished");
+        });
+        tracing::debug!(target: "app::handler", "dropped: app::handler logs info and above");
+    });
+    logger.flush();
+
+    // A burst into a small queue that keeps only the newest records
+    let burst = MemorySink::new(100);
+    let small = BackgroundWriter::start(
+        vec![Box::new(SlowSink(burst.clone()))],
+        Box::new(HumanFormat),
+        WriterConfig {
+            capacity: 8,
+            overflow: OverflowPolicy::DropOldest,
+        },
+    );
+    for i in 0..50 {
+        let _ = small.send(Record::new(
+            Level::Info,
+            "burst",
+            "event",
+            vec![("i".to_string(), i.to_string())],
+        ));
+    }
+    small.flush();
+    let kept = burst.lines();
+    println!(
+        "Burst of 50: {} written, {} dropped, last: {}",
+        kept.len(),
+        small.dropped(),
+        kept.last().map(String::as_str).unwrap_or("-")
+    );
+
+    // Everything queued is delivered before the writer thread stops; later records are
+    // written synchronously
+    logger.set_overflow(OverflowPolicy::Block);
+    logger.info("app", "shutting down", &[("dropped", &logger.dropped())]);
+    logger.shutdown();
+    logger.add_sink(Box::new(ConsoleSink::new(Stream::Stdout)));
+    logger.info("app", "written after shutdown", &[]);
+    logger.flush();
+}
+
+// Simulates a sink that cannot keep up, e.g. a remote collector
+struct SlowSink(MemorySink);
+
+impl Sink for SlowSink {
+    fn write(&mut self, record: &Record, line: &str) -> io::Result<()> {
+        thread::sleep(Duration::from_millis(2));
+        self.0.write(record, line)
+    }
 }
@@ -0,0 +1,533 @@
+use crate::format::civil_from_days;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Never,
+    Size(u64),
+    Hourly,
+    Daily,
+}
+
+// Parsed from "never", "hourly", "daily" or "size:<bytes>"
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "never" => Ok(Trigger::Never),
+            "hourly" => Ok(Trigger::Hourly),
+            "daily" => Ok(Trigger::Daily),
+            other => other
+                .strip_prefix("size:")
+                .and_then(|bytes| bytes.parse().ok())
+                .map(Trigger::Size)
+                .ok_or_else(|| format!("unknown rotation trigger `{}`", other)),
+        }
+    }
+}
+
+// How rotated files are named: `log.txt.1`, `log.txt.2`, ... (newest is 1)
+// or `log.txt.2026-10-19T08-00-00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    Index,
+    Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationConfig {
+    pub trigger: Trigger,
+    pub naming: Naming,
+    pub compress: bool,
+    pub max_files: Option<usize>,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        RotationConfig {
+            trigger: Trigger::Never,
+            naming: Naming::Index,
+            compress: false,
+            max_files: None,
+            max_age: None,
+        }
+    }
+}
+
+// Appends lines to `path` and rotates it according to `RotationConfig`. It has no locking of
+// its own: it lives in a `FileSink` owned by the single log writer thread, so a rotation can
+// never interleave with a line being written
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    config: RotationConfig,
+    file: File,
+    size: u64,
+    period: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(path: impl AsRef<Path>, config: RotationConfig) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        let period = period_of(config.trigger, SystemTime::now());
+        // A file left over from an earlier hour or day is rotated before anything is appended,
+        // so it never mixes with lines from the current period
+        let stale = size > 0 && period_of(config.trigger, metadata.modified()?) != period;
+        let mut writer = RotatingFileWriter {
+            path,
+            config,
+            file,
+            size,
+            period,
+        };
+        if stale {
+            writer.rotate()?;
+        }
+        Ok(writer)
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.should_rotate(len) {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn should_rotate(&self, incoming: u64) -> bool {
+        match self.config.trigger {
+            Trigger::Never => false,
+            // Never rotate an empty file, even if a single line exceeds the threshold
+            Trigger::Size(max) => self.size > 0 && self.size + incoming > max,
+            Trigger::Hourly | Trigger::Daily => {
+                period_of(self.config.trigger, SystemTime::now()) != self.period
+            }
+        }
+    }
+
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let rotated = match self.config.naming {
+            Naming::Index => {
+                self.shift_indexed()?;
+                self.sibling(&format!("{}.1", self.file_name()))
+            }
+            Naming::Timestamp => self.timestamped_name(),
+        };
+        fs::rename(&self.path, &rotated)?;
+        if self.config.compress {
+            compress(&rotated)?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.period = period_of(self.config.trigger, SystemTime::now());
+        self.apply_retention()
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn sibling(&self, name: &str) -> PathBuf {
+        self.path.with_file_name(name)
+    }
+
+    // Rotated files that belong to this log, newest first
+    fn rotated_files(&self) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+        let prefix = format!("{}.", self.file_name());
+        let dir = match self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                files.push((entry.path(), entry.metadata()?.modified()?));
+            }
+        }
+        match self.config.naming {
+            Naming::Index => files.sort_by_key(|(path, _)| self.index_of(path)),
+            Naming::Timestamp => files.sort_by(|a, b| b.0.cmp(&a.0)),
+        }
+        Ok(files)
+    }
+
+    fn index_of(&self, path: &Path) -> u64 {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        name[self.file_name().len() + 1..]
+            .trim_end_matches(".gz")
+            .parse()
+            .unwrap_or(u64::MAX)
+    }
+
+    // log.txt.2 -> log.txt.3, log.txt.1 -> log.txt.2, keeping any .gz extension
+    fn shift_indexed(&self) -> io::Result<()> {
+        let mut files = self.rotated_files()?;
+        files.reverse();
+        for (path, _) in files {
+            let index = self.index_of(&path);
+            if index == u64::MAX {
+                continue;
+            }
+            let gz = if path.to_string_lossy().ends_with(".gz") {
+                ".gz"
+            } else {
+                ""
+            };
+            let target = self.sibling(&format!("{}.{}{}", self.file_name(), index + 1, gz));
+            fs::rename(&path, target)?;
+        }
+        Ok(())
+    }
+
+    fn timestamped_name(&self) -> PathBuf {
+        let base = format!("{}.{}", self.file_name(), file_stamp(SystemTime::now()));
+        let mut candidate = self.sibling(&base);
+        let mut n = 1;
+        while candidate.exists() || gz_path(&candidate).exists() {
+            candidate = self.sibling(&format!("{}-{}", base, n));
+            n += 1;
+        }
+        candidate
+    }
+
+    fn apply_retention(&self) -> io::Result<()> {
+        let now = SystemTime::now();
+        for (i, (path, modified)) in self.rotated_files()?.into_iter().enumerate() {
+            let too_many = self.config.max_files.map(|max| i >= max).unwrap_or(false);
+            let too_old = self
+                .config
+                .max_age
+                .map(|max| now.duration_since(modified).unwrap_or_default() > max)
+                .unwrap_or(false);
+            if too_many || too_old {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+fn compress(path: &Path) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let output = File::create(gz_path(path))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+// Identifies the hour or day a timestamp falls into (UTC); 0 when rotation is not time based
+fn period_of(trigger: Trigger, time: SystemTime) -> u64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    match trigger {
+        Trigger::Hourly => secs / 3600,
+        Trigger::Daily => secs / 86_400,
+        Trigger::Never | Trigger::Size(_) => 0,
+    }
+}
+
+fn file_stamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{HumanFormat, Record};
+    use crate::level::Level;
+    use crate::sink::FileSink;
+    use crate::writer::{BackgroundWriter, OverflowPolicy, WriterConfig};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    // A fresh, empty `<tmp>/logger-test-<pid>-<n>/`
+    fn temp_dir() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "logger-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn read_gz(path: &Path) -> String {
+        let mut text = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    fn set_age(path: &Path, age: Duration) {
+        let file = OpenOptions::new().append(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn sized(max: u64) -> RotationConfig {
+        RotationConfig {
+            trigger: Trigger::Size(max),
+            ..RotationConfig::default()
+        }
+    }
+
+    #[test]
+    fn size_trigger_shifts_indexed_files() {
+        let dir = temp_dir();
+        let path = dir.join("app.log");
+        // Each line is 6 bytes with its newline, so two fit under the limit
+        let mut writer = RotatingFileWriter::new(&path, sized(12)).unwrap();
+        for line in ["aaaaa", "bbbbb", "ccccc", "ddddd", "eeeee"] {
+            writer.write_line(line).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(names(&dir), ["app.log", "app.log.1", "app.log.2"]);
+        assert_eq!(read(&path), "eeeee\n");
+        assert_eq!(read(&dir.join("app.log.1")), "ccccc\nddddd\n");
+        assert_eq!(read(&dir.join("app.log.2")), "aaaaa\nbbbbb\n");
+    }
+
+    #[test]
+    fn an_oversized_line_still_lands_in_an_empty_file() {
+        let dir = temp_dir();
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"), sized(4)).unwrap();
+        writer.write_line("longer than four").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(names(&dir), ["app.log"]);
+    }
+
+    #[test]
+    fn time_trigger_rotates_when_the_period_changes() {
+        let dir = temp_dir();
+        let path = dir.join("app.log");
+        let config = RotationConfig {
+            trigger: Trigger::Hourly,
+            naming: Naming::Timestamp,
+            ..RotationConfig::default()
+        };
+        let mut writer = RotatingFileWriter::new(&path, config).unwrap();
+        writer.write_line("this hour").unwrap();
+        writer.write_line("still this hour").unwrap();
+        assert_eq!(names(&dir), ["app.log"]);
+
+        // Pretend the file was opened an hour ago
+        writer.period -= 1;
+        writer.write_line("next hour").unwrap();
+        writer.flush().unwrap();
+
+        let names = names(&dir);
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert!(names[1].starts_with("app.log.20"), "{:?}", names);
+        assert_eq!(read(&dir.join(&names[1])), "this hour\nstill this hour\n");
+        assert_eq!(read(&path), "next hour\n");
+    }
+
+    #[test]
+    fn a_file_from_an_earlier_period_is_rotated_on_open() {
+        let dir = temp_dir();
+        let path = dir.join("app.log");
+        fs::write(&path, "yesterday\n").unwrap();
+        set_age(&path, Duration::from_secs(2 * 86_400));
+        let config = RotationConfig {
+            trigger: Trigger::Daily,
+            ..RotationConfig::default()
+        };
+
+        let mut writer = RotatingFileWriter::new(&path, config).unwrap();
+        writer.write_line("today").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(names(&dir), ["app.log", "app.log.1"]);
+        assert_eq!(read(&dir.join("app.log.1")), "yesterday\n");
+        assert_eq!(read(&path), "today\n");
+    }
+
+    #[test]
+    fn a_file_from_the_current_period_is_appended_to() {
+        let dir = temp_dir();
+        let path = dir.join("app.log");
+        fs::write(&path, "earlier today\n").unwrap();
+        let config = RotationConfig {
+            trigger: Trigger::Daily,
+            ..RotationConfig::default()
+        };
+
+        let mut writer = RotatingFileWriter::new(&path, config).unwrap();
+        writer.write_line("now").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(names(&dir), ["app.log"]);
+        assert_eq!(read(&path), "earlier today\nnow\n");
+    }
+
+    #[test]
+    fn compressed_files_keep_their_contents_and_shift_with_the_rest() {
+        let dir = temp_dir();
+        let config = RotationConfig {
+            compress: true,
+            ..sized(6)
+        };
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"), config).unwrap();
+        for line in ["aaaaa", "bbbbb", "ccccc"] {
+            writer.write_line(line).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(names(&dir), ["app.log", "app.log.1.gz", "app.log.2.gz"]);
+        assert_eq!(read_gz(&dir.join("app.log.1.gz")), "bbbbb\n");
+        assert_eq!(read_gz(&dir.join("app.log.2.gz")), "aaaaa\n");
+    }
+
+    #[test]
+    fn retention_keeps_only_the_newest_files() {
+        let dir = temp_dir();
+        let config = RotationConfig {
+            max_files: Some(2),
+            ..sized(6)
+        };
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"), config).unwrap();
+        for line in ["aaaaa", "bbbbb", "ccccc", "ddddd", "eeeee"] {
+            writer.write_line(line).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(names(&dir), ["app.log", "app.log.1", "app.log.2"]);
+        assert_eq!(read(&dir.join("app.log.1")), "ddddd\n");
+        assert_eq!(read(&dir.join("app.log.2")), "ccccc\n");
+    }
+
+    #[test]
+    fn retention_removes_files_past_the_maximum_age() {
+        let dir = temp_dir();
+        let config = RotationConfig {
+            max_age: Some(Duration::from_secs(3600)),
+            ..sized(6)
+        };
+        let mut writer = RotatingFileWriter::new(dir.join("app.log"), config).unwrap();
+        writer.write_line("aaaaa").unwrap();
+        writer.write_line("bbbbb").unwrap();
+        set_age(&dir.join("app.log.1"), Duration::from_secs(2 * 3600));
+        writer.write_line("ccccc").unwrap();
+        writer.flush().unwrap();
+
+        // The old file was shifted to .2 and then dropped; the fresh .1 survives
+        assert_eq!(names(&dir), ["app.log", "app.log.1"]);
+        assert_eq!(read(&dir.join("app.log.1")), "bbbbb\n");
+    }
+
+    #[test]
+    fn rotation_loses_no_lines_while_threads_are_logging() {
+        let dir = temp_dir();
+        let sink = FileSink::new(dir.join("app.log"), sized(2048)).unwrap();
+        let writer = BackgroundWriter::start(
+            vec![Box::new(sink)],
+            Box::new(HumanFormat),
+            WriterConfig {
+                capacity: 64,
+                overflow: OverflowPolicy::Block,
+            },
+        );
+        thread::scope(|scope| {
+            for t in 0..4 {
+                let writer = &writer;
+                scope.spawn(move || {
+                    for i in 0..250 {
+                        let message = format!("thread-{}-line-{}", t, i);
+                        writer
+                            .send(Record::new(Level::Info, "test", &message, Vec::new()))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        writer.shutdown().unwrap();
+
+        let names = names(&dir);
+        assert!(names.len() > 10, "{:?}", names);
+        let mut messages: Vec<String> = names
+            .iter()
+            .flat_map(|name| {
+                read(&dir.join(name))
+                    .lines()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .map(|line| line.rsplit(' ').next().unwrap().to_string())
+            .collect();
+        messages.sort();
+        let mut expected: Vec<String> = (0..4)
+            .flat_map(|t| (0..250).map(move |i| format!("thread-{}-line-{}", t, i)))
+            .collect();
+        expected.sort();
+        assert_eq!(messages, expected);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::format::{rfc3339, Record};
+use crate::level::Level;
+use crate::rotation::{RotatingFileWriter, RotationConfig};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+// Sink: a destination for formatted records. Sinks are owned by the background writer thread,
+// so they only need to be `Send`
+pub trait Sink: Send {
+    fn write(&mut self, record: &Record, line: &str) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct FileSink {
+    writer: RotatingFileWriter,
+}
+
+impl FileSink {
+    pub fn new(path: impl AsRef<Path>, rotation: RotationConfig) -> io::Result<Self> {
+        Ok(FileSink {
+            writer: RotatingFileWriter::new(path, rotation)?,
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn write(&mut self, _record: &Record, line: &str) -> io::Result<()> {
+        self.writer.write_line(line)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub struct ConsoleSink {
+    stream: Stream,
+}
+
+impl ConsoleSink {
+    pub fn new(stream: Stream) -> Self {
+        ConsoleSink { stream }
+    }
+}
+
+impl Sink for ConsoleSink {
+    fn write(&mut self, _record: &Record, line: &str) -> io::Result<()> {
+        match self.stream {
+            Stream::Stdout => writeln!(io::stdout().lock(), "{}", line),
+            Stream::Stderr => writeln!(io::stderr().lock(), "{}", line),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stream {
+            Stream::Stdout => io::stdout().flush(),
+            Stream::Stderr => io::stderr().flush(),
+        }
+    }
+}
+
+// Keeps the last `capacity` lines in memory (none at all for 0); clones share the same buffer,
+// so a test can hand one clone to the logger and inspect the other
+#[derive(Clone)]
+pub struct MemorySink {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl MemorySink {
+    pub fn new(capacity: usize) -> Self {
+        MemorySink {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Sink for MemorySink {
+    fn write(&mut self, _record: &Record, line: &str) -> io::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+        Ok(())
+    }
+}
+
+// Sends RFC 5424 syslog messages over UDP, e.g. to a local rsyslog on 127.0.0.1:514
+pub struct SyslogUdpSink {
+    socket: UdpSocket,
+    app_name: String,
+    hostname: String,
+}
+
+impl SyslogUdpSink {
+    const FACILITY_USER: u8 = 1;
+
+    pub fn new(target: impl ToSocketAddrs, app_name: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.connect(target)?;
+        Ok(SyslogUdpSink {
+            socket,
+            app_name: app_name.to_string(),
+            hostname: "localhost".to_string(),
+        })
+    }
+
+    fn severity(level: Level) -> u8 {
+        match level {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        }
+    }
+}
+
+impl Sink for SyslogUdpSink {
+    fn write(&mut self, record: &Record, _line: &str) -> io::Result<()> {
+        let priority = Self::FACILITY_USER * 8 + Self::severity(record.level);
+        let mut message = record.message.clone();
+        for (key, value) in &record.fields {
+            message.push_str(&format!(" {}={}", key, value));
+        }
+        let datagram = format!(
+            "<{}>1 {} {} {} {} {} - {}",
+            priority,
+            rfc3339(record.timestamp),
+            self.hostname,
+            self.app_name,
+            process::id(),
+            record.module.replace("::", "."),
+            message
+        );
+        self.socket.send(datagram.as_bytes()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(sink: &mut MemorySink, message: &str) {
+        let record = Record::new(Level::Info, "test", message, Vec::new());
+        sink.write(&record, message).unwrap();
+    }
+
+    #[test]
+    fn memory_sink_keeps_the_last_lines() {
+        let mut sink = MemorySink::new(2);
+        for message in ["a", "b", "c"] {
+            write(&mut sink, message);
+        }
+        assert_eq!(sink.lines(), ["b", "c"]);
+    }
+
+    #[test]
+    fn memory_sink_with_no_capacity_stores_nothing() {
+        let mut sink = MemorySink::new(0);
+        for message in ["a", "b", "c"] {
+            write(&mut sink, message);
+        }
+        assert!(sink.lines().is_empty());
+    }
+}
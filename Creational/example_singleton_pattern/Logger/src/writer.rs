@@ -0,0 +1,477 @@
+use crate::format::{Format, Record};
+use crate::sink::Sink;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Sinks = Vec<Box<dyn Sink>>;
+
+// What the writer thread hands back on shutdown, so the caller can keep writing synchronously
+pub type Output = (Sinks, Box<dyn Format>);
+
+// What `send` does when `capacity` records are already waiting for the writer thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Block,
+    DropNewest,
+    DropOldest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WriterConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig {
+            capacity: 1024,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+enum Command {
+    Write(Record),
+    SetSinks(Sinks),
+    SetFormat(Box<dyn Format>),
+    AddSink(Box<dyn Sink>),
+    Flush(mpsc::Sender<()>),
+    Shutdown,
+}
+
+// Only `Write` commands count towards the capacity and can be dropped; control commands are
+// always queued so a flush or shutdown can never be lost to the overflow policy
+struct Queue {
+    commands: VecDeque<Command>,
+    records: usize,
+    overflow: OverflowPolicy,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+// Bounded queue plus one thread that owns the sinks and the format. Callers only pay for
+// pushing a record; formatting and I/O happen on the writer thread, in the order the records
+// were queued. Sink and format changes travel through the same queue, so each record is
+// written with the sinks and format that were current when it was logged
+pub struct BackgroundWriter {
+    shared: Arc<Shared>,
+    handle: Mutex<Option<JoinHandle<Output>>>,
+}
+
+impl BackgroundWriter {
+    pub fn start(sinks: Sinks, format: Box<dyn Format>, config: WriterConfig) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                commands: VecDeque::new(),
+                records: 0,
+                overflow: config.overflow,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: config.capacity.max(1),
+            dropped: AtomicU64::new(0),
+        });
+        let worker = Arc::clone(&shared);
+        let handle = thread::Builder::new()
+            .name("log-writer".to_string())
+            .spawn(move || run(worker, sinks, format))
+            .unwrap();
+        BackgroundWriter {
+            shared,
+            handle: Mutex::new(Some(handle)),
+        }
+    }
+
+    // Hands the record back once the writer has been shut down, so the caller can still deliver it
+    pub fn send(&self, record: Record) -> Result<(), Record> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        while queue.records >= self.shared.capacity && !queue.closed {
+            match queue.overflow {
+                OverflowPolicy::Block => queue = self.shared.not_full.wait(queue).unwrap(),
+                OverflowPolicy::DropNewest => {
+                    self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                OverflowPolicy::DropOldest => {
+                    let oldest = queue
+                        .commands
+                        .iter()
+                        .position(|command| matches!(command, Command::Write(_)));
+                    if let Some(index) = oldest {
+                        queue.commands.remove(index);
+                        queue.records -= 1;
+                        self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        if queue.closed {
+            return Err(record);
+        }
+        queue.commands.push_back(Command::Write(record));
+        queue.records += 1;
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+
+    fn control(&self, command: Command) -> bool {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.closed {
+            return false;
+        }
+        queue.commands.push_back(command);
+        self.shared.not_empty.notify_one();
+        true
+    }
+
+    pub fn set_overflow(&self, overflow: OverflowPolicy) {
+        self.shared.queue.lock().unwrap().overflow = overflow;
+        self.shared.not_full.notify_all();
+    }
+
+    // Takes effect after every record queued before this call has been written
+    pub fn set_sinks(&self, sinks: Sinks) -> bool {
+        self.control(Command::SetSinks(sinks))
+    }
+
+    pub fn add_sink(&self, sink: Box<dyn Sink>) -> bool {
+        self.control(Command::AddSink(sink))
+    }
+
+    // Like `set_sinks`: records queued before this call keep the old format
+    pub fn set_format(&self, format: Box<dyn Format>) -> bool {
+        self.control(Command::SetFormat(format))
+    }
+
+    // Blocks until every record queued before this call has been written and the sinks flushed
+    pub fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if self.control(Command::Flush(done)) {
+            let _ = wait.recv();
+        }
+    }
+
+    // Drains the queue, flushes the sinks and stops the thread. Returns the sinks and format so
+    // the caller can keep writing synchronously; `None` if the writer was already shut down
+    pub fn shutdown(&self) -> Option<Output> {
+        let handle = self.handle.lock().unwrap().take()?;
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.commands.push_back(Command::Shutdown);
+            queue.closed = true;
+            self.shared.not_empty.notify_one();
+            self.shared.not_full.notify_all();
+        }
+        handle.join().ok()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+pub fn write_to_sinks(sinks: &mut [Box<dyn Sink>], format: &dyn Format, record: &Record) {
+    let line = format.format(record);
+    for sink in sinks.iter_mut() {
+        if let Err(e) = sink.write(record, &line) {
+            eprintln!("log sink error: {}", e);
+        }
+    }
+}
+
+fn flush_sinks(sinks: &mut [Box<dyn Sink>]) {
+    for sink in sinks.iter_mut() {
+        if let Err(e) = sink.flush() {
+            eprintln!("log sink error: {}", e);
+        }
+    }
+}
+
+fn run(shared: Arc<Shared>, mut sinks: Sinks, mut format: Box<dyn Format>) -> Output {
+    loop {
+        // Take everything that is queued in one go, so producers are blocked for as short as possible
+        let batch: Vec<Command> = {
+            let mut queue = shared.queue.lock().unwrap();
+            while queue.commands.is_empty() {
+                queue = shared.not_empty.wait(queue).unwrap();
+            }
+            queue.records = 0;
+            shared.not_full.notify_all();
+            queue.commands.drain(..).collect()
+        };
+
+        for command in batch {
+            match command {
+                Command::Write(record) => write_to_sinks(&mut sinks, &*format, &record),
+                Command::SetSinks(new_sinks) => {
+                    flush_sinks(&mut sinks);
+                    sinks = new_sinks;
+                }
+                Command::AddSink(sink) => sinks.push(sink),
+                Command::SetFormat(new_format) => format = new_format,
+                Command::Flush(done) => {
+                    flush_sinks(&mut sinks);
+                    let _ = done.send(());
+                }
+                Command::Shutdown => {
+                    flush_sinks(&mut sinks);
+                    return (sinks, format);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{HumanFormat, JsonFormat};
+    use crate::level::Level;
+    use crate::sink::MemorySink;
+    use std::io;
+
+    // Holds the writer thread at its first record until the test opens the gate, so every
+    // later command is still queued when it is issued. `held` reports that the writer has
+    // taken that first record off the queue
+    struct GatedSink {
+        gate: Option<(mpsc::Sender<()>, mpsc::Receiver<()>)>,
+        inner: MemorySink,
+    }
+
+    impl Sink for GatedSink {
+        fn write(&mut self, record: &Record, line: &str) -> io::Result<()> {
+            if let Some((held, gate)) = self.gate.take() {
+                let _ = held.send(());
+                let _ = gate.recv();
+            }
+            self.inner.write(record, line)
+        }
+    }
+
+    // A writer stuck on its first record, the sink it writes to and the gate that releases it
+    struct Gated {
+        writer: BackgroundWriter,
+        memory: MemorySink,
+        open: mpsc::Sender<()>,
+    }
+
+    fn gated(config: WriterConfig) -> Gated {
+        let memory = MemorySink::new(100);
+        let (open, gate) = mpsc::channel();
+        let (held, holding) = mpsc::channel();
+        let sink = GatedSink {
+            gate: Some((held, gate)),
+            inner: memory.clone(),
+        };
+        let writer = BackgroundWriter::start(vec![Box::new(sink)], Box::new(HumanFormat), config);
+        writer.send(record("first")).unwrap();
+        holding.recv().unwrap();
+        Gated {
+            writer,
+            memory,
+            open,
+        }
+    }
+
+    fn record(message: &str) -> Record {
+        Record::new(Level::Info, "test", message, Vec::new())
+    }
+
+    // The message at the end of each line a sink received
+    fn messages(memory: &MemorySink) -> Vec<String> {
+        memory
+            .lines()
+            .into_iter()
+            .map(|line| line.rsplit(' ').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn format_change_applies_to_records_logged_after_it() {
+        let Gated {
+            writer,
+            memory,
+            open,
+        } = gated(WriterConfig::default());
+
+        writer.send(record("second")).unwrap();
+        writer.set_format(Box::new(JsonFormat));
+        writer.send(record("third")).unwrap();
+        open.send(()).unwrap();
+        writer.flush();
+
+        let lines = memory.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(!lines[0].starts_with('{'), "{}", lines[0]);
+        assert!(!lines[1].starts_with('{'), "{}", lines[1]);
+        assert!(lines[2].starts_with('{'), "{}", lines[2]);
+    }
+
+    #[test]
+    fn drop_newest_discards_records_that_do_not_fit() {
+        let Gated {
+            writer,
+            memory,
+            open,
+        } = gated(WriterConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::DropNewest,
+        });
+        for message in ["a", "b", "c", "d"] {
+            writer.send(record(message)).unwrap();
+        }
+        assert_eq!(writer.dropped(), 2);
+        open.send(()).unwrap();
+        writer.flush();
+        assert_eq!(messages(&memory), ["first", "a", "b"]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room_for_new_records() {
+        let Gated {
+            writer,
+            memory,
+            open,
+        } = gated(WriterConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::DropOldest,
+        });
+        for message in ["a", "b", "c", "d"] {
+            writer.send(record(message)).unwrap();
+        }
+        assert_eq!(writer.dropped(), 2);
+        open.send(()).unwrap();
+        writer.flush();
+        assert_eq!(messages(&memory), ["first", "c", "d"]);
+    }
+
+    #[test]
+    fn block_waits_for_room_and_loses_nothing() {
+        let Gated {
+            writer,
+            memory,
+            open,
+        } = gated(WriterConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::Block,
+        });
+        let (sent, all_sent) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for message in ["a", "b", "c", "d"] {
+                    writer.send(record(message)).unwrap();
+                }
+                sent.send(()).unwrap();
+            });
+            // "c" cannot be queued until the writer gets going again
+            assert!(all_sent
+                .recv_timeout(std::time::Duration::from_millis(100))
+                .is_err());
+            open.send(()).unwrap();
+            all_sent.recv().unwrap();
+        });
+        writer.flush();
+        assert_eq!(writer.dropped(), 0);
+        assert_eq!(messages(&memory), ["first", "a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn flush_is_never_dropped_by_the_overflow_policy() {
+        let Gated {
+            writer,
+            memory,
+            open,
+        } = gated(WriterConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::DropNewest,
+        });
+        writer.send(record("a")).unwrap();
+        writer.send(record("b")).unwrap();
+        let (flushed, done) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                writer.flush();
+                flushed.send(()).unwrap();
+            });
+            open.send(()).unwrap();
+            done.recv().unwrap();
+        });
+        assert_eq!(messages(&memory), ["first", "a"]);
+    }
+
+    #[test]
+    fn flush_delivers_everything_logged_before_it() {
+        let memory = MemorySink::new(1000);
+        let writer = BackgroundWriter::start(
+            vec![Box::new(memory.clone())],
+            Box::new(HumanFormat),
+            WriterConfig {
+                capacity: 8,
+                overflow: OverflowPolicy::Block,
+            },
+        );
+        thread::scope(|scope| {
+            for t in 0..4 {
+                let writer = &writer;
+                scope.spawn(move || {
+                    for i in 0..100 {
+                        writer.send(record(&format!("{}-{}", t, i))).unwrap();
+                    }
+                });
+            }
+        });
+        writer.flush();
+        let lines = messages(&memory);
+        assert_eq!(lines.len(), 400);
+        // Each thread's records arrive in the order it logged them
+        for t in 0..4 {
+            let own: Vec<&String> = lines
+                .iter()
+                .filter(|line| line.starts_with(&format!("{}-", t)))
+                .collect();
+            let expected: Vec<String> = (0..100).map(|i| format!("{}-{}", t, i)).collect();
+            assert_eq!(own, expected.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn shutdown_delivers_everything_and_hands_back_the_sinks() {
+        let memory = MemorySink::new(1000);
+        let writer = BackgroundWriter::start(
+            vec![Box::new(memory.clone())],
+            Box::new(HumanFormat),
+            WriterConfig::default(),
+        );
+        for i in 0..200 {
+            writer.send(record(&i.to_string())).unwrap();
+        }
+        let (sinks, _format) = writer.shutdown().unwrap();
+        assert_eq!(sinks.len(), 1);
+        assert_eq!(memory.lines().len(), 200);
+
+        // Afterwards records come back to the caller instead of vanishing
+        let rejected = writer.send(record("late")).unwrap_err();
+        assert_eq!(rejected.message, "late");
+        assert!(writer.shutdown().is_none());
+        writer.flush();
+        assert!(!writer.set_format(Box::new(JsonFormat)));
+    }
+}